@@ -0,0 +1,183 @@
+// A stand-in for Enderfall Hub's IPC endpoint, for exercising the Hub integration without the Hub.
+//
+//   cargo run --example hub_stand_in -- [--endpoint <path>] [--token <token.json>] [--refuse]
+//
+// It listens where the app looks by default, so a running stand-in makes "Open Enderfall Hub"
+// focus instead of launch. `--token` serves that file's JSON to launch token requests, and
+// `--refuse` answers every focus or token request with an error.
+//
+// Tokens are `{ "payload", "signature" }` envelopes. A debug build of the app accepts ones signed
+// with a development key when ENDERFALL_LAUNCH_TOKEN_KEY holds its base64 public key.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+struct Options {
+  endpoint: String,
+  token: Option<Value>,
+  refuse: bool,
+}
+
+#[cfg(unix)]
+fn default_endpoint() -> String {
+  tauri::api::path::data_dir()
+    .map(|dir| dir.join("Enderfall"))
+    .unwrap_or_else(std::env::temp_dir)
+    .join("hub.sock")
+    .to_string_lossy()
+    .to_string()
+}
+
+#[cfg(windows)]
+fn default_endpoint() -> String {
+  r"\\.\pipe\enderfall-hub".to_string()
+}
+
+fn parse_options() -> Result<Options, String> {
+  let mut options = Options {
+    endpoint: default_endpoint(),
+    token: None,
+    refuse: false,
+  };
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--endpoint" => {
+        options.endpoint = args.next().ok_or("--endpoint needs a value.")?;
+      }
+      "--token" => {
+        let path = args.next().ok_or("--token needs a value.")?;
+        let raw = fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+        options.token = Some(serde_json::from_str(&raw).map_err(|e| format!("{path}: {e}"))?);
+      }
+      "--refuse" => options.refuse = true,
+      _ => return Err(format!("Unknown option {arg}.")),
+    }
+  }
+  Ok(options)
+}
+
+fn reply(request: &Value, options: &Options) -> Value {
+  let kind = request.get("type").and_then(Value::as_str).unwrap_or("");
+  let app_id = request.get("appId").and_then(Value::as_str).unwrap_or("?");
+  eprintln!("{kind} from {app_id}");
+  match kind {
+    "ping" => json!({ "ok": true }),
+    _ if options.refuse => json!({ "ok": false, "error": "The stand-in Hub was told to refuse." }),
+    "focus" => json!({ "ok": true }),
+    "launchToken" => match &options.token {
+      Some(token) => json!({ "ok": true, "token": token }),
+      None => json!({ "ok": false, "error": "Not signed in." }),
+    },
+    _ => json!({ "ok": false, "error": format!("Unknown request {kind}.") }),
+  }
+}
+
+fn serve_one(stream: impl Read + Write, options: &Options) -> io::Result<()> {
+  let mut reader = BufReader::new(stream);
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let response = match serde_json::from_str::<Value>(&line) {
+    Ok(request) => reply(&request, options),
+    Err(e) => json!({ "ok": false, "error": e.to_string() }),
+  };
+  let mut raw = serde_json::to_vec(&response)?;
+  raw.push(b'\n');
+  reader.get_mut().write_all(&raw)?;
+  reader.get_mut().flush()
+}
+
+#[cfg(unix)]
+fn serve(options: &Options) -> io::Result<()> {
+  let path = std::path::Path::new(&options.endpoint);
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  // A socket left behind by a previous run would make bind fail.
+  let _ = fs::remove_file(path);
+  let listener = std::os::unix::net::UnixListener::bind(path)?;
+  eprintln!("Stand-in Hub listening on {}", options.endpoint);
+  for stream in listener.incoming() {
+    if let Err(e) = stream.and_then(|stream| serve_one(stream, options)) {
+      eprintln!("error: {e}");
+    }
+  }
+  Ok(())
+}
+
+#[cfg(windows)]
+fn accept(endpoint: &str) -> io::Result<fs::File> {
+  use std::ffi::{c_void, OsStr};
+  use std::os::windows::ffi::OsStrExt;
+  use std::os::windows::io::FromRawHandle;
+  #[link(name = "kernel32")]
+  extern "system" {
+    fn CreateNamedPipeW(
+      name: *const u16,
+      open_mode: u32,
+      pipe_mode: u32,
+      max_instances: u32,
+      out_buffer_size: u32,
+      in_buffer_size: u32,
+      default_timeout: u32,
+      security_attributes: *mut c_void,
+    ) -> *mut c_void;
+    fn ConnectNamedPipe(pipe: *mut c_void, overlapped: *mut c_void) -> i32;
+  }
+  const PIPE_ACCESS_DUPLEX: u32 = 3;
+  const PIPE_TYPE_BYTE: u32 = 0;
+  const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+  const ERROR_PIPE_CONNECTED: i32 = 535;
+  let name: Vec<u16> = OsStr::new(endpoint)
+    .encode_wide()
+    .chain(std::iter::once(0))
+    .collect();
+  let pipe = unsafe {
+    CreateNamedPipeW(
+      name.as_ptr(),
+      PIPE_ACCESS_DUPLEX,
+      PIPE_TYPE_BYTE,
+      PIPE_UNLIMITED_INSTANCES,
+      4096,
+      4096,
+      0,
+      std::ptr::null_mut(),
+    )
+  };
+  if pipe as isize == -1 {
+    return Err(io::Error::last_os_error());
+  }
+  let file = unsafe { fs::File::from_raw_handle(pipe) };
+  if unsafe { ConnectNamedPipe(pipe, std::ptr::null_mut()) } == 0 {
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+      return Err(error);
+    }
+  }
+  Ok(file)
+}
+
+#[cfg(windows)]
+fn serve(options: &Options) -> io::Result<()> {
+  eprintln!("Stand-in Hub listening on {}", options.endpoint);
+  loop {
+    if let Err(e) = accept(&options.endpoint).and_then(|pipe| serve_one(pipe, options)) {
+      eprintln!("error: {e}");
+    }
+  }
+}
+
+fn main() {
+  let options = match parse_options() {
+    Ok(options) => options,
+    Err(message) => {
+      eprintln!("error: {message}");
+      std::process::exit(2);
+    }
+  };
+  if let Err(e) = serve(&options) {
+    eprintln!("error: {e}");
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,506 @@
+use crate::character::{RelationshipEntry, RelationshipKind, TabData, SNAPSHOT_VERSION};
+use crate::entitlement::Entitlements;
+use crate::library::{now_millis, CharacterRecord, Library};
+use crate::migrate;
+use crate::portraits::{decode_data_url, extension, mime_type, PortraitStore};
+use crate::rules;
+use crate::storage::write_atomic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const BUNDLE_FORMAT: &str = "enderfall-character-bundle";
+pub const BUNDLE_SCHEMA_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+  pub format: String,
+  pub schema_version: u32,
+  pub snapshot_version: u32,
+  pub created_at: u64,
+  pub character: BundledCharacter,
+  #[serde(default)]
+  pub related: Vec<BundledCharacter>,
+  pub files: BTreeMap<String, BundledFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledCharacter {
+  pub title: String,
+  pub sheet: String,
+  pub portrait: Option<String>,
+  // Relationship entry ids on the main character that point at this sheet.
+  #[serde(default)]
+  pub relationship_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundledFile {
+  pub sha256: String,
+  pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+  pub path: String,
+  pub related: Vec<String>,
+  pub unresolved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedBundle {
+  pub record: CharacterRecord,
+  pub related: Vec<CharacterRecord>,
+}
+
+struct BundleWriter {
+  zip: ZipWriter<Cursor<Vec<u8>>>,
+  files: BTreeMap<String, BundledFile>,
+}
+
+impl BundleWriter {
+  fn add(&mut self, name: &str, bytes: &[u8]) -> Result<(), String> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    self
+      .zip
+      .start_file(name, options)
+      .map_err(|e| e.to_string())?;
+    self.zip.write_all(bytes).map_err(|e| e.to_string())?;
+    self.files.insert(
+      name.to_string(),
+      BundledFile {
+        sha256: sha256_hex(bytes),
+        size: bytes.len() as u64,
+      },
+    );
+    Ok(())
+  }
+
+  // Writes the sheet with its portrait split out into a real image file.
+  fn add_character(
+    &mut self,
+    portraits: &PortraitStore,
+    stem: &str,
+    record: &CharacterRecord,
+    relationship_ids: Vec<String>,
+  ) -> Result<BundledCharacter, String> {
+    let mut record = record.clone();
+    // Relationship entries keep their cached portraits inline; the store is not in the bundle.
+    portraits.inline_record(&mut record);
+    let mut portrait = None;
+    if let Some(TabData::Sheet(snapshot)) = &mut record.data {
+      let image = snapshot.portrait.as_deref().and_then(decode_data_url);
+      if let Some((mime, bytes)) = image.and_then(|bytes| Some((mime_type(&bytes)?, bytes))) {
+        let name = format!("{stem}.{}", extension(mime));
+        self.add(&name, &bytes)?;
+        snapshot.portrait = None;
+        portrait = Some(name);
+      }
+    }
+    record.id = String::new();
+    record.updated_at = 0;
+    let sheet = format!("{stem}.json");
+    let raw = serde_json::to_vec_pretty(&record).map_err(|e| e.to_string())?;
+    self.add(&sheet, &raw)?;
+    Ok(BundledCharacter {
+      title: record.title,
+      sheet,
+      portrait,
+      relationship_ids,
+    })
+  }
+}
+
+pub fn export_bundle(
+  library: &Library,
+  portraits: &PortraitStore,
+  record: &CharacterRecord,
+  path: &Path,
+) -> Result<ExportReport, String> {
+  // Built in memory and written in one go, so a failed export never leaves a truncated bundle
+  // where an earlier one was.
+  let mut writer = BundleWriter {
+    zip: ZipWriter::new(Cursor::new(Vec::new())),
+    files: BTreeMap::new(),
+  };
+  let character = writer.add_character(portraits, "character", record, Vec::new())?;
+
+  let mut related: Vec<(CharacterRecord, Vec<String>)> = Vec::new();
+  let mut unresolved = Vec::new();
+  for kind in RelationshipKind::ALL {
+    for entry in record.relationships.get(kind) {
+      match find_related(library, record, entry) {
+        Some(target) => match related.iter_mut().find(|(known, _)| known.id == target.id) {
+          Some((_, ids)) => ids.push(entry.id.clone()),
+          None => related.push((target, vec![entry.id.clone()])),
+        },
+        None => unresolved.push(entry.name.clone()),
+      }
+    }
+  }
+  let mut bundled_related = Vec::new();
+  for (index, (target, ids)) in related.iter().enumerate() {
+    let stem = format!("related/{}", index + 1);
+    bundled_related.push(writer.add_character(portraits, &stem, target, ids.clone())?);
+  }
+
+  let manifest = BundleManifest {
+    format: BUNDLE_FORMAT.to_string(),
+    schema_version: BUNDLE_SCHEMA_VERSION,
+    snapshot_version: SNAPSHOT_VERSION,
+    created_at: now_millis(),
+    character,
+    related: bundled_related,
+    files: writer.files.clone(),
+  };
+  let raw = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+  let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+  writer
+    .zip
+    .start_file(MANIFEST_FILE, options)
+    .map_err(|e| e.to_string())?;
+  writer.zip.write_all(&raw).map_err(|e| e.to_string())?;
+  let bytes = writer.zip.finish().map_err(|e| e.to_string())?.into_inner();
+  write_atomic(path, &bytes)?;
+
+  Ok(ExportReport {
+    path: path.to_string_lossy().to_string(),
+    related: related
+      .into_iter()
+      .map(|(target, _)| target.title)
+      .collect(),
+    unresolved,
+  })
+}
+
+// Entries linked by UUID resolve directly; older ones fall back to their name and source file.
+fn find_related(
+  library: &Library,
+  owner: &CharacterRecord,
+  entry: &RelationshipEntry,
+) -> Option<CharacterRecord> {
+  if let Some(uuid) = &entry.target_uuid {
+    return library.find_by_uuid(uuid);
+  }
+  let source_stem = entry
+    .source_file
+    .as_deref()
+    .and_then(|file| Path::new(file).file_stem())
+    .and_then(|stem| stem.to_str())
+    .map(str::to_lowercase);
+  let name = entry.name.trim().to_lowercase();
+  library
+    .records()
+    .into_iter()
+    .filter(|candidate| candidate.id != owner.id)
+    .find(|candidate| {
+      let snapshot_name = match &candidate.data {
+        Some(TabData::Sheet(snapshot)) => snapshot.name().map(str::to_lowercase),
+        _ => None,
+      };
+      snapshot_name.as_deref() == Some(name.as_str())
+        || source_stem.as_deref() == Some(candidate.title.to_lowercase().as_str())
+    })
+}
+
+pub fn import_bundle(
+  library: &Library,
+  portraits: &PortraitStore,
+  path: &Path,
+) -> Result<ImportedBundle, String> {
+  let file = File::open(path).map_err(|e| e.to_string())?;
+  let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+  let manifest: BundleManifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_FILE)?)
+    .map_err(|e| format!("Bundle manifest is unreadable: {e}"))?;
+  if manifest.format != BUNDLE_FORMAT {
+    return Err("This file is not a character bundle.".to_string());
+  }
+  if manifest.schema_version > BUNDLE_SCHEMA_VERSION {
+    return Err(format!(
+      "Bundle schema version {} is newer than supported version {}.",
+      manifest.schema_version, BUNDLE_SCHEMA_VERSION
+    ));
+  }
+
+  let stamp = now_millis();
+  let mut record = read_character(&mut archive, portraits, &manifest, &manifest.character)?;
+  record.id = format!("tab-{stamp}-0");
+  // A character already in the library keeps its UUID; the imported copy gets a new one, and the
+  // related sheets that pointed at the old one follow it.
+  let mut renamed = None;
+  if let Some(TabData::Sheet(snapshot)) = &mut record.data {
+    if let Some(uuid) = snapshot
+      .uuid
+      .clone()
+      .filter(|uuid| library.find_by_uuid(uuid).is_some())
+    {
+      let fresh = Uuid::new_v4().to_string();
+      snapshot.uuid = Some(fresh.clone());
+      renamed = Some((uuid, fresh));
+    }
+  }
+  let mut related = Vec::new();
+  for (index, bundled) in manifest.related.iter().enumerate() {
+    let mut target = read_character(&mut archive, portraits, &manifest, bundled)?;
+    let target = match target.uuid().and_then(|uuid| library.find_by_uuid(uuid)) {
+      Some(existing) => existing,
+      None => {
+        if let Some((old, fresh)) = &renamed {
+          for entry in relationship_entries(&mut target) {
+            if entry.target_uuid.as_deref() == Some(old.as_str()) {
+              entry.target_uuid = Some(fresh.clone());
+            }
+          }
+        }
+        target.id = format!("tab-{stamp}-{}", index + 1);
+        library.save(target)?
+      }
+    };
+    // The main sheet's entries for this character point at it by UUID from now on.
+    if let Some(uuid) = target.uuid() {
+      for entry in relationship_entries(&mut record) {
+        if bundled.relationship_ids.contains(&entry.id) {
+          entry.target_uuid = Some(uuid.to_string());
+        }
+      }
+    }
+    related.push(target);
+  }
+  Ok(ImportedBundle { record, related })
+}
+
+// Relationship entries on the record and on the sheet it carries.
+fn relationship_entries(record: &mut CharacterRecord) -> Vec<&mut RelationshipEntry> {
+  let mut entries: Vec<&mut RelationshipEntry> = Vec::new();
+  let sheet = match &mut record.data {
+    Some(TabData::Sheet(snapshot)) => snapshot.relationships.as_mut(),
+    _ => None,
+  };
+  for relationships in sheet.into_iter().chain([&mut record.relationships]) {
+    entries.extend(relationships.entries_mut());
+  }
+  entries
+}
+
+fn read_character(
+  archive: &mut ZipArchive<File>,
+  portraits: &PortraitStore,
+  manifest: &BundleManifest,
+  bundled: &BundledCharacter,
+) -> Result<CharacterRecord, String> {
+  let raw = read_verified(archive, manifest, &bundled.sheet)?;
+  let mut value: Value = serde_json::from_slice(&raw).map_err(|e| e.to_string())?;
+  if let Some(data) = value.get_mut("data").filter(|data| !data.is_null()) {
+    let report = migrate::migrate_value(data.take())?;
+    if !report.blank {
+      rules::validate_value(&report.snapshot).into_result()?;
+    }
+    *data = report.snapshot;
+  }
+  let mut record: CharacterRecord = serde_json::from_value(value).map_err(|e| e.to_string())?;
+  if let Some(portrait) = &bundled.portrait {
+    let bytes = read_verified(archive, manifest, portrait)?;
+    if let Some(TabData::Sheet(snapshot)) = &mut record.data {
+      snapshot.portrait = Some(portraits.put(&bytes, None)?.hash);
+    }
+  }
+  portraits.intern_record(&mut record)?;
+  Ok(record)
+}
+
+fn read_verified(
+  archive: &mut ZipArchive<File>,
+  manifest: &BundleManifest,
+  name: &str,
+) -> Result<Vec<u8>, String> {
+  let expected = manifest
+    .files
+    .get(name)
+    .ok_or_else(|| format!("Bundle manifest does not list {name}."))?;
+  let bytes = read_entry(archive, name)?;
+  if bytes.len() as u64 != expected.size || sha256_hex(&bytes) != expected.sha256 {
+    return Err(format!(
+      "Checksum mismatch for {name}; the bundle is damaged."
+    ));
+  }
+  Ok(bytes)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+  let entry = archive
+    .by_name(name)
+    .map_err(|_| format!("Bundle is missing {name}."))?;
+  let mut bytes = Vec::new();
+  entry
+    .take(MAX_ENTRY_BYTES)
+    .read_to_end(&mut bytes)
+    .map_err(|e| e.to_string())?;
+  Ok(bytes)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(bytes))
+}
+
+#[tauri::command]
+pub fn bundle_export(
+  library: tauri::State<'_, Library>,
+  portraits: tauri::State<'_, PortraitStore>,
+  entitlements: tauri::State<'_, Entitlements>,
+  record: CharacterRecord,
+  path: String,
+) -> Result<ExportReport, String> {
+  entitlements.require_premium()?;
+  export_bundle(&library, &portraits, &record, Path::new(&path))
+}
+
+#[tauri::command]
+pub fn bundle_import(
+  library: tauri::State<'_, Library>,
+  portraits: tauri::State<'_, PortraitStore>,
+  entitlements: tauri::State<'_, Entitlements>,
+  path: String,
+) -> Result<ImportedBundle, String> {
+  entitlements.require_premium()?;
+  import_bundle(&library, &portraits, Path::new(&path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::CharacterSnapshot;
+  use std::fs;
+
+  fn sheet(id: &str, name: &str, uuid: &str) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(uuid.to_string()),
+      ..Default::default()
+    };
+    snapshot.identity.insert("Name".into(), name.into());
+    CharacterRecord {
+      id: id.into(),
+      title: name.into(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..Default::default()
+    }
+  }
+
+  fn link(record: &mut CharacterRecord, entry_id: &str, target: &CharacterRecord) {
+    record.relationships.friends.push(RelationshipEntry {
+      id: entry_id.into(),
+      name: target.title.clone(),
+      target_uuid: target.uuid().map(str::to_string),
+      ..Default::default()
+    });
+  }
+
+  // Copies the bundle with one entry's bytes changed and the manifest left as it was.
+  fn tamper(from: &Path, to: &Path, name: &str) {
+    let mut archive = ZipArchive::new(File::open(from).unwrap()).unwrap();
+    let mut zip = ZipWriter::new(File::create(to).unwrap());
+    for index in 0..archive.len() {
+      let mut entry = archive.by_index(index).unwrap();
+      let entry_name = entry.name().to_string();
+      let mut bytes = Vec::new();
+      entry.read_to_end(&mut bytes).unwrap();
+      if entry_name == name {
+        bytes = String::from_utf8(bytes)
+          .unwrap()
+          .replace("Ada", "Eve")
+          .into_bytes();
+      }
+      zip.start_file(entry_name, FileOptions::default()).unwrap();
+      zip.write_all(&bytes).unwrap();
+    }
+    zip.finish().unwrap();
+  }
+
+  #[test]
+  fn rejects_a_bundle_whose_files_do_not_match_the_manifest() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path();
+    let library = Library::new(dir.join("library"));
+    let portraits = PortraitStore::new(dir.join("portraits"));
+    let record = sheet("a", "Ada", "11111111-1111-4111-8111-111111111111");
+    let bundle = dir.join("ada.ecc");
+    export_bundle(&library, &portraits, &record, &bundle).unwrap();
+    assert!(import_bundle(&library, &portraits, &bundle).is_ok());
+
+    let damaged = dir.join("damaged.ecc");
+    tamper(&bundle, &damaged, "character.json");
+    let error = import_bundle(&library, &portraits, &damaged).unwrap_err();
+    assert!(
+      error.contains("Checksum mismatch for character.json"),
+      "{error}"
+    );
+  }
+
+  #[test]
+  fn related_sheets_follow_a_reassigned_uuid() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path();
+    let library = Library::new(dir.join("library"));
+    let portraits = PortraitStore::new(dir.join("portraits"));
+    let mut ada = sheet("a", "Ada", "11111111-1111-4111-8111-111111111111");
+    let mut bo = sheet("b", "Bo", "22222222-2222-4222-8222-222222222222");
+    link(&mut bo, "bo-ada", &ada);
+    let bo = library.save(bo).unwrap();
+    link(&mut ada, "ada-bo", &bo);
+    let ada = library.save(ada).unwrap();
+    let bundle = dir.join("ada.ecc");
+    export_bundle(&library, &portraits, &ada, &bundle).unwrap();
+
+    // Imported elsewhere: Ada exists there already, Bo does not.
+    let other = Library::new(dir.join("other"));
+    other.save(ada.clone()).unwrap();
+    let imported = import_bundle(&other, &portraits, &bundle).unwrap();
+    let fresh = imported.record.uuid().unwrap();
+    assert_ne!(fresh, ada.uuid().unwrap());
+    let bo = &imported.related[0];
+    assert_eq!(
+      bo.relationships.friends[0].target_uuid.as_deref(),
+      Some(fresh)
+    );
+    assert_eq!(
+      imported.record.relationships.friends[0]
+        .target_uuid
+        .as_deref(),
+      bo.uuid()
+    );
+  }
+
+  #[test]
+  fn export_replaces_an_existing_bundle_whole() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path().join("out");
+    let library = Library::new(scratch.path().join("library"));
+    let portraits = PortraitStore::new(scratch.path().join("portraits"));
+    let bundle = dir.join("ada.ecc");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&bundle, vec![0u8; 1 << 20]).unwrap();
+    let record = sheet("a", "Ada", "11111111-1111-4111-8111-111111111111");
+    export_bundle(&library, &portraits, &record, &bundle).unwrap();
+    assert!(fs::metadata(&bundle).unwrap().len() < 1 << 20);
+    let imported = import_bundle(&library, &portraits, &bundle).unwrap();
+    assert_eq!(imported.record.title, "Ada");
+    let names: Vec<_> = fs::read_dir(&dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect();
+    assert_eq!(names, ["ada.ecc"]);
+  }
+}
@@ -0,0 +1,253 @@
+use crate::entitlement::Entitlements;
+use crate::migrate::{self, AppliedMigration};
+use crate::portraits::PortraitStore;
+use crate::rules;
+use crate::storage::write_json_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CharacterSnapshot {
+  pub version: u32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub uuid: Option<String>,
+  pub identity: BTreeMap<String, String>,
+  pub notes: Vec<Note>,
+  pub body: StatSection,
+  pub skills: StatSection,
+  pub priorities: StatSection,
+  pub mind: PersonaSection,
+  pub social: PersonaSection,
+  pub portrait: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub relationships: Option<Relationships>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub relationship_selection: Option<RelationshipSelection>,
+}
+
+impl Default for CharacterSnapshot {
+  fn default() -> Self {
+    Self {
+      version: SNAPSHOT_VERSION,
+      uuid: None,
+      identity: BTreeMap::new(),
+      notes: Vec::new(),
+      body: StatSection::default(),
+      skills: StatSection::default(),
+      priorities: StatSection::default(),
+      mind: PersonaSection::default(),
+      social: PersonaSection::default(),
+      portrait: None,
+      relationships: None,
+      relationship_selection: None,
+    }
+  }
+}
+
+impl CharacterSnapshot {
+  pub fn name(&self) -> Option<&str> {
+    std::iter::once("Name")
+      .chain(rules::NAME_ALIASES)
+      .filter_map(|key| self.identity.get(key))
+      .map(|value| value.trim())
+      .find(|value| !value.is_empty())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlankTab {
+  #[serde(rename = "__blank")]
+  pub blank: bool,
+  pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TabData {
+  Blank(BlankTab),
+  Sheet(Box<CharacterSnapshot>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Section {
+  Body,
+  Skills,
+  Priorities,
+  Mind,
+  Social,
+}
+
+impl Section {
+  pub fn key(self) -> &'static str {
+    match self {
+      Section::Body => "body",
+      Section::Skills => "skills",
+      Section::Priorities => "priorities",
+      Section::Mind => "mind",
+      Section::Social => "social",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Note {
+  pub title: String,
+  pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatSection {
+  pub stats: BTreeMap<String, u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonaSection {
+  #[serde(flatten)]
+  pub stats: StatSection,
+  pub sliders: BTreeMap<String, u8>,
+  pub traits: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RelationshipEntry {
+  pub id: String,
+  pub name: String,
+  pub portrait: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub relation: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source_file: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub target_uuid: Option<String>,
+  pub added_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Relationships {
+  pub family: Vec<RelationshipEntry>,
+  pub friends: Vec<RelationshipEntry>,
+  pub love: Vec<RelationshipEntry>,
+  pub hate: Vec<RelationshipEntry>,
+}
+
+impl Relationships {
+  pub fn get(&self, kind: RelationshipKind) -> &[RelationshipEntry] {
+    match kind {
+      RelationshipKind::Family => &self.family,
+      RelationshipKind::Friends => &self.friends,
+      RelationshipKind::Love => &self.love,
+      RelationshipKind::Hate => &self.hate,
+    }
+  }
+
+  pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut RelationshipEntry> {
+    self
+      .family
+      .iter_mut()
+      .chain(self.friends.iter_mut())
+      .chain(self.love.iter_mut())
+      .chain(self.hate.iter_mut())
+  }
+
+  pub fn get_mut(&mut self, kind: RelationshipKind) -> &mut Vec<RelationshipEntry> {
+    match kind {
+      RelationshipKind::Family => &mut self.family,
+      RelationshipKind::Friends => &mut self.friends,
+      RelationshipKind::Love => &mut self.love,
+      RelationshipKind::Hate => &mut self.hate,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationshipKind {
+  Family,
+  Friends,
+  Love,
+  Hate,
+}
+
+impl RelationshipKind {
+  pub fn key(self) -> &'static str {
+    match self {
+      RelationshipKind::Family => "family",
+      RelationshipKind::Friends => "friends",
+      RelationshipKind::Love => "love",
+      RelationshipKind::Hate => "hate",
+    }
+  }
+
+  pub const ALL: [RelationshipKind; 4] = [
+    RelationshipKind::Family,
+    RelationshipKind::Friends,
+    RelationshipKind::Love,
+    RelationshipKind::Hate,
+  ];
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelationshipSelection {
+  pub family: Option<String>,
+  pub friends: Option<String>,
+  pub love: Option<String>,
+  pub hate: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedCharacter {
+  pub snapshot: CharacterSnapshot,
+  pub migrations: Vec<AppliedMigration>,
+}
+
+// Saving over a sheet must not leave it half-written if the app or the machine goes down.
+pub fn write_snapshot(path: &Path, snapshot: &CharacterSnapshot) -> Result<(), String> {
+  write_json_atomic(path, snapshot)
+}
+
+#[tauri::command]
+pub fn load_character(
+  portraits: tauri::State<'_, PortraitStore>,
+  path: String,
+) -> Result<LoadedCharacter, String> {
+  let raw = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+  let value: serde_json::Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+  let report = migrate::migrate_value(value)?;
+  if report.blank {
+    return Err("Blank tab placeholders are not character sheets.".to_string());
+  }
+  rules::validate_value(&report.snapshot).into_result()?;
+  let mut snapshot: CharacterSnapshot =
+    serde_json::from_value(report.snapshot).map_err(|e| e.to_string())?;
+  portraits.intern_snapshot(&mut snapshot)?;
+  Ok(LoadedCharacter {
+    snapshot,
+    migrations: report.applied,
+  })
+}
+
+#[tauri::command]
+pub fn save_character(
+  entitlements: tauri::State<'_, Entitlements>,
+  portraits: tauri::State<'_, PortraitStore>,
+  path: String,
+  mut snapshot: CharacterSnapshot,
+) -> Result<(), String> {
+  entitlements.require_premium()?;
+  rules::validate_snapshot(&snapshot).into_result()?;
+  portraits.inline_snapshot(&mut snapshot);
+  write_snapshot(Path::new(&path), &snapshot)
+}
@@ -0,0 +1,506 @@
+use crate::character::CharacterSnapshot;
+use crate::entitlement;
+use crate::export::{self, ExportFormat};
+use crate::gc;
+use crate::library::Library;
+use crate::migrate::{self, MigrationReport};
+use crate::pdf::PageSize;
+use crate::portraits::PortraitStore;
+use crate::rules;
+use crate::storage::{self, write_atomic};
+use crate::tree::{self, TreeLayout};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const OPEN_FILES_EVENT: &str = "open-files";
+const OPENABLE_EXTENSIONS: [&str; 2] = ["json", "ecc"];
+
+// Files handed to the app on the command line, waiting for the UI to open them as tabs.
+pub struct PendingFiles(Mutex<Vec<String>>);
+
+impl PendingFiles {
+  pub fn new(files: Vec<String>) -> Self {
+    Self(Mutex::new(files))
+  }
+
+  pub fn push(&self, files: Vec<String>) {
+    if let Ok(mut pending) = self.0.lock() {
+      pending.extend(files);
+    }
+  }
+
+  pub fn take(&self) -> Vec<String> {
+    self
+      .0
+      .lock()
+      .map(|mut pending| std::mem::take(&mut *pending))
+      .unwrap_or_default()
+  }
+}
+
+// Skips the executable and any flags; relative paths resolve against the caller's cwd.
+pub fn file_args(args: &[String], cwd: &Path) -> Vec<String> {
+  args
+    .iter()
+    .skip(1)
+    .filter(|arg| !arg.starts_with('-'))
+    .map(|arg| cwd.join(arg))
+    .filter(|path| path.is_file())
+    .filter(|path| {
+      path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| OPENABLE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+    })
+    .map(|path| path.to_string_lossy().to_string())
+    .collect()
+}
+
+#[tauri::command]
+pub fn cli_take_pending(pending: tauri::State<'_, PendingFiles>) -> Vec<String> {
+  pending.take()
+}
+
+const USAGE: &str = "Usage:
+  character-creation [FILE...]
+  character-creation validate <sheet.json>...
+  character-creation migrate <sheet.json> [--output <path> | --in-place]
+  character-creation export --format md|pdf|csv <sheet.json> [--page a4|letter] [--output <path>]
+  character-creation list-library [--library <dir>] [--json]
+  character-creation tree [--root <uuid>] --output <tree.svg> [--library <dir>]
+  character-creation tree --all --output <dir> [--library <dir>]
+  character-creation gc [--delete] [--json]
+
+export and tree need premium access, verified through Enderfall Hub.
+";
+
+pub const EXIT_OK: i32 = 0;
+// The command ran but a sheet failed validation.
+pub const EXIT_INVALID: i32 = 1;
+// Bad arguments or an unreadable file.
+pub const EXIT_ERROR: i32 = 2;
+
+const SUBCOMMANDS: [&str; 9] = [
+  "validate",
+  "migrate",
+  "export",
+  "list-library",
+  "tree",
+  "gc",
+  "help",
+  "--help",
+  "-h",
+];
+const VALUE_FLAGS: [&str; 6] = [
+  "--format",
+  "--output",
+  "-o",
+  "--library",
+  "--page",
+  "--root",
+];
+const SWITCHES: [&str; 4] = ["--in-place", "--json", "--delete", "--all"];
+
+struct Options {
+  positional: Vec<String>,
+  values: BTreeMap<&'static str, String>,
+  switches: BTreeSet<&'static str>,
+}
+
+impl Options {
+  fn parse(args: &[String]) -> Result<Self, String> {
+    let mut options = Options {
+      positional: Vec::new(),
+      values: BTreeMap::new(),
+      switches: BTreeSet::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      if let Some(flag) = VALUE_FLAGS.iter().find(|flag| *flag == arg) {
+        let value = args
+          .next()
+          .ok_or_else(|| format!("{flag} needs a value."))?;
+        let key = if *flag == "-o" { "--output" } else { flag };
+        options.values.insert(key, value.clone());
+      } else if let Some(switch) = SWITCHES.iter().find(|switch| *switch == arg) {
+        options.switches.insert(switch);
+      } else if arg.starts_with('-') {
+        return Err(format!("Unknown option {arg}."));
+      } else {
+        options.positional.push(arg.clone());
+      }
+    }
+    Ok(options)
+  }
+
+  fn single_input(&self) -> Result<&str, String> {
+    match self.positional.as_slice() {
+      [input] => Ok(input),
+      [] => Err("Missing the sheet to read.".to_string()),
+      _ => Err("Only one sheet can be given.".to_string()),
+    }
+  }
+}
+
+// Runs a headless subcommand and returns its exit code, or None when the GUI should start.
+pub fn run(args: &[String], config: &tauri::Config) -> Option<i32> {
+  let command = args.get(1)?;
+  if !SUBCOMMANDS.contains(&command.as_str()) {
+    return None;
+  }
+  #[cfg(windows)]
+  attach_console();
+  let rest = &args[2..];
+  let result = match command.as_str() {
+    "validate" => Options::parse(rest).and_then(|options| validate(&options)),
+    "migrate" => Options::parse(rest).and_then(|options| migrate(&options)),
+    "export" => Options::parse(rest).and_then(|options| export(&options, config)),
+    "list-library" => Options::parse(rest).and_then(|options| list_library(&options, config)),
+    "tree" => Options::parse(rest).and_then(|options| family_tree(&options, config)),
+    "gc" => Options::parse(rest).and_then(|options| collect_garbage(&options, config)),
+    "help" | "--help" | "-h" => {
+      emit(USAGE);
+      Ok(EXIT_OK)
+    }
+    _ => unreachable!("checked against SUBCOMMANDS"),
+  };
+  Some(result.unwrap_or_else(|message| {
+    eprintln!("error: {message}");
+    eprint!("{USAGE}");
+    EXIT_ERROR
+  }))
+}
+
+// Release builds use the GUI subsystem on Windows; borrow the terminal we were started from.
+#[cfg(windows)]
+fn attach_console() {
+  #[link(name = "kernel32")]
+  extern "system" {
+    fn AttachConsole(process_id: u32) -> i32;
+  }
+  const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+  unsafe {
+    AttachConsole(ATTACH_PARENT_PROCESS);
+  }
+}
+
+// A closed pipe (`| head`) is not worth a panic.
+fn emit_bytes(bytes: &[u8]) {
+  let mut stdout = std::io::stdout().lock();
+  let _ = stdout.write_all(bytes).and_then(|_| stdout.flush());
+}
+
+fn emit(text: &str) {
+  emit_bytes(text.as_bytes());
+}
+
+fn read_sheet(path: &str) -> Result<MigrationReport, String> {
+  let raw = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+  let value: Value = serde_json::from_str(&raw).map_err(|e| format!("{path}: {e}"))?;
+  let report = migrate::migrate_value(value).map_err(|e| format!("{path}: {e}"))?;
+  if report.blank {
+    return Err(format!(
+      "{path}: blank tab placeholders are not character sheets."
+    ));
+  }
+  Ok(report)
+}
+
+fn validate(options: &Options) -> Result<i32, String> {
+  if options.positional.is_empty() {
+    return Err("Give at least one sheet to validate.".to_string());
+  }
+  let mut code = EXIT_OK;
+  for path in &options.positional {
+    let report = match read_sheet(path) {
+      Ok(report) => report,
+      Err(message) => {
+        eprintln!("{message}");
+        code = EXIT_ERROR;
+        continue;
+      }
+    };
+    let validation = rules::validate_value(&report.snapshot);
+    if validation.valid {
+      emit(&format!("{path}: ok\n"));
+    } else {
+      emit(&format!("{path}: invalid\n"));
+      for message in &validation.messages {
+        emit(&format!("  {message}\n"));
+      }
+      code = code.max(EXIT_INVALID);
+    }
+  }
+  Ok(code)
+}
+
+fn migrate(options: &Options) -> Result<i32, String> {
+  let input = options.single_input()?;
+  let output = match (
+    options.values.get("--output"),
+    options.switches.contains("--in-place"),
+  ) {
+    (Some(_), true) => return Err("Use either --output or --in-place, not both.".to_string()),
+    (Some(output), false) => Some(output.as_str()),
+    (None, true) => Some(input),
+    (None, false) => None,
+  };
+  let report = read_sheet(input)?;
+  let validation = rules::validate_value(&report.snapshot);
+  if !validation.valid {
+    eprintln!("{input}: invalid after migration");
+    for message in &validation.messages {
+      eprintln!("  {message}");
+    }
+    return Ok(EXIT_INVALID);
+  }
+  for migration in &report.applied {
+    eprintln!(
+      "{input}: v{} -> v{}: {}",
+      migration.from, migration.to, migration.description
+    );
+  }
+  let raw = serde_json::to_string_pretty(&report.snapshot).map_err(|e| e.to_string())?;
+  match output {
+    Some(output) => write_atomic(Path::new(output), raw.as_bytes())?,
+    None => emit(&format!("{raw}\n")),
+  }
+  Ok(EXIT_OK)
+}
+
+// Exports are premium in the app, and the same check applies here.
+fn require_premium(config: &tauri::Config) -> Result<(), String> {
+  entitlement::require_premium_in(storage::enderfall_dir(tauri::api::path::app_data_dir(
+    config,
+  ))?)
+}
+
+fn export(options: &Options, config: &tauri::Config) -> Result<i32, String> {
+  let input = options.single_input()?;
+  let format = options
+    .values
+    .get("--format")
+    .ok_or_else(|| "export needs --format md, pdf or csv.".to_string())?;
+  let format =
+    ExportFormat::parse(format).ok_or_else(|| format!("Unknown export format {format}."))?;
+  let page = match options.values.get("--page") {
+    Some(page) => PageSize::parse(page).ok_or_else(|| format!("Unknown page size {page}."))?,
+    None => PageSize::default(),
+  };
+  require_premium(config)?;
+  let report = read_sheet(input)?;
+  let validation = rules::validate_value(&report.snapshot);
+  if !validation.valid {
+    eprintln!("{input}: invalid");
+    for message in &validation.messages {
+      eprintln!("  {message}");
+    }
+    return Ok(EXIT_INVALID);
+  }
+  let snapshot: CharacterSnapshot =
+    serde_json::from_value(report.snapshot).map_err(|e| e.to_string())?;
+  let portraits = PortraitStore::new(
+    storage::enderfall_dir(tauri::api::path::app_data_dir(config))?.join("portraits"),
+  );
+  let bytes = export::export(&snapshot, format, page, &portraits);
+  // PDF is binary, so it goes next to the sheet unless told otherwise.
+  let output = match options.values.get("--output") {
+    Some(output) => Some(PathBuf::from(output)),
+    None if format == ExportFormat::Pdf => {
+      Some(Path::new(input).with_extension(format.extension()))
+    }
+    None => None,
+  };
+  match output {
+    Some(output) => write_atomic(&output, &bytes)?,
+    None => emit_bytes(&bytes),
+  }
+  Ok(EXIT_OK)
+}
+
+fn library_root(options: &Options, config: &tauri::Config) -> Result<PathBuf, String> {
+  match options.values.get("--library") {
+    Some(root) => Ok(PathBuf::from(root)),
+    None => Ok(storage::enderfall_dir(tauri::api::path::app_data_dir(config))?.join("library")),
+  }
+}
+
+fn list_library(options: &Options, config: &tauri::Config) -> Result<i32, String> {
+  let library = Library::new(library_root(options, config)?);
+  let summaries = library.list();
+  if options.switches.contains("--json") {
+    let raw = serde_json::to_string_pretty(&summaries).map_err(|e| e.to_string())?;
+    emit(&format!("{raw}\n"));
+  } else {
+    for summary in &summaries {
+      emit(&format!("{}\t{}\n", summary.id, summary.title));
+    }
+  }
+  Ok(EXIT_OK)
+}
+
+// Renders the tree around --root (or the whole library), or with --all one SVG per separate
+// family into the --output folder.
+fn family_tree(options: &Options, config: &tauri::Config) -> Result<i32, String> {
+  if !options.positional.is_empty() {
+    return Err("tree does not take sheet paths.".to_string());
+  }
+  let output = options
+    .values
+    .get("--output")
+    .ok_or_else(|| "tree needs --output.".to_string())?;
+  let root = library_root(options, config)?;
+  // Portraits sit beside the library, as main.rs lays out the data directory.
+  let portraits = PortraitStore::new(
+    root
+      .parent()
+      .map_or_else(|| PathBuf::from("portraits"), |dir| dir.join("portraits")),
+  );
+  require_premium(config)?;
+  let library = Library::new(root);
+  let written: Vec<(PathBuf, TreeLayout)> = if options.switches.contains("--all") {
+    if options.values.contains_key("--root") {
+      return Err("Use either --root or --all, not both.".to_string());
+    }
+    tree::export_all_svgs(&library, &portraits, Path::new(output))?
+  } else {
+    let root = options.values.get("--root").map(String::as_str);
+    let path = PathBuf::from(output);
+    let layout = tree::export_svg(&library, &portraits, root, &path)?;
+    vec![(path, layout)]
+  };
+  for (path, layout) in &written {
+    emit(&format!(
+      "{}\t{} people\n",
+      path.display(),
+      layout.nodes.len()
+    ));
+    for omitted in &layout.omitted {
+      eprintln!("{}: left out: {omitted}", path.display());
+    }
+  }
+  Ok(EXIT_OK)
+}
+
+// Reports portrait files nothing refers to; --delete removes the ones past the grace period.
+fn collect_garbage(options: &Options, config: &tauri::Config) -> Result<i32, String> {
+  let data_dir = storage::enderfall_dir(tauri::api::path::app_data_dir(config))?;
+  let report = gc::sweep_data_dir(&data_dir, options.switches.contains("--delete"))?;
+  if options.switches.contains("--json") {
+    let raw = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    emit(&format!("{raw}\n"));
+    return Ok(EXIT_OK);
+  }
+  for orphan in &report.orphans {
+    let state = if orphan.in_grace { "recent" } else { "stale" };
+    emit(&format!("{}\t{}\t{state}\n", orphan.name, orphan.bytes));
+  }
+  emit(&format!(
+    "{} of {} files unreferenced, {} bytes reclaimable, {} deleted ({} bytes freed)\n",
+    report.orphans.len(),
+    report.files,
+    report.reclaimable_bytes,
+    report.deleted,
+    report.freed_bytes
+  ));
+  Ok(EXIT_OK)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn parses_flags_switches_and_sheets() {
+    let options = Options::parse(&args(&["a.json", "-o", "out.md", "--json"])).unwrap();
+    assert_eq!(options.positional, ["a.json"]);
+    assert_eq!(options.values["--output"], "out.md");
+    assert!(!options.values.contains_key("-o"));
+    assert!(options.switches.contains("--json"));
+  }
+
+  #[test]
+  fn rejects_unknown_options_and_missing_values() {
+    let error = Options::parse(&args(&["a.json", "--verbose"])).err();
+    assert_eq!(error.as_deref(), Some("Unknown option --verbose."));
+    let error = Options::parse(&args(&["a.json", "--format"])).err();
+    assert_eq!(error.as_deref(), Some("--format needs a value."));
+    let error = Options::parse(&args(&["-o"])).err();
+    assert_eq!(error.as_deref(), Some("-o needs a value."));
+  }
+
+  #[test]
+  fn validate_tells_invalid_sheets_from_unreadable_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, value: Value| {
+      let path = dir.path().join(name);
+      fs::write(&path, value.to_string()).unwrap();
+      path.to_string_lossy().to_string()
+    };
+    let valid = write(
+      "valid.json",
+      json!({ "version": 2, "identity": { "Name": "Ada" } }),
+    );
+    let invalid = write(
+      "invalid.json",
+      json!({ "version": 2, "body": { "stats": { "Strength": 6, "Dexterity": 6, "Health": 6, "Energy": 3 } } }),
+    );
+    let missing = dir
+      .path()
+      .join("missing.json")
+      .to_string_lossy()
+      .to_string();
+    let run = |paths: &[&String]| {
+      let options = Options {
+        positional: paths.iter().map(|path| path.to_string()).collect(),
+        values: BTreeMap::new(),
+        switches: BTreeSet::new(),
+      };
+      validate(&options).unwrap()
+    };
+    assert_eq!(run(&[&valid]), EXIT_OK);
+    assert_eq!(run(&[&valid, &invalid]), EXIT_INVALID);
+    assert_eq!(run(&[&missing]), EXIT_ERROR);
+    assert_eq!(run(&[&invalid, &missing]), EXIT_ERROR);
+  }
+
+  #[test]
+  fn forwarded_paths_resolve_against_the_callers_cwd() {
+    let dir = tempfile::tempdir().unwrap();
+    let cwd = dir.path().join("sheets");
+    fs::create_dir_all(&cwd).unwrap();
+    for name in ["ada.json", "bo.ECC", "notes.txt"] {
+      fs::write(cwd.join(name), "{}").unwrap();
+    }
+    let absolute = cwd.join("ada.json").to_string_lossy().to_string();
+    let files = file_args(
+      &args(&[
+        "app",
+        "ada.json",
+        "--flag",
+        "bo.ECC",
+        "notes.txt",
+        "gone.json",
+        &absolute,
+      ]),
+      &cwd,
+    );
+    assert_eq!(
+      files,
+      [
+        absolute.clone(),
+        cwd.join("bo.ECC").to_string_lossy().to_string(),
+        absolute,
+      ]
+    );
+  }
+}
@@ -0,0 +1,482 @@
+use crate::character::{
+  CharacterSnapshot, Note, PersonaSection, RelationshipEntry, RelationshipKind, Relationships,
+  Section, StatSection,
+};
+use crate::history::History;
+use crate::library::Library;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+  pub left_name: Option<String>,
+  pub right_name: Option<String>,
+  pub identity: Vec<IdentityChange>,
+  pub sections: Vec<SectionDiff>,
+  pub notes: Vec<NoteChange>,
+  pub relationships: Vec<RelationshipChange>,
+  pub portrait_changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityChange {
+  pub field: String,
+  pub left: Option<String>,
+  pub right: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionDiff {
+  pub section: Section,
+  pub left_total: u32,
+  pub right_total: u32,
+  pub stats: Vec<StatDelta>,
+  pub sliders: Vec<SliderMove>,
+  pub traits: Vec<TraitToggle>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatDelta {
+  pub stat: String,
+  pub left: u8,
+  pub right: u8,
+  pub delta: i16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SliderMove {
+  pub slider: String,
+  pub left: Option<u8>,
+  pub right: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraitToggle {
+  #[serde(rename = "trait")]
+  pub trait_name: String,
+  pub left: bool,
+  pub right: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+  Added,
+  Removed,
+  Edited,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteChange {
+  pub index: usize,
+  pub change: ChangeKind,
+  pub left: Option<Note>,
+  pub right: Option<Note>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationshipChange {
+  pub category: RelationshipKind,
+  pub id: String,
+  pub change: ChangeKind,
+  pub left: Option<RelationshipEntry>,
+  pub right: Option<RelationshipEntry>,
+}
+
+// Picks a sheet out of the library: the current record, or one of its history versions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRef {
+  pub character_id: String,
+  pub version_id: Option<String>,
+}
+
+pub fn diff_snapshots(left: &CharacterSnapshot, right: &CharacterSnapshot) -> SnapshotDiff {
+  SnapshotDiff {
+    left_name: left.name().map(str::to_string),
+    right_name: right.name().map(str::to_string),
+    identity: diff_identity(left, right),
+    sections: diff_sections(left, right),
+    notes: diff_notes(&left.notes, &right.notes),
+    relationships: diff_relationships(left.relationships.as_ref(), right.relationships.as_ref()),
+    portrait_changed: left.portrait != right.portrait,
+  }
+}
+
+fn diff_identity(left: &CharacterSnapshot, right: &CharacterSnapshot) -> Vec<IdentityChange> {
+  let fields: BTreeSet<&String> = left.identity.keys().chain(right.identity.keys()).collect();
+  let value = |snapshot: &CharacterSnapshot, field: &str| {
+    snapshot
+      .identity
+      .get(field)
+      .map(|value| value.trim().to_string())
+      .filter(|value| !value.is_empty())
+  };
+  fields
+    .into_iter()
+    .map(|field| IdentityChange {
+      field: field.clone(),
+      left: value(left, field),
+      right: value(right, field),
+    })
+    .filter(|change| change.left != change.right)
+    .collect()
+}
+
+fn diff_sections(left: &CharacterSnapshot, right: &CharacterSnapshot) -> Vec<SectionDiff> {
+  let empty = PersonaSection::default();
+  let sections = [
+    (Section::Body, &left.body, &right.body, &empty, &empty),
+    (Section::Skills, &left.skills, &right.skills, &empty, &empty),
+    (
+      Section::Priorities,
+      &left.priorities,
+      &right.priorities,
+      &empty,
+      &empty,
+    ),
+    (
+      Section::Mind,
+      &left.mind.stats,
+      &right.mind.stats,
+      &left.mind,
+      &right.mind,
+    ),
+    (
+      Section::Social,
+      &left.social.stats,
+      &right.social.stats,
+      &left.social,
+      &right.social,
+    ),
+  ];
+  sections
+    .into_iter()
+    .map(
+      |(section, left_stats, right_stats, left_persona, right_persona)| SectionDiff {
+        section,
+        left_total: total(left_stats),
+        right_total: total(right_stats),
+        stats: diff_stats(left_stats, right_stats),
+        sliders: diff_sliders(left_persona, right_persona),
+        traits: diff_traits(left_persona, right_persona),
+      },
+    )
+    .filter(|diff| !diff.stats.is_empty() || !diff.sliders.is_empty() || !diff.traits.is_empty())
+    .collect()
+}
+
+fn total(section: &StatSection) -> u32 {
+  section.stats.values().map(|value| u32::from(*value)).sum()
+}
+
+fn diff_stats(left: &StatSection, right: &StatSection) -> Vec<StatDelta> {
+  let stats: BTreeSet<&String> = left.stats.keys().chain(right.stats.keys()).collect();
+  stats
+    .into_iter()
+    .filter_map(|stat| {
+      let before = left.stats.get(stat).copied().unwrap_or(0);
+      let after = right.stats.get(stat).copied().unwrap_or(0);
+      (before != after).then(|| StatDelta {
+        stat: stat.clone(),
+        left: before,
+        right: after,
+        delta: i16::from(after) - i16::from(before),
+      })
+    })
+    .collect()
+}
+
+fn diff_sliders(left: &PersonaSection, right: &PersonaSection) -> Vec<SliderMove> {
+  let sliders: BTreeSet<&String> = left.sliders.keys().chain(right.sliders.keys()).collect();
+  sliders
+    .into_iter()
+    .map(|slider| SliderMove {
+      slider: slider.clone(),
+      left: left.sliders.get(slider).copied(),
+      right: right.sliders.get(slider).copied(),
+    })
+    .filter(|change| change.left != change.right)
+    .collect()
+}
+
+fn diff_traits(left: &PersonaSection, right: &PersonaSection) -> Vec<TraitToggle> {
+  let traits: BTreeSet<&String> = left.traits.keys().chain(right.traits.keys()).collect();
+  traits
+    .into_iter()
+    .map(|name| TraitToggle {
+      trait_name: name.clone(),
+      left: left.traits.get(name).copied().unwrap_or(false),
+      right: right.traits.get(name).copied().unwrap_or(false),
+    })
+    .filter(|toggle| toggle.left != toggle.right)
+    .collect()
+}
+
+// Notes are positional on the sheet, so they are compared slot by slot.
+fn diff_notes(left: &[Note], right: &[Note]) -> Vec<NoteChange> {
+  let slot = |notes: &[Note], index: usize| {
+    notes
+      .get(index)
+      .filter(|note| !note.title.trim().is_empty() || !note.text.trim().is_empty())
+      .cloned()
+  };
+  (0..left.len().max(right.len()))
+    .filter_map(|index| {
+      let before = slot(left, index);
+      let after = slot(right, index);
+      let change = match (&before, &after) {
+        (None, None) => return None,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        (Some(a), Some(b)) if a == b => return None,
+        (Some(_), Some(_)) => ChangeKind::Edited,
+      };
+      Some(NoteChange {
+        index,
+        change,
+        left: before,
+        right: after,
+      })
+    })
+    .collect()
+}
+
+fn diff_relationships(
+  left: Option<&Relationships>,
+  right: Option<&Relationships>,
+) -> Vec<RelationshipChange> {
+  let mut changes = Vec::new();
+  for category in RelationshipKind::ALL {
+    let before = left.map(|r| r.get(category)).unwrap_or_default();
+    let after = right.map(|r| r.get(category)).unwrap_or_default();
+    let ids: BTreeSet<&String> = before
+      .iter()
+      .chain(after.iter())
+      .map(|entry| &entry.id)
+      .collect();
+    for id in ids {
+      let a = before.iter().find(|entry| &entry.id == id);
+      let b = after.iter().find(|entry| &entry.id == id);
+      let change = match (a, b) {
+        (Some(_), None) => ChangeKind::Removed,
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(a), Some(b)) if !entry_changed(a, b) => continue,
+        _ => ChangeKind::Edited,
+      };
+      changes.push(RelationshipChange {
+        category,
+        id: id.clone(),
+        change,
+        left: a.cloned(),
+        right: b.cloned(),
+      });
+    }
+  }
+  changes
+}
+
+// `addedAt` is bookkeeping and does not count as an edit; linking to another character does.
+fn entry_changed(a: &RelationshipEntry, b: &RelationshipEntry) -> bool {
+  a.name != b.name
+    || a.portrait != b.portrait
+    || a.relation != b.relation
+    || a.source_file != b.source_file
+    || a.target_uuid != b.target_uuid
+}
+
+fn resolve(
+  library: &Library,
+  history: &History,
+  reference: &SnapshotRef,
+) -> Result<CharacterSnapshot, String> {
+  let record = match &reference.version_id {
+    Some(version_id) => history.get(&reference.character_id, version_id)?.record,
+    None => library.open(&reference.character_id)?,
+  };
+  record
+    .snapshot()
+    .ok_or_else(|| format!("{} has no character sheet yet.", record.title))
+}
+
+#[tauri::command]
+pub fn diff_characters(left: CharacterSnapshot, right: CharacterSnapshot) -> SnapshotDiff {
+  diff_snapshots(&left, &right)
+}
+
+#[tauri::command]
+pub fn diff_library(
+  library: tauri::State<'_, Library>,
+  history: tauri::State<'_, History>,
+  left: SnapshotRef,
+  right: SnapshotRef,
+) -> Result<SnapshotDiff, String> {
+  let left = resolve(&library, &history, &left)?;
+  let right = resolve(&library, &history, &right)?;
+  Ok(diff_snapshots(&left, &right))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(id: &str, name: &str) -> RelationshipEntry {
+    RelationshipEntry {
+      id: id.into(),
+      name: name.into(),
+      added_at: 1,
+      ..Default::default()
+    }
+  }
+
+  fn note(title: &str, text: &str) -> Note {
+    Note {
+      title: title.into(),
+      text: text.into(),
+    }
+  }
+
+  #[test]
+  fn identity_changes_ignore_blank_values() {
+    let mut left = CharacterSnapshot::default();
+    let mut right = CharacterSnapshot::default();
+    left.identity.insert("Name".into(), "Ada".into());
+    right.identity.insert("Name".into(), " Ada ".into());
+    left.identity.insert("Age".into(), "30".into());
+    right.identity.insert("Age".into(), "31".into());
+    right.identity.insert("Nickname".into(), "  ".into());
+    right.identity.insert("Gender".into(), "female".into());
+    let diff = diff_snapshots(&left, &right);
+    let changes: Vec<(&str, Option<&str>, Option<&str>)> = diff
+      .identity
+      .iter()
+      .map(|c| (c.field.as_str(), c.left.as_deref(), c.right.as_deref()))
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        ("Age", Some("30"), Some("31")),
+        ("Gender", None, Some("female"))
+      ]
+    );
+    assert_eq!(diff.left_name.as_deref(), Some("Ada"));
+  }
+
+  #[test]
+  fn sections_report_stats_sliders_and_traits() {
+    let mut left = CharacterSnapshot::default();
+    let mut right = CharacterSnapshot::default();
+    left.body.stats.insert("Strength".into(), 3);
+    right.body.stats.insert("Strength".into(), 1);
+    right.body.stats.insert("Health".into(), 2);
+    left.skills.stats.insert("Luck".into(), 4);
+    right.skills.stats.insert("Luck".into(), 4);
+    left.mind.sliders.insert("Calm".into(), 2);
+    right.mind.sliders.insert("Calm".into(), 5);
+    right.mind.traits.insert("Curious".into(), true);
+    left.mind.traits.insert("Shy".into(), false);
+    let diff = diff_snapshots(&left, &right);
+    let sections: Vec<Section> = diff.sections.iter().map(|s| s.section).collect();
+    assert_eq!(sections, [Section::Body, Section::Mind]);
+
+    let body = &diff.sections[0];
+    assert_eq!((body.left_total, body.right_total), (3, 3));
+    let deltas: Vec<(&str, i16)> = body
+      .stats
+      .iter()
+      .map(|d| (d.stat.as_str(), d.delta))
+      .collect();
+    assert_eq!(deltas, [("Health", 2), ("Strength", -2)]);
+
+    let mind = &diff.sections[1];
+    assert!(mind.stats.is_empty());
+    assert_eq!(mind.sliders.len(), 1);
+    assert_eq!(
+      (mind.sliders[0].left, mind.sliders[0].right),
+      (Some(2), Some(5))
+    );
+    let traits: Vec<&str> = mind.traits.iter().map(|t| t.trait_name.as_str()).collect();
+    assert_eq!(traits, ["Curious"]);
+  }
+
+  #[test]
+  fn notes_are_compared_slot_by_slot() {
+    let left = CharacterSnapshot {
+      notes: vec![note("Past", "Born"), note("Goal", "Sail"), note("", " ")],
+      ..Default::default()
+    };
+    let right = CharacterSnapshot {
+      notes: vec![
+        note("Past", "Born"),
+        note("Goal", "Fly"),
+        note("Fear", "Water"),
+      ],
+      ..Default::default()
+    };
+    let changes: Vec<(usize, ChangeKind)> = diff_snapshots(&left, &right)
+      .notes
+      .iter()
+      .map(|c| (c.index, c.change))
+      .collect();
+    assert_eq!(changes, [(1, ChangeKind::Edited), (2, ChangeKind::Added)]);
+    let changes: Vec<(usize, ChangeKind)> = diff_snapshots(&right, &left)
+      .notes
+      .iter()
+      .map(|c| (c.index, c.change))
+      .collect();
+    assert_eq!(changes, [(1, ChangeKind::Edited), (2, ChangeKind::Removed)]);
+  }
+
+  #[test]
+  fn relationship_entries_are_matched_by_id() {
+    let relinked = RelationshipEntry {
+      target_uuid: Some("first".into()),
+      ..entry("relinked", "Cy")
+    };
+    let left = Relationships {
+      friends: vec![entry("kept", "Bo"), entry("gone", "Di"), relinked],
+      family: vec![entry("renamed", "Ed")],
+      ..Default::default()
+    };
+
+    let mut right = left.clone();
+    right.friends.retain(|e| e.id != "gone");
+    right.friends[0].added_at = 99;
+    right.friends[1].target_uuid = Some("second".into());
+    right.family[0].name = "Eddie".into();
+    right.love.push(entry("new", "Fy"));
+
+    let left = CharacterSnapshot {
+      relationships: Some(left),
+      ..Default::default()
+    };
+    let right = CharacterSnapshot {
+      relationships: Some(right),
+      ..Default::default()
+    };
+    let diff = diff_snapshots(&left, &right);
+    let changes: Vec<(RelationshipKind, &str, ChangeKind)> = diff
+      .relationships
+      .iter()
+      .map(|c| (c.category, c.id.as_str(), c.change))
+      .collect();
+    assert_eq!(
+      changes,
+      [
+        (RelationshipKind::Family, "renamed", ChangeKind::Edited),
+        (RelationshipKind::Friends, "gone", ChangeKind::Removed),
+        (RelationshipKind::Friends, "relinked", ChangeKind::Edited),
+        (RelationshipKind::Love, "new", ChangeKind::Added),
+      ]
+    );
+    assert!(diff_snapshots(&left, &left).relationships.is_empty());
+  }
+}
@@ -0,0 +1,396 @@
+use crate::hub::{self, HubRequest};
+use crate::library::now_millis;
+use crate::storage::write_json_atomic;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+pub const APP_ID: &str = "character-creation-sheet";
+const SETTINGS_FILE: &str = "entitlement.json";
+const TOKEN_FILE: &str = "launch-token.json";
+// Enderfall Hub's launch token signing key (Ed25519, base64).
+const HUB_PUBLIC_KEY: &str = "UU/KEW5Bb3hGFqEId4qknOi0McB9SBaMIHZSI9RJS/M=";
+// Debug builds accept tokens signed with a development key, e.g. from the stand-in Hub.
+#[cfg(debug_assertions)]
+const DEV_KEY_VAR: &str = "ENDERFALL_LAUNCH_TOKEN_KEY";
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const MAX_OFFLINE_GRACE_HOURS: u64 = 14 * 24;
+
+// The Hub signs the base64url payload text itself, so no JSON canonicalisation is needed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedToken {
+  pub payload: String,
+  pub signature: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Claims {
+  app_id: String,
+  expires_at: u64,
+  #[serde(default)]
+  display_name: Option<String>,
+  #[serde(default)]
+  email: Option<String>,
+  #[serde(default)]
+  avatar_url: Option<String>,
+  #[serde(default)]
+  avatar_path: Option<String>,
+}
+
+// Read from the settings file only; the webview cannot widen the grace period.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EntitlementSettings {
+  pub offline_grace_hours: u64,
+}
+
+impl Default for EntitlementSettings {
+  fn default() -> Self {
+    Self {
+      offline_grace_hours: 72,
+    }
+  }
+}
+
+impl EntitlementSettings {
+  fn grace_ms(self) -> u64 {
+    self.offline_grace_hours.min(MAX_OFFLINE_GRACE_HOURS) * HOUR_MS
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockReason {
+  NoToken,
+  BadSignature,
+  WrongApp,
+  Expired,
+}
+
+// What the UI gets instead of the token: no email, no signature, nothing to replay.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entitlement {
+  pub allowed: bool,
+  pub reason: Option<LockReason>,
+  // The Hub could not be reached and a cached token inside its grace period was used.
+  pub offline: bool,
+  pub expires_at: Option<u64>,
+  pub display_name: Option<String>,
+  pub avatar_url: Option<String>,
+  pub avatar_path: Option<String>,
+  #[serde(skip)]
+  valid_until: u64,
+}
+
+impl Entitlement {
+  fn locked(reason: LockReason) -> Self {
+    Self {
+      reason: Some(reason),
+      ..Self::default()
+    }
+  }
+
+  fn granted(claims: Claims, offline: bool, grace_ms: u64) -> Self {
+    let display_name = claims
+      .display_name
+      .filter(|name| !name.is_empty())
+      .or_else(|| {
+        claims
+          .email
+          .as_deref()
+          .and_then(|email| email.split('@').next())
+          .map(str::to_string)
+      });
+    Self {
+      allowed: true,
+      reason: None,
+      offline,
+      expires_at: Some(claims.expires_at),
+      display_name,
+      avatar_url: claims.avatar_url,
+      avatar_path: claims.avatar_path,
+      valid_until: claims.expires_at.saturating_add(grace_ms),
+    }
+  }
+}
+
+fn public_key() -> Option<VerifyingKey> {
+  #[cfg(debug_assertions)]
+  let encoded = std::env::var(DEV_KEY_VAR).unwrap_or_else(|_| HUB_PUBLIC_KEY.to_string());
+  #[cfg(not(debug_assertions))]
+  let encoded = HUB_PUBLIC_KEY.to_string();
+  let bytes: [u8; 32] = STANDARD.decode(encoded.trim()).ok()?.try_into().ok()?;
+  VerifyingKey::from_bytes(&bytes).ok()
+}
+
+// `grace_ms` is zero for a token fresh from the Hub and the offline grace for a cached one.
+fn verify(
+  key: Option<&VerifyingKey>,
+  token: &SignedToken,
+  now: u64,
+  grace_ms: u64,
+) -> Result<Claims, LockReason> {
+  let key = key.ok_or(LockReason::BadSignature)?;
+  let signature: [u8; 64] = STANDARD
+    .decode(&token.signature)
+    .ok()
+    .and_then(|bytes| bytes.try_into().ok())
+    .ok_or(LockReason::BadSignature)?;
+  key
+    .verify_strict(token.payload.as_bytes(), &Signature::from_bytes(&signature))
+    .map_err(|_| LockReason::BadSignature)?;
+  let claims: Claims = URL_SAFE_NO_PAD
+    .decode(&token.payload)
+    .ok()
+    .and_then(|raw| serde_json::from_slice(&raw).ok())
+    .ok_or(LockReason::BadSignature)?;
+  if claims.app_id != APP_ID {
+    return Err(LockReason::WrongApp);
+  }
+  if now > claims.expires_at.saturating_add(grace_ms) {
+    return Err(LockReason::Expired);
+  }
+  Ok(claims)
+}
+
+pub struct Entitlements {
+  root: PathBuf,
+  settings: EntitlementSettings,
+  key: Option<VerifyingKey>,
+  current: Mutex<Entitlement>,
+}
+
+impl Entitlements {
+  pub fn open(root: PathBuf) -> Self {
+    Self::with_key(root, public_key())
+  }
+
+  fn with_key(root: PathBuf, key: Option<VerifyingKey>) -> Self {
+    let settings = fs::read_to_string(root.join(SETTINGS_FILE))
+      .ok()
+      .and_then(|raw| serde_json::from_str(&raw).ok())
+      .unwrap_or_default();
+    Self {
+      root,
+      settings,
+      key,
+      current: Mutex::new(Entitlement::locked(LockReason::NoToken)),
+    }
+  }
+
+  fn cached_token(&self) -> Option<SignedToken> {
+    let raw = fs::read_to_string(self.root.join(TOKEN_FILE)).ok()?;
+    serde_json::from_str(&raw).ok()
+  }
+
+  fn check_fresh(&self, token: Option<Value>, now: u64) -> Entitlement {
+    let Some(token) = token.and_then(|token| serde_json::from_value::<SignedToken>(token).ok())
+    else {
+      return Entitlement::locked(LockReason::NoToken);
+    };
+    match verify(self.key.as_ref(), &token, now, 0) {
+      Ok(claims) => {
+        // Cached as received; it is verified again before every offline use.
+        let _ = write_json_atomic(&self.root.join(TOKEN_FILE), &token);
+        Entitlement::granted(claims, false, self.settings.grace_ms())
+      }
+      Err(reason) => Entitlement::locked(reason),
+    }
+  }
+
+  fn check_cached(&self, now: u64) -> Entitlement {
+    let Some(token) = self.cached_token() else {
+      return Entitlement::locked(LockReason::NoToken);
+    };
+    let grace_ms = self.settings.grace_ms();
+    match verify(self.key.as_ref(), &token, now, grace_ms) {
+      Ok(claims) => Entitlement::granted(claims, true, grace_ms),
+      Err(reason) => Entitlement::locked(reason),
+    }
+  }
+
+  // Asks the Hub first; only an unreachable Hub falls back to the cached token.
+  pub fn refresh(&self) -> Entitlement {
+    let now = now_millis();
+    let request = HubRequest::LaunchToken {
+      app_id: APP_ID.to_string(),
+    };
+    let entitlement = match hub::discover().request(&request) {
+      Ok(response) if response.ok => self.check_fresh(response.token, now),
+      Ok(_) => {
+        // The Hub is up but has no token for us, e.g. after signing out.
+        let _ = fs::remove_file(self.root.join(TOKEN_FILE));
+        Entitlement::locked(LockReason::NoToken)
+      }
+      Err(_) => self.check_cached(now),
+    };
+    self.set_current(entitlement)
+  }
+
+  fn set_current(&self, entitlement: Entitlement) -> Entitlement {
+    if let Ok(mut current) = self.current.lock() {
+      *current = entitlement.clone();
+    }
+    entitlement
+  }
+
+  // The one premium check, used by every gated command and CLI subcommand.
+  pub fn require_premium(&self) -> Result<(), String> {
+    self.require_premium_at(now_millis())
+  }
+
+  fn require_premium_at(&self, now: u64) -> Result<(), String> {
+    let allowed = self
+      .current
+      .lock()
+      .map(|current| current.allowed && now <= current.valid_until)
+      .unwrap_or(false);
+    if allowed {
+      Ok(())
+    } else {
+      Err("Premium access is required. Open Enderfall Hub to verify it.".to_string())
+    }
+  }
+}
+
+// For the CLI, which has no running app to keep the entitlement in.
+pub fn require_premium_in(data_dir: PathBuf) -> Result<(), String> {
+  let entitlements = Entitlements::open(data_dir);
+  entitlements.refresh();
+  entitlements.require_premium()
+}
+
+// Talking to the Hub can block for the IPC timeout, so it stays off the async workers.
+#[tauri::command]
+pub async fn entitlement_refresh(app: tauri::AppHandle) -> Result<Entitlement, String> {
+  tauri::async_runtime::spawn_blocking(move || app.state::<Entitlements>().refresh())
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ed25519_dalek::{Signer, SigningKey};
+  use serde_json::json;
+  use tempfile::TempDir;
+
+  const NOW: u64 = 1_800_000_000_000;
+
+  fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+  }
+
+  fn token(key: &SigningKey, claims: Value) -> SignedToken {
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    SignedToken {
+      signature: STANDARD.encode(key.sign(payload.as_bytes()).to_bytes()),
+      payload,
+    }
+  }
+
+  fn claims(expires_at: u64) -> Value {
+    json!({ "appId": APP_ID, "expiresAt": expires_at, "email": "ada@example.com" })
+  }
+
+  fn scratch(grace_hours: u64) -> (TempDir, Entitlements) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    let settings = EntitlementSettings {
+      offline_grace_hours: grace_hours,
+    };
+    write_json_atomic(&root.join(SETTINGS_FILE), &settings).unwrap();
+    let entitlements = Entitlements::with_key(root, Some(signing_key().verifying_key()));
+    (dir, entitlements)
+  }
+
+  #[test]
+  fn signature_must_match_the_hub_key() {
+    let key = signing_key();
+    let good = token(&key, claims(NOW + HOUR_MS));
+    let verified = verify(Some(&key.verifying_key()), &good, NOW, 0).unwrap();
+    assert_eq!(verified.email.as_deref(), Some("ada@example.com"));
+
+    let forged = token(&SigningKey::from_bytes(&[9; 32]), claims(NOW + HOUR_MS));
+    assert_eq!(
+      verify(Some(&key.verifying_key()), &forged, NOW, 0).unwrap_err(),
+      LockReason::BadSignature
+    );
+    let tampered = SignedToken {
+      payload: URL_SAFE_NO_PAD
+        .encode(json!({ "appId": APP_ID, "expiresAt": u64::MAX }).to_string()),
+      ..good.clone()
+    };
+    assert_eq!(
+      verify(Some(&key.verifying_key()), &tampered, NOW, 0).unwrap_err(),
+      LockReason::BadSignature
+    );
+    assert_eq!(
+      verify(None, &good, NOW, 0).unwrap_err(),
+      LockReason::BadSignature
+    );
+    let other_app = token(
+      &key,
+      json!({ "appId": "other", "expiresAt": NOW + HOUR_MS }),
+    );
+    assert_eq!(
+      verify(Some(&key.verifying_key()), &other_app, NOW, 0).unwrap_err(),
+      LockReason::WrongApp
+    );
+  }
+
+  #[test]
+  fn fresh_tokens_expire_without_grace() {
+    let (_dir, entitlements) = scratch(72);
+    let key = signing_key();
+    let expired = serde_json::to_value(token(&key, claims(NOW - 1))).unwrap();
+    let entitlement = entitlements.check_fresh(Some(expired), NOW);
+    assert_eq!(entitlement.reason, Some(LockReason::Expired));
+    assert!(!entitlements.root.join(TOKEN_FILE).exists());
+
+    let valid = serde_json::to_value(token(&key, claims(NOW + HOUR_MS))).unwrap();
+    let entitlement = entitlements.check_fresh(Some(valid), NOW);
+    assert!(entitlement.allowed && !entitlement.offline);
+    // Only the redacted fields reach the UI.
+    let raw = serde_json::to_string(&entitlement).unwrap();
+    assert!(!raw.contains("example.com") && raw.contains("\"displayName\":\"ada\""));
+    assert!(entitlements.root.join(TOKEN_FILE).exists());
+  }
+
+  #[test]
+  fn cached_tokens_last_through_the_grace_period() {
+    let (_dir, entitlements) = scratch(2);
+    let expires_at = NOW - HOUR_MS;
+    let cached = token(&signing_key(), claims(expires_at));
+    write_json_atomic(&entitlements.root.join(TOKEN_FILE), &cached).unwrap();
+
+    let entitlement = entitlements.set_current(entitlements.check_cached(NOW));
+    assert!(entitlement.allowed && entitlement.offline);
+    assert!(entitlements.require_premium_at(NOW).is_ok());
+    // The gate closes when the grace period runs out, even without another refresh.
+    assert!(entitlements
+      .require_premium_at(expires_at + 2 * HOUR_MS + 1)
+      .is_err());
+    let later = entitlements.check_cached(expires_at + 2 * HOUR_MS + 1);
+    assert_eq!(later.reason, Some(LockReason::Expired));
+  }
+
+  #[test]
+  fn grace_period_is_capped() {
+    let (_dir, entitlements) = scratch(10_000);
+    let expires_at = NOW - (MAX_OFFLINE_GRACE_HOURS + 1) * HOUR_MS;
+    let cached = token(&signing_key(), claims(expires_at));
+    write_json_atomic(&entitlements.root.join(TOKEN_FILE), &cached).unwrap();
+    let entitlement = entitlements.check_cached(NOW);
+    assert_eq!(entitlement.reason, Some(LockReason::Expired));
+  }
+}
@@ -0,0 +1,603 @@
+use crate::character::{CharacterSnapshot, PersonaSection, RelationshipKind, Section, StatSection};
+use crate::entitlement::Entitlements;
+use crate::imaging;
+use crate::pdf::{fit_width, text_width, Font, PageSize, Paint, PdfWriter};
+use crate::portraits::PortraitStore;
+use crate::rules::{SectionRules, IDENTITY_FIELDS, SECTION_RULES, SLIDER_MAX};
+use crate::storage::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+  Md,
+  Pdf,
+  Csv,
+}
+
+impl ExportFormat {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value.to_lowercase().as_str() {
+      "md" | "markdown" => Some(ExportFormat::Md),
+      "pdf" => Some(ExportFormat::Pdf),
+      "csv" => Some(ExportFormat::Csv),
+      _ => None,
+    }
+  }
+
+  pub fn extension(self) -> &'static str {
+    match self {
+      ExportFormat::Md => "md",
+      ExportFormat::Pdf => "pdf",
+      ExportFormat::Csv => "csv",
+    }
+  }
+}
+
+// For the CLI. A sheet file carries its portrait inline or, when saved from the library, as the
+// hash of an image in `portraits`.
+pub fn export(
+  snapshot: &CharacterSnapshot,
+  format: ExportFormat,
+  page: PageSize,
+  portraits: &PortraitStore,
+) -> Vec<u8> {
+  match format {
+    ExportFormat::Md => to_markdown(snapshot).into_bytes(),
+    ExportFormat::Csv => to_csv(snapshot).into_bytes(),
+    ExportFormat::Pdf => {
+      let portrait = snapshot
+        .portrait
+        .as_deref()
+        .and_then(|value| portraits.bytes(value));
+      to_pdf(snapshot, portrait.as_deref(), page)
+    }
+  }
+}
+
+fn title(rules: &SectionRules) -> String {
+  capitalize(rules.section.key())
+}
+
+fn section<'a>(snapshot: &'a CharacterSnapshot, rules: &SectionRules) -> SectionView<'a> {
+  match rules.section {
+    Section::Body => SectionView::Stats(&snapshot.body),
+    Section::Skills => SectionView::Stats(&snapshot.skills),
+    Section::Priorities => SectionView::Stats(&snapshot.priorities),
+    Section::Mind => SectionView::Persona(&snapshot.mind),
+    Section::Social => SectionView::Persona(&snapshot.social),
+  }
+}
+
+enum SectionView<'a> {
+  Stats(&'a StatSection),
+  Persona(&'a PersonaSection),
+}
+
+impl SectionView<'_> {
+  fn stats(&self) -> &StatSection {
+    match self {
+      SectionView::Stats(stats) => stats,
+      SectionView::Persona(persona) => &persona.stats,
+    }
+  }
+
+  fn slider(&self, name: &str) -> Option<u8> {
+    match self {
+      SectionView::Persona(persona) => persona.sliders.get(name).copied(),
+      SectionView::Stats(_) => None,
+    }
+  }
+
+  fn trait_set(&self, name: &str) -> bool {
+    match self {
+      SectionView::Persona(persona) => persona.traits.get(name).copied().unwrap_or(false),
+      SectionView::Stats(_) => false,
+    }
+  }
+}
+
+fn stat(view: &SectionView, name: &str) -> u8 {
+  view.stats().stats.get(name).copied().unwrap_or(0)
+}
+
+fn used(view: &SectionView) -> u32 {
+  view
+    .stats()
+    .stats
+    .values()
+    .map(|value| u32::from(*value))
+    .sum()
+}
+
+fn dots(value: u8, max: u8) -> String {
+  (0..max)
+    .map(|index| if index < value { '●' } else { '○' })
+    .collect()
+}
+
+// Identity in sheet order, followed by any custom fields the sheet carries.
+fn identity(snapshot: &CharacterSnapshot) -> Vec<(&str, &str)> {
+  let mut fields: Vec<(&str, &str)> = IDENTITY_FIELDS
+    .iter()
+    .map(|field| {
+      let value = snapshot.identity.get(*field).map_or("", String::as_str);
+      (*field, value)
+    })
+    .collect();
+  fields.extend(
+    snapshot
+      .identity
+      .iter()
+      .filter(|(key, _)| !IDENTITY_FIELDS.contains(&key.as_str()))
+      .map(|(key, value)| (key.as_str(), value.as_str())),
+  );
+  fields
+}
+
+fn display_name(snapshot: &CharacterSnapshot) -> &str {
+  snapshot.name().unwrap_or("Unnamed character")
+}
+
+fn md_cell(value: &str) -> String {
+  value.replace('|', "\\|").replace('\n', "<br>")
+}
+
+pub fn to_markdown(snapshot: &CharacterSnapshot) -> String {
+  let mut out = String::new();
+  let _ = writeln!(out, "# {}\n", display_name(snapshot));
+  out.push_str("## Identity\n\n| Field | Value |\n| --- | --- |\n");
+  for (field, value) in identity(snapshot) {
+    let _ = writeln!(out, "| {} | {} |", md_cell(field), md_cell(value));
+  }
+  for rules in &SECTION_RULES {
+    let view = section(snapshot, rules);
+    let _ = writeln!(
+      out,
+      "\n## {} ({}/{})\n\n| Stat | Dots |\n| --- | --- |",
+      title(rules),
+      used(&view),
+      rules.cap
+    );
+    for name in rules.stats {
+      let value = stat(&view, name);
+      let _ = writeln!(
+        out,
+        "| {name} | {} {value}/{} |",
+        dots(value, rules.dots),
+        rules.dots
+      );
+    }
+    if !rules.sliders.is_empty() {
+      out.push_str("\n| Slider | Position |\n| --- | --- |\n");
+      for name in rules.sliders {
+        let value = view.slider(name).unwrap_or(SLIDER_MAX / 2);
+        let _ = writeln!(out, "| {name} | {value}/{SLIDER_MAX} |");
+      }
+    }
+    let traits: Vec<&str> = rules
+      .traits
+      .iter()
+      .copied()
+      .filter(|name| view.trait_set(name))
+      .collect();
+    if !traits.is_empty() {
+      let _ = writeln!(out, "\nTraits: {}", traits.join(", "));
+    }
+  }
+  let notes: Vec<_> = snapshot
+    .notes
+    .iter()
+    .filter(|note| !note.title.trim().is_empty() || !note.text.trim().is_empty())
+    .collect();
+  if !notes.is_empty() {
+    out.push_str("\n## Notes\n");
+    for note in notes {
+      let _ = writeln!(out, "\n### {}\n\n{}", note.title.trim(), note.text.trim());
+    }
+  }
+  if let Some(relationships) = &snapshot.relationships {
+    let mut wrote_heading = false;
+    for kind in RelationshipKind::ALL {
+      let entries = relationships.get(kind);
+      if entries.is_empty() {
+        continue;
+      }
+      if !wrote_heading {
+        out.push_str("\n## Relationships\n");
+        wrote_heading = true;
+      }
+      let _ = writeln!(out, "\n### {}\n", capitalize(kind.key()));
+      for entry in entries {
+        match entry.relation.as_deref().filter(|r| !r.trim().is_empty()) {
+          Some(relation) => {
+            let _ = writeln!(out, "- {} ({})", entry.name, relation.trim());
+          }
+          None => {
+            let _ = writeln!(out, "- {}", entry.name);
+          }
+        }
+      }
+    }
+  }
+  out
+}
+
+fn capitalize(value: &str) -> String {
+  let mut chars = value.chars();
+  chars
+    .next()
+    .map(|first| first.to_uppercase().chain(chars).collect())
+    .unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+// One row per value: section, group, name, value.
+pub fn to_csv(snapshot: &CharacterSnapshot) -> String {
+  let mut rows: Vec<[String; 4]> = Vec::new();
+  for (field, value) in identity(snapshot) {
+    rows.push(["identity".into(), String::new(), field.into(), value.into()]);
+  }
+  for rules in &SECTION_RULES {
+    let view = section(snapshot, rules);
+    let key = rules.section.key();
+    for name in rules.stats {
+      rows.push([
+        key.into(),
+        "stats".into(),
+        (*name).into(),
+        stat(&view, name).to_string(),
+      ]);
+    }
+    for name in rules.sliders {
+      let value = view.slider(name).unwrap_or(SLIDER_MAX / 2);
+      rows.push([
+        key.into(),
+        "sliders".into(),
+        (*name).into(),
+        value.to_string(),
+      ]);
+    }
+    for name in rules.traits {
+      rows.push([
+        key.into(),
+        "traits".into(),
+        (*name).into(),
+        view.trait_set(name).to_string(),
+      ]);
+    }
+  }
+  for note in &snapshot.notes {
+    rows.push([
+      "notes".into(),
+      String::new(),
+      note.title.clone(),
+      note.text.clone(),
+    ]);
+  }
+  if let Some(relationships) = &snapshot.relationships {
+    for kind in RelationshipKind::ALL {
+      for entry in relationships.get(kind) {
+        rows.push([
+          "relationships".into(),
+          kind.key().into(),
+          entry.name.clone(),
+          entry.relation.clone().unwrap_or_default(),
+        ]);
+      }
+    }
+  }
+  let mut out = String::from("section,group,name,value\n");
+  for row in rows {
+    let cells: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+    let _ = writeln!(out, "{}", cells.join(","));
+  }
+  out
+}
+
+const INK: f32 = 0.1;
+const MUTED: f32 = 0.45;
+const RULE: f32 = 0.75;
+const PORTRAIT_WIDTH: f32 = 140.0;
+const PORTRAIT_HEIGHT: f32 = 180.0;
+const IDENTITY_ROW: f32 = 34.0;
+const LABEL_SIZE: f32 = 7.5;
+const VALUE_SIZE: f32 = 10.5;
+const CELL_SIZE: f32 = 9.5;
+const ROW_HEIGHT: f32 = 15.0;
+const GUTTER: f32 = 18.0;
+const DOT_RADIUS: f32 = 3.0;
+const DOT_PITCH: f32 = 9.0;
+const SLIDER_TRACK: f32 = 180.0;
+const TRAIT_COLUMNS: usize = 4;
+
+// Fitted and centred in the frame; a portrait that cannot be decoded leaves the frame empty.
+fn draw_portrait(pdf: &mut PdfWriter, portrait: Option<&[u8]>, x: f32, top: f32) {
+  let bottom = top - PORTRAIT_HEIGHT;
+  match portrait.and_then(|bytes| imaging::print_jpeg(bytes).ok()) {
+    Some((jpeg, width, height)) => {
+      let scale = (PORTRAIT_WIDTH / width as f32).min(PORTRAIT_HEIGHT / height as f32);
+      let (drawn_width, drawn_height) = (width as f32 * scale, height as f32 * scale);
+      let image = pdf.add_jpeg(jpeg, width, height);
+      pdf.image(
+        image,
+        x + (PORTRAIT_WIDTH - drawn_width) / 2.0,
+        bottom + (PORTRAIT_HEIGHT - drawn_height) / 2.0,
+        drawn_width,
+        drawn_height,
+      );
+    }
+    None => {
+      let label = "No portrait";
+      pdf.text_at(
+        x + (PORTRAIT_WIDTH - text_width(label, CELL_SIZE)) / 2.0,
+        bottom + PORTRAIT_HEIGHT / 2.0 - 3.0,
+        label,
+        Font::Regular,
+        CELL_SIZE,
+        MUTED,
+      );
+    }
+  }
+  pdf.rect(
+    x,
+    bottom,
+    PORTRAIT_WIDTH,
+    PORTRAIT_HEIGHT,
+    Paint::Stroke(RULE),
+  );
+}
+
+// Two columns of labelled fill-in lines, continued on the next page when a row does not fit.
+fn draw_identity(pdf: &mut PdfWriter, fields: &[(&str, &str)], x: f32, width: f32) {
+  let column = (width - GUTTER) / 2.0;
+  for row in fields.chunks(2) {
+    pdf.ensure(IDENTITY_ROW);
+    let row_top = pdf.y();
+    for (index, (field, value)) in row.iter().enumerate() {
+      let left = x + index as f32 * (column + GUTTER);
+      pdf.text_at(
+        left,
+        row_top - 8.0,
+        &fit_width(&field.to_uppercase(), LABEL_SIZE, column),
+        Font::Bold,
+        LABEL_SIZE,
+        MUTED,
+      );
+      pdf.text_at(
+        left,
+        row_top - 21.0,
+        &fit_width(value, VALUE_SIZE, column),
+        Font::Regular,
+        VALUE_SIZE,
+        INK,
+      );
+      pdf.segment(
+        (left, row_top - 25.0),
+        (left + column, row_top - 25.0),
+        RULE,
+      );
+    }
+    pdf.advance(IDENTITY_ROW);
+  }
+}
+
+// Filled dots for the value, hollow ones up to the section's maximum, read down each column.
+fn draw_stats(pdf: &mut PdfWriter, view: &SectionView, rules: &SectionRules) {
+  let columns = if rules.stats.len() > 12 { 3 } else { 2 };
+  let column = (pdf.content_width() - GUTTER * (columns - 1) as f32) / columns as f32;
+  let rows = rules.stats.len().div_ceil(columns);
+  let dots_width = f32::from(rules.dots) * DOT_PITCH;
+  for row in 0..rows {
+    pdf.ensure(ROW_HEIGHT);
+    pdf.advance(ROW_HEIGHT);
+    let y = pdf.y();
+    for index in (0..columns).map(|col| col * rows + row) {
+      let Some(name) = rules.stats.get(index) else {
+        continue;
+      };
+      let x = pdf.left() + (index / rows) as f32 * (column + GUTTER);
+      let label = fit_width(name, CELL_SIZE, column - dots_width - 6.0);
+      pdf.text_at(x, y + 1.0, &label, Font::Regular, CELL_SIZE, INK);
+      let value = stat(view, name);
+      for dot in 0..rules.dots {
+        let paint = if dot < value {
+          Paint::Fill(INK)
+        } else {
+          Paint::Stroke(MUTED)
+        };
+        let cx = x + column - dots_width + DOT_PITCH * (f32::from(dot) + 0.5);
+        pdf.circle(cx, y + 4.0, DOT_RADIUS, paint);
+      }
+    }
+  }
+}
+
+// Each pair on a ticked track, with the marker at the slider's position.
+fn draw_sliders(pdf: &mut PdfWriter, view: &SectionView, rules: &SectionRules) {
+  if rules.sliders.is_empty() {
+    return;
+  }
+  pdf.advance(6.0);
+  let label_width = (pdf.content_width() - SLIDER_TRACK) / 2.0 - 10.0;
+  let track_left = pdf.left() + label_width + 10.0;
+  let track_right = track_left + SLIDER_TRACK;
+  for name in rules.sliders {
+    pdf.ensure(ROW_HEIGHT);
+    pdf.advance(ROW_HEIGHT);
+    let y = pdf.y();
+    let (low, high) = name.split_once('/').unwrap_or((name, ""));
+    let low = fit_width(low.trim(), CELL_SIZE, label_width);
+    let high = fit_width(high.trim(), CELL_SIZE, label_width);
+    let low_x = track_left - 10.0 - text_width(&low, CELL_SIZE);
+    pdf.text_at(low_x, y + 1.0, &low, Font::Regular, CELL_SIZE, INK);
+    pdf.text_at(
+      track_right + 10.0,
+      y + 1.0,
+      &high,
+      Font::Regular,
+      CELL_SIZE,
+      INK,
+    );
+    let middle = y + 4.0;
+    pdf.segment((track_left, middle), (track_right, middle), RULE);
+    let position = |step: u8| track_left + SLIDER_TRACK * f32::from(step) / f32::from(SLIDER_MAX);
+    for step in 0..=SLIDER_MAX {
+      let x = position(step);
+      pdf.segment((x, middle - 2.5), (x, middle + 2.5), RULE);
+    }
+    let value = view.slider(name).unwrap_or(SLIDER_MAX / 2).min(SLIDER_MAX);
+    pdf.circle(position(value), middle, 4.0, Paint::Fill(INK));
+  }
+}
+
+fn draw_traits(pdf: &mut PdfWriter, view: &SectionView, rules: &SectionRules) {
+  if rules.traits.is_empty() {
+    return;
+  }
+  pdf.advance(6.0);
+  let column = pdf.content_width() / TRAIT_COLUMNS as f32;
+  for chunk in rules.traits.chunks(TRAIT_COLUMNS) {
+    pdf.ensure(ROW_HEIGHT);
+    pdf.advance(ROW_HEIGHT);
+    let y = pdf.y();
+    for (col, name) in chunk.iter().enumerate() {
+      let x = pdf.left() + col as f32 * column;
+      pdf.rect(x, y, 8.0, 8.0, Paint::Stroke(MUTED));
+      if view.trait_set(name) {
+        pdf.rect(x + 2.0, y + 2.0, 4.0, 4.0, Paint::Fill(INK));
+      }
+      let label = fit_width(name, CELL_SIZE, column - 16.0);
+      pdf.text_at(x + 13.0, y + 1.0, &label, Font::Regular, CELL_SIZE, INK);
+    }
+  }
+}
+
+// The whole sheet as it looks in the app, for printing. `portrait` is the image's bytes.
+pub fn to_pdf(snapshot: &CharacterSnapshot, portrait: Option<&[u8]>, page: PageSize) -> Vec<u8> {
+  let mut pdf = PdfWriter::new(page);
+  pdf.title(display_name(snapshot));
+  pdf.advance(10.0);
+  let fields = identity(snapshot);
+  pdf.ensure(PORTRAIT_HEIGHT);
+  let top = pdf.y();
+  let first_page = pdf.page();
+  let left = pdf.left();
+  draw_portrait(&mut pdf, portrait, left, top);
+  let identity_left = left + PORTRAIT_WIDTH + GUTTER;
+  let identity_width = pdf.content_width() - PORTRAIT_WIDTH - GUTTER;
+  draw_identity(&mut pdf, &fields, identity_left, identity_width);
+  // Sections start below the portrait when the fields beside it end above its bottom.
+  if pdf.page() == first_page {
+    pdf.advance((pdf.y() - (top - PORTRAIT_HEIGHT)).max(0.0));
+  }
+  for rules in &SECTION_RULES {
+    let view = section(snapshot, rules);
+    pdf.heading(&format!("{} ({}/{})", title(rules), used(&view), rules.cap));
+    draw_stats(&mut pdf, &view, rules);
+    draw_sliders(&mut pdf, &view, rules);
+    draw_traits(&mut pdf, &view, rules);
+  }
+  let notes: Vec<_> = snapshot
+    .notes
+    .iter()
+    .filter(|note| !note.title.trim().is_empty() || !note.text.trim().is_empty())
+    .collect();
+  if !notes.is_empty() {
+    pdf.heading("Notes");
+    for note in notes {
+      pdf.line(note.title.trim());
+      for paragraph in note.text.trim().lines() {
+        pdf.line(paragraph);
+      }
+    }
+  }
+  if let Some(relationships) = &snapshot.relationships {
+    for kind in RelationshipKind::ALL {
+      let entries = relationships.get(kind);
+      if entries.is_empty() {
+        continue;
+      }
+      pdf.heading(&capitalize(kind.key()));
+      for entry in entries {
+        match entry.relation.as_deref().filter(|r| !r.trim().is_empty()) {
+          Some(relation) => pdf.line(&format!("{} ({})", entry.name, relation.trim())),
+          None => pdf.line(&entry.name),
+        }
+      }
+    }
+  }
+  pdf.finish()
+}
+
+#[tauri::command]
+pub async fn export_pdf(
+  entitlements: tauri::State<'_, Entitlements>,
+  portraits: tauri::State<'_, PortraitStore>,
+  snapshot: CharacterSnapshot,
+  path: String,
+  page: PageSize,
+) -> Result<(), String> {
+  entitlements.require_premium()?;
+  let portrait = snapshot
+    .portrait
+    .as_deref()
+    .and_then(|value| portraits.bytes(value));
+  write_atomic(
+    Path::new(&path),
+    &to_pdf(&snapshot, portrait.as_deref(), page),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::Note;
+
+  fn sheet(name: &str, note: Note) -> CharacterSnapshot {
+    let mut snapshot = CharacterSnapshot {
+      notes: vec![note],
+      ..Default::default()
+    };
+    snapshot.identity.insert("Name".into(), name.into());
+    snapshot
+  }
+
+  #[test]
+  fn csv_quotes_fields_that_need_it() {
+    let csv = to_csv(&sheet(
+      "Ada, \"the\" Countess",
+      Note {
+        title: "Plain".into(),
+        text: "line one\nline two".into(),
+      },
+    ));
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("section,group,name,value"));
+    assert!(csv.contains("identity,,Name,\"Ada, \"\"the\"\" Countess\"\n"));
+    assert!(csv.contains("notes,,Plain,\"line one\nline two\"\n"));
+    assert!(csv.contains("body,stats,Strength,0\n"));
+  }
+
+  #[test]
+  fn markdown_cells_keep_the_table_intact() {
+    let markdown = to_markdown(&sheet(
+      "Ada | Bo\nLovelace",
+      Note {
+        title: "History".into(),
+        text: "Born.".into(),
+      },
+    ));
+    assert!(markdown.contains("| Name | Ada \\| Bo<br>Lovelace |\n"));
+    assert!(markdown.contains("\n### History\n\nBorn.\n"));
+  }
+}
@@ -0,0 +1,488 @@
+use crate::character::{RelationshipKind, TabData};
+use crate::library::CharacterRecord;
+use crate::links::LinkIndex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+  Parent,
+  Child,
+  Sibling,
+  Grandparent,
+  Grandchild,
+  AuntUncle,
+  NieceNephew,
+  Spouse,
+  Cousin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gender {
+  Male,
+  Female,
+  Unknown,
+}
+
+// (role, male label, female label, neutral label, extra synonyms)
+const FAMILY_LABELS: [(Role, &str, &str, &str, &[&str]); 9] = [
+  (
+    Role::Parent,
+    "father",
+    "mother",
+    "parent",
+    &["dad", "mom", "mum"],
+  ),
+  (Role::Child, "son", "daughter", "child", &[]),
+  (Role::Sibling, "brother", "sister", "sibling", &[]),
+  (
+    Role::Grandparent,
+    "grandfather",
+    "grandmother",
+    "grandparent",
+    &["grandpa", "grandma"],
+  ),
+  (
+    Role::Grandchild,
+    "grandson",
+    "granddaughter",
+    "grandchild",
+    &[],
+  ),
+  (Role::AuntUncle, "uncle", "aunt", "pibling", &[]),
+  (Role::NieceNephew, "nephew", "niece", "nibling", &[]),
+  (Role::Spouse, "husband", "wife", "spouse", &["partner"]),
+  (Role::Cousin, "cousin", "cousin", "cousin", &[]),
+];
+
+impl Role {
+  fn inverse(self) -> Role {
+    match self {
+      Role::Parent => Role::Child,
+      Role::Child => Role::Parent,
+      Role::Grandparent => Role::Grandchild,
+      Role::Grandchild => Role::Grandparent,
+      Role::AuntUncle => Role::NieceNephew,
+      Role::NieceNephew => Role::AuntUncle,
+      Role::Sibling | Role::Spouse | Role::Cousin => self,
+    }
+  }
+
+  fn labels(self) -> Vec<&'static str> {
+    FAMILY_LABELS
+      .iter()
+      .filter(|(role, ..)| *role == self)
+      .flat_map(|(_, male, female, neutral, synonyms)| {
+        [*male, *female, *neutral]
+          .into_iter()
+          .chain(synonyms.iter().copied())
+      })
+      .collect()
+  }
+
+  fn label(self, gender: Gender) -> &'static str {
+    let (_, male, female, neutral, _) = FAMILY_LABELS
+      .iter()
+      .find(|(role, ..)| *role == self)
+      .expect("every role has labels");
+    match gender {
+      Gender::Male => male,
+      Gender::Female => female,
+      Gender::Unknown => neutral,
+    }
+  }
+}
+
+// A family label split into its role and the step-/half-/in-law qualifiers around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FamilyLabel {
+  pub prefix: &'static str,
+  pub role: Role,
+  pub in_law: bool,
+}
+
+impl FamilyLabel {
+  pub fn parse(label: &str) -> Option<Self> {
+    let mut rest = label.trim().to_lowercase().replace(['_', ' '], "-");
+    let mut prefix = "";
+    for candidate in ["step", "half"] {
+      if let Some(stripped) = rest.strip_prefix(candidate) {
+        prefix = candidate;
+        rest = stripped.trim_start_matches('-').to_string();
+        break;
+      }
+    }
+    let in_law = rest.ends_with("-in-law");
+    let word = rest.trim_end_matches("-in-law");
+    let role = FAMILY_LABELS
+      .iter()
+      .find(|(_, male, female, neutral, synonyms)| {
+        [*male, *female, *neutral].contains(&word) || synonyms.contains(&word)
+      })
+      .map(|(role, ..)| *role)?;
+    Some(Self {
+      prefix,
+      role,
+      in_law,
+    })
+  }
+
+  pub fn inverse(&self) -> Self {
+    Self {
+      prefix: self.prefix,
+      role: self.role.inverse(),
+      in_law: self.in_law,
+    }
+  }
+
+  pub fn render(&self, gender: Gender) -> String {
+    self.render_word(self.role.label(gender))
+  }
+
+  pub fn render_word(&self, word: &str) -> String {
+    let mut label = match self.prefix {
+      "" => word.to_string(),
+      "half" => format!("half-{word}"),
+      prefix => format!("{prefix}{word}"),
+    };
+    if self.in_law {
+      label.push_str("-in-law");
+    }
+    label
+  }
+
+  pub fn accepted(&self) -> Vec<String> {
+    self
+      .role
+      .labels()
+      .into_iter()
+      .map(|word| self.render_word(word))
+      .collect()
+  }
+}
+
+pub fn gender_of(record: &CharacterRecord) -> Gender {
+  match &record.data {
+    Some(TabData::Sheet(snapshot)) => snapshot
+      .identity
+      .get("Gender")
+      .map_or(Gender::Unknown, |value| Gender::parse(value)),
+    _ => Gender::Unknown,
+  }
+}
+
+impl Gender {
+  pub fn parse(value: &str) -> Self {
+    match value.trim().to_lowercase().as_str() {
+      "m" | "male" | "man" | "boy" => Gender::Male,
+      "f" | "female" | "woman" | "girl" => Gender::Female,
+      _ => Gender::Unknown,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Person {
+  pub uuid: String,
+  pub name: String,
+  pub gender: Gender,
+  pub birthday: Option<String>,
+  pub portrait: Option<String>,
+}
+
+// One couple (or lone parent) and the children they share; siblings with no known parents
+// get a family with no partners.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Family {
+  pub partners: Vec<String>,
+  pub children: Vec<String>,
+}
+
+// A family link the tree cannot show, kept so exports can say what they left out.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OmittedLink {
+  pub uuid: String,
+  pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FamilyModel {
+  pub people: Vec<Person>,
+  pub families: Vec<Family>,
+  pub omitted: Vec<OmittedLink>,
+}
+
+impl FamilyModel {
+  // Only plain parent/child/spouse/sibling labels shape the tree. Wider family is implied by
+  // it; step-, half- and in-law relations and parents past the second are listed in `omitted`.
+  pub fn build(records: &[CharacterRecord]) -> Self {
+    let index = LinkIndex::from_records(records);
+    let mut people = Vec::new();
+    let mut parents: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut couples: BTreeSet<Vec<String>> = BTreeSet::new();
+    let mut sibling_groups: Vec<BTreeSet<String>> = Vec::new();
+    let mut omitted = Vec::new();
+    for record in records {
+      let (Some(TabData::Sheet(snapshot)), Some(uuid)) = (&record.data, record.uuid()) else {
+        continue;
+      };
+      let name = snapshot
+        .name()
+        .map_or_else(|| record.title.clone(), str::to_string);
+      people.push(Person {
+        uuid: uuid.to_string(),
+        name: name.clone(),
+        gender: gender_of(record),
+        birthday: snapshot
+          .identity
+          .get("Birthday")
+          .map(|value| value.trim().to_string())
+          .filter(|value| !value.is_empty()),
+        portrait: snapshot.portrait.clone(),
+      });
+      for entry in record.relationships.get(RelationshipKind::Family) {
+        let Some(target) = index.resolve(entry).filter(|target| target.uuid != uuid) else {
+          continue;
+        };
+        let Some(label) = entry.relation.as_deref().and_then(FamilyLabel::parse) else {
+          continue;
+        };
+        if !label.prefix.is_empty() || label.in_law {
+          omitted.push(OmittedLink {
+            uuid: uuid.to_string(),
+            reason: format!(
+              "{name} lists {} as {}",
+              entry.name,
+              entry.relation.as_deref().unwrap_or_default().trim()
+            ),
+          });
+          continue;
+        }
+        let (own, other) = (uuid.to_string(), target.uuid.clone());
+        match label.role {
+          Role::Parent => {
+            parents.entry(own).or_default().insert(other);
+          }
+          Role::Child => {
+            parents.entry(other).or_default().insert(own);
+          }
+          Role::Spouse => {
+            let mut pair = vec![own, other];
+            pair.sort();
+            couples.insert(pair);
+          }
+          Role::Sibling => {
+            let joined: Vec<usize> = sibling_groups
+              .iter()
+              .enumerate()
+              .filter(|(_, group)| group.contains(&own) || group.contains(&other))
+              .map(|(position, _)| position)
+              .collect();
+            let mut group = BTreeSet::from([own, other]);
+            for position in joined.into_iter().rev() {
+              group.extend(sibling_groups.remove(position));
+            }
+            sibling_groups.push(group);
+          }
+          _ => {}
+        }
+      }
+    }
+
+    // Siblings share the parents any one of them names.
+    let mut families: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+    let mut orphan_groups = Vec::new();
+    for group in sibling_groups {
+      let known = group
+        .iter()
+        .find_map(|member| parents.get(member).filter(|set| !set.is_empty()).cloned());
+      match known {
+        Some(known) => {
+          for member in &group {
+            parents
+              .entry(member.clone())
+              .or_insert_with(|| known.clone());
+          }
+        }
+        None => orphan_groups.push(group),
+      }
+    }
+    let name_of = |uuid: &str| {
+      people
+        .iter()
+        .find(|person| person.uuid == uuid)
+        .map_or_else(|| uuid.to_string(), |person| person.name.clone())
+    };
+    for (child, set) in parents {
+      let mut partners: Vec<String> = set.into_iter().collect();
+      // A family has at most two partners; any further parents are reported rather than shown.
+      for extra in partners.split_off(partners.len().min(2)) {
+        omitted.push(OmittedLink {
+          uuid: child.clone(),
+          reason: format!(
+            "{} has more than two parents; {} is left out",
+            name_of(&child),
+            name_of(&extra)
+          ),
+        });
+      }
+      families.entry(partners).or_default().push(child);
+    }
+    for couple in couples {
+      families.entry(couple).or_default();
+    }
+    let mut families: Vec<Family> = families
+      .into_iter()
+      .map(|(partners, children)| Family { partners, children })
+      .collect();
+    families.extend(orphan_groups.into_iter().map(|group| Family {
+      partners: Vec::new(),
+      children: group.into_iter().collect(),
+    }));
+    // Siblings keep the order the library lists them in.
+    let rank = |uuid: &String| people.iter().position(|person| &person.uuid == uuid);
+    for family in &mut families {
+      family.children.sort_by_key(rank);
+    }
+    Self {
+      people,
+      families,
+      omitted,
+    }
+  }
+
+  pub fn person(&self, uuid: &str) -> Option<&Person> {
+    self.people.iter().find(|person| person.uuid == uuid)
+  }
+
+  // Everyone connected to `uuid` through parents, children or marriage.
+  pub fn component(&self, uuid: &str) -> Self {
+    let mut members = BTreeSet::from([uuid.to_string()]);
+    let mut grown = true;
+    while grown {
+      grown = false;
+      for family in &self.families {
+        let people: Vec<&String> = family.partners.iter().chain(&family.children).collect();
+        if people.iter().any(|person| members.contains(*person)) {
+          for person in people {
+            grown |= members.insert(person.clone());
+          }
+        }
+      }
+    }
+    Self {
+      people: self
+        .people
+        .iter()
+        .filter(|person| members.contains(&person.uuid))
+        .cloned()
+        .collect(),
+      families: self
+        .families
+        .iter()
+        .filter(|family| {
+          family
+            .partners
+            .iter()
+            .chain(&family.children)
+            .any(|person| members.contains(person))
+        })
+        .cloned()
+        .collect(),
+      omitted: self
+        .omitted
+        .iter()
+        .filter(|link| members.contains(&link.uuid))
+        .cloned()
+        .collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::{CharacterSnapshot, RelationshipEntry};
+
+  fn person(name: &str) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(format!("uuid-{}", name.to_lowercase())),
+      ..CharacterSnapshot::default()
+    };
+    snapshot
+      .identity
+      .insert("Name".to_string(), name.to_string());
+    CharacterRecord {
+      id: name.to_lowercase(),
+      title: name.to_string(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..CharacterRecord::default()
+    }
+  }
+
+  fn link(from: &mut CharacterRecord, to: &CharacterRecord, relation: &str) {
+    from
+      .relationships
+      .get_mut(RelationshipKind::Family)
+      .push(RelationshipEntry {
+        id: format!("{}-{}", from.id, to.id),
+        name: to.title.clone(),
+        portrait: None,
+        relation: Some(relation.to_string()),
+        source_file: None,
+        target_uuid: to.uuid().map(str::to_string),
+        added_at: 0,
+      });
+  }
+
+  #[test]
+  fn labels_parse_with_qualifiers() {
+    let label = FamilyLabel::parse("Step Mother-in-law").unwrap();
+    assert_eq!(label.prefix, "step");
+    assert_eq!(label.role, Role::Parent);
+    assert!(label.in_law);
+    assert_eq!(
+      label.inverse().render(Gender::Female),
+      "stepdaughter-in-law"
+    );
+    assert_eq!(
+      FamilyLabel::parse("half_brother").unwrap().role,
+      Role::Sibling
+    );
+    assert!(FamilyLabel::parse("rival").is_none());
+  }
+
+  #[test]
+  fn build_reports_links_the_tree_cannot_show() {
+    let [a, b, c, step] = ["Ada", "Bram", "Cato", "Sela"].map(person);
+    let mut kid = person("Kit");
+    link(&mut kid, &a, "mother");
+    link(&mut kid, &b, "father");
+    link(&mut kid, &c, "parent");
+    link(&mut kid, &step, "stepmother");
+    let records = [a, b, c, step, kid];
+    let model = FamilyModel::build(&records);
+    let family = model
+      .families
+      .iter()
+      .find(|family| family.children == ["uuid-kit"])
+      .unwrap();
+    assert_eq!(family.partners.len(), 2);
+    let reasons: Vec<&str> = model
+      .omitted
+      .iter()
+      .map(|link| link.reason.as_str())
+      .collect();
+    assert_eq!(
+      reasons,
+      [
+        "Kit lists Sela as stepmother",
+        "Kit has more than two parents; Cato is left out"
+      ]
+    );
+    assert_eq!(model.component("uuid-kit").omitted.len(), 2);
+    assert!(model.component("uuid-sela").omitted.is_empty());
+  }
+}
@@ -0,0 +1,339 @@
+use crate::journal::{Journal, JOURNAL_FILE};
+use crate::library::Library;
+use crate::portraits::{is_portrait_hash, PortraitStore, THUMBNAILS_DIR};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// An image stored moments before a crash stays put until its save is retried or its journal
+// entry is recovered.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanFile {
+  pub name: String,
+  pub bytes: u64,
+  pub modified_at: u64,
+  // Too recent to delete yet.
+  pub in_grace: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepReport {
+  pub files: usize,
+  pub orphans: Vec<OrphanFile>,
+  pub reclaimable_bytes: u64,
+  pub deleted: usize,
+  pub freed_bytes: u64,
+}
+
+// Portraits are referenced from `portrait` fields: on a sheet, on its relationship entries, and
+// in the same places inside history versions and journal patches. Other hex, such as a hash
+// quoted in a note, is not a reference.
+fn portrait_references(value: &Value, references: &mut HashSet<String>) {
+  match value {
+    Value::Object(map) => {
+      for (key, child) in map {
+        match child.as_str() {
+          Some(hash) if key == "portrait" && is_portrait_hash(hash) => {
+            references.insert(hash.to_string());
+          }
+          _ => portrait_references(child, references),
+        }
+      }
+    }
+    Value::Array(items) => {
+      for item in items {
+        portrait_references(item, references);
+      }
+    }
+    _ => {}
+  }
+}
+
+// A JSON document (records, versions, settings) or one per line (the journal), whose last line
+// may have been torn by a crash.
+fn parse_source(raw: &str) -> Option<Vec<Value>> {
+  if let Ok(value) = serde_json::from_str(raw) {
+    return Some(vec![value]);
+  }
+  let lines: Vec<&str> = raw.lines().filter(|line| !line.trim().is_empty()).collect();
+  let mut values = Vec::new();
+  for (index, line) in lines.iter().enumerate() {
+    match serde_json::from_str(line) {
+      Ok(value) => values.push(value),
+      Err(_) if index + 1 == lines.len() => {}
+      Err(_) => return None,
+    }
+  }
+  Some(values)
+}
+
+// Records, versions and settings are JSON, and the journal has a file of its own. Whatever else
+// the system or the user leaves in these folders (.DS_Store, Thumbs.db, desktop.ini) is skipped.
+fn is_source(name: &str) -> bool {
+  name.ends_with(".json") || name == JOURNAL_FILE
+}
+
+// The file a `write_atomic` temp file (`.{name}.{pid}-{n}.tmp`) is about to replace.
+fn temp_target(name: &str) -> Option<&str> {
+  let inner = name.strip_prefix('.')?.strip_suffix(".tmp")?;
+  inner.rsplit_once('.').map(|(target, _)| target)
+}
+
+fn collect_references(dir: &Path, references: &mut HashSet<String>) -> Result<(), String> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+    Err(e) => return Err(format!("{}: {e}", dir.display())),
+  };
+  for entry in entries {
+    let path = entry.map_err(|e| e.to_string())?.path();
+    if path.is_dir() {
+      collect_references(&path, references)?;
+      continue;
+    }
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default();
+    let temporary = temp_target(&name);
+    if !is_source(temporary.unwrap_or(&name)) {
+      continue;
+    }
+    // An unreadable reference must stop the sweep, not make its images look unused; a temp file
+    // renamed away meanwhile is fine, as its images are young enough to be in their grace period.
+    let raw = match fs::read(&path) {
+      Ok(raw) => raw,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    match parse_source(&String::from_utf8_lossy(&raw)) {
+      Some(values) => {
+        for value in &values {
+          portrait_references(value, references);
+        }
+      }
+      // Half-written; see above.
+      None if temporary.is_some() => {}
+      None => {
+        return Err(format!(
+          "{}: could not be read to look for portraits.",
+          path.display()
+        ))
+      }
+    }
+  }
+  Ok(())
+}
+
+// The portrait a stored file belongs to: itself, or the one a thumbnail was made from.
+fn owner(name: &str) -> Option<&str> {
+  let hash = name.split_once('-').map_or(name, |(hash, _)| hash);
+  is_portrait_hash(hash).then_some(hash)
+}
+
+fn millis(time: SystemTime) -> u64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+// Finds stored images nothing refers to; with `delete`, removes those older than the grace period.
+pub fn sweep(
+  store: &PortraitStore,
+  sources: &[PathBuf],
+  delete: bool,
+) -> Result<SweepReport, String> {
+  let mut references = HashSet::new();
+  for source in sources {
+    collect_references(source, &mut references)?;
+  }
+  let now = SystemTime::now();
+  let mut report = SweepReport::default();
+  for dir in [
+    store.root().to_path_buf(),
+    store.root().join(THUMBNAILS_DIR),
+  ] {
+    let Ok(entries) = fs::read_dir(&dir) else {
+      continue;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+      let name = entry.file_name().to_string_lossy().to_string();
+      // Left behind by an interrupted write; never referenced.
+      let temporary = name.ends_with(".tmp");
+      let owner = if temporary { None } else { owner(&name) };
+      if !temporary && owner.is_none() {
+        continue;
+      }
+      let Ok(metadata) = entry.metadata() else {
+        continue;
+      };
+      if !metadata.is_file() {
+        continue;
+      }
+      report.files += 1;
+      if owner.is_some_and(|hash| references.contains(hash)) {
+        continue;
+      }
+      let path = entry.path();
+      let modified = metadata.modified().unwrap_or(now);
+      let in_grace = now
+        .duration_since(modified)
+        .map_or(true, |age| age < GRACE_PERIOD);
+      report.reclaimable_bytes += metadata.len();
+      // Storing the same image again refreshes its mtime, so look once more right before deleting.
+      let still_stale = || {
+        fs::metadata(&path)
+          .and_then(|metadata| metadata.modified())
+          .is_ok_and(|current| current == modified)
+      };
+      if delete && !in_grace && still_stale() && fs::remove_file(&path).is_ok() {
+        report.deleted += 1;
+        report.freed_bytes += metadata.len();
+      }
+      report.orphans.push(OrphanFile {
+        name,
+        bytes: metadata.len(),
+        modified_at: millis(modified),
+        in_grace,
+      });
+    }
+  }
+  report.orphans.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(report)
+}
+
+const SETTINGS_FILE: &str = "portrait-gc.json";
+
+// Deleting at startup happens without anyone asking, so it is off unless the settings file in
+// the data directory turns it on.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GcSettings {
+  pub sweep_on_startup: bool,
+}
+
+impl GcSettings {
+  pub fn open(data_dir: &Path) -> Self {
+    fs::read_to_string(data_dir.join(SETTINGS_FILE))
+      .ok()
+      .and_then(|raw| serde_json::from_str(&raw).ok())
+      .unwrap_or_default()
+  }
+}
+
+// For callers outside the running app (startup and the CLI), laid out as main.rs sets it up.
+pub fn sweep_data_dir(data_dir: &Path, delete: bool) -> Result<SweepReport, String> {
+  let store = PortraitStore::new(data_dir.join("portraits"));
+  let sources = [data_dir.join("library"), data_dir.join("journal")];
+  sweep(&store, &sources, delete)
+}
+
+#[tauri::command]
+pub async fn portrait_gc(
+  library: tauri::State<'_, Library>,
+  journal: tauri::State<'_, Journal>,
+  portraits: tauri::State<'_, PortraitStore>,
+  delete: bool,
+) -> Result<SweepReport, String> {
+  let sources = [library.root().to_path_buf(), journal.dir().to_path_buf()];
+  sweep(&portraits, &sources, delete)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stored(store: &PortraitStore, digit: char, age: Duration) -> String {
+    let hash = digit.to_string().repeat(64);
+    let path = store.root().join(&hash);
+    fs::write(&path, b"image").unwrap();
+    fs::File::options()
+      .write(true)
+      .open(&path)
+      .unwrap()
+      .set_modified(SystemTime::now() - age)
+      .unwrap();
+    hash
+  }
+
+  #[test]
+  fn only_unreferenced_portraits_past_their_grace_period_are_deleted() {
+    let scratch = tempfile::tempdir().unwrap();
+    let root = scratch.path();
+    let store = PortraitStore::new(root.join("portraits"));
+    let library = root.join("library");
+    fs::create_dir_all(store.root()).unwrap();
+    fs::create_dir_all(library.join("characters")).unwrap();
+    let old = GRACE_PERIOD * 2;
+    let kept = stored(&store, 'a', old);
+    let quoted = stored(&store, 'b', old);
+    let recent = stored(&store, 'c', Duration::ZERO);
+    // Only the `portrait` field refers to an image; the same hex in a note does not.
+    let record = serde_json::json!({
+      "snapshot": { "portrait": kept, "notes": quoted },
+    });
+    fs::write(
+      library.join("characters").join("x.json"),
+      record.to_string(),
+    )
+    .unwrap();
+
+    let report = sweep(&store, std::slice::from_ref(&library), true).unwrap();
+    assert_eq!(report.files, 3);
+    assert_eq!(report.deleted, 1);
+    let orphans: Vec<_> = report
+      .orphans
+      .iter()
+      .map(|orphan| (orphan.name.as_str(), orphan.in_grace))
+      .collect();
+    assert_eq!(orphans, [(quoted.as_str(), false), (recent.as_str(), true)]);
+    assert!(store.root().join(&kept).exists());
+    assert!(!store.root().join(&quoted).exists());
+    assert!(store.root().join(&recent).exists());
+
+    // A reference that cannot be read stops the sweep instead of orphaning everything.
+    fs::write(library.join("characters").join("z.json"), "not json\n{}").unwrap();
+    assert!(sweep(&store, &[library], true).is_err());
+    assert!(store.root().join(&kept).exists());
+  }
+
+  #[test]
+  fn only_json_and_the_journal_are_read() {
+    let scratch = tempfile::tempdir().unwrap();
+    let root = scratch.path();
+    let store = PortraitStore::new(root.join("portraits"));
+    let journal = root.join("journal");
+    fs::create_dir_all(store.root()).unwrap();
+    fs::create_dir_all(&journal).unwrap();
+    let old = GRACE_PERIOD * 2;
+    let journaled = stored(&store, 'a', old);
+    let pending = stored(&store, 'b', old);
+    let ignored = stored(&store, 'c', old);
+    let line = |hash: &str| format!("{}\n", serde_json::json!({ "patch": { "portrait": hash } }));
+    fs::write(journal.join(JOURNAL_FILE), line(&journaled)).unwrap();
+    fs::write(journal.join(".autosave.journal.7-0.tmp"), line(&pending)).unwrap();
+    fs::write(journal.join(".x.json.7-1.tmp"), "{ \"half").unwrap();
+    // Neither parses nor counts, even when it happens to look like JSON.
+    fs::write(journal.join(".DS_Store"), b"\0\0\0\x01Bud1\xff").unwrap();
+    fs::write(journal.join("Thumbs.db"), b"\xd0\xcf\x11\xe0").unwrap();
+    fs::write(journal.join("desktop.ini"), "[.ShellClassInfo]\n").unwrap();
+    fs::write(journal.join("notes.txt"), line(&ignored)).unwrap();
+
+    let report = sweep(&store, &[journal], false).unwrap();
+    let orphans: Vec<_> = report
+      .orphans
+      .iter()
+      .map(|orphan| orphan.name.as_str())
+      .collect();
+    assert_eq!(orphans, [ignored.as_str()]);
+  }
+}
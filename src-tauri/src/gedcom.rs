@@ -0,0 +1,580 @@
+use crate::character::{CharacterSnapshot, RelationshipEntry, RelationshipKind, TabData};
+use crate::entitlement::Entitlements;
+use crate::family::{gender_of, FamilyLabel, FamilyModel, Gender, Role};
+use crate::library::{now_millis, CharacterRecord, Library};
+use crate::rules::IDENTITY_FIELDS;
+use crate::storage::write_atomic;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+// Longest line GEDCOM 5.5.1 allows, counted in bytes to stay safe with UTF-8 names.
+const MAX_LINE: usize = 255;
+
+const MONTHS: [&str; 12] = [
+  "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GedcomExport {
+  pub path: String,
+  pub individuals: usize,
+  pub families: usize,
+  // Family links GEDCOM cannot carry, such as step-relations or a third parent.
+  pub omitted: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GedcomImport {
+  pub records: Vec<CharacterRecord>,
+  // Individuals whose _UID already belongs to a library character; those sheets are left as-is.
+  pub reused: Vec<String>,
+  pub families: usize,
+}
+
+pub fn to_gedcom(model: &FamilyModel) -> String {
+  let mut out = String::new();
+  out.push_str("0 HEAD\n1 SOUR ENDERFALL_CHARACTER_CREATION\n2 NAME Character Creation\n");
+  let _ = writeln!(out, "2 VERS {}", env!("CARGO_PKG_VERSION"));
+  out.push_str("1 SUBM @U1@\n1 GEDC\n2 VERS 5.5.1\n2 FORM LINEAGE-LINKED\n1 CHAR UTF-8\n");
+  out.push_str("0 @U1@ SUBM\n1 NAME Character Creation\n");
+
+  let xref: BTreeMap<&str, String> = model
+    .people
+    .iter()
+    .enumerate()
+    .map(|(index, person)| (person.uuid.as_str(), format!("@I{}@", index + 1)))
+    .collect();
+  for person in &model.people {
+    let _ = writeln!(out, "0 {} INDI", xref[person.uuid.as_str()]);
+    write_value(&mut out, 1, "NAME", &gedcom_name(&person.name));
+    let sex = match person.gender {
+      Gender::Male => "M",
+      Gender::Female => "F",
+      Gender::Unknown => "U",
+    };
+    let _ = writeln!(out, "1 SEX {sex}");
+    if let Some(birthday) = &person.birthday {
+      out.push_str("1 BIRT\n");
+      write_value(&mut out, 2, "DATE", &gedcom_date(birthday));
+    }
+    let _ = writeln!(out, "1 _UID {}", person.uuid);
+    for (index, family) in model.families.iter().enumerate() {
+      if family.partners.contains(&person.uuid) {
+        let _ = writeln!(out, "1 FAMS @F{}@", index + 1);
+      }
+      if family.children.contains(&person.uuid) {
+        let _ = writeln!(out, "1 FAMC @F{}@", index + 1);
+      }
+    }
+  }
+
+  for (index, family) in model.families.iter().enumerate() {
+    let _ = writeln!(out, "0 @F{}@ FAM", index + 1);
+    let gender = |uuid: &String| model.person(uuid).map_or(Gender::Unknown, |p| p.gender);
+    // GEDCOM only has HUSB and WIFE; a woman takes WIFE unless both partners are women.
+    let mut partners: Vec<&String> = family.partners.iter().collect();
+    partners.sort_by_key(|uuid| gender(uuid) == Gender::Female);
+    let tags: &[&str] = match partners.as_slice() {
+      [only] if gender(only) == Gender::Female => &["WIFE"],
+      _ => &["HUSB", "WIFE"],
+    };
+    for (tag, uuid) in tags.iter().zip(partners) {
+      let _ = writeln!(out, "1 {tag} {}", xref[uuid.as_str()]);
+    }
+    for child in &family.children {
+      let _ = writeln!(out, "1 CHIL {}", xref[child.as_str()]);
+    }
+  }
+  out.push_str("0 TRLR\n");
+  out
+}
+
+// The last word is taken as the surname: "Aria Vell" becomes "Aria /Vell/".
+fn gedcom_name(name: &str) -> String {
+  let name = name.replace('/', " ");
+  match name.trim().rsplit_once(' ') {
+    Some((given, surname)) => format!("{} /{}/", given.trim(), surname),
+    None => name.trim().to_string(),
+  }
+}
+
+// ISO dates become "12 MAR 1990"; anything else (fantasy calendars included) is a date phrase.
+fn gedcom_date(value: &str) -> String {
+  let parts: Vec<&str> = value.trim().split('-').collect();
+  let numbers: Option<Vec<u32>> = parts.iter().map(|part| part.parse().ok()).collect();
+  match (parts.as_slice(), numbers.as_deref()) {
+    ([year], Some([_])) if year.len() == 4 => year.to_string(),
+    ([year, _], Some([_, month])) if year.len() == 4 && (1..=12).contains(month) => {
+      format!("{} {year}", MONTHS[*month as usize - 1])
+    }
+    ([year, _, _], Some([_, month, day]))
+      if year.len() == 4 && (1..=12).contains(month) && (1..=31).contains(day) =>
+    {
+      format!("{day} {} {year}", MONTHS[*month as usize - 1])
+    }
+    _ => format!("({})", value.trim().replace(['(', ')'], "")),
+  }
+}
+
+fn sheet_date(value: &str) -> String {
+  let value = value.trim();
+  if let Some(phrase) = value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+    return phrase.trim().to_string();
+  }
+  let parts: Vec<&str> = value.split_whitespace().collect();
+  let month = |word: &str| {
+    MONTHS
+      .iter()
+      .position(|month| month.eq_ignore_ascii_case(word))
+      .map(|index| index + 1)
+  };
+  match parts.as_slice() {
+    [day, name, year] => match (day.parse::<u32>(), month(name), year.parse::<u32>()) {
+      (Ok(day), Some(month), Ok(year)) => format!("{year:04}-{month:02}-{day:02}"),
+      _ => value.to_string(),
+    },
+    [name, year] => match (month(name), year.parse::<u32>()) {
+      (Some(month), Ok(year)) => format!("{year:04}-{month:02}"),
+      _ => value.to_string(),
+    },
+    _ => value.to_string(),
+  }
+}
+
+// Writes `level tag value`, moving newlines onto CONT lines and anything past the line limit
+// onto CONC lines.
+fn write_value(out: &mut String, level: usize, tag: &str, value: &str) {
+  for (index, line) in value.split('\n').enumerate() {
+    let (mut level, mut tag) = if index == 0 {
+      (level, tag)
+    } else {
+      (level + 1, "CONT")
+    };
+    let mut rest = line;
+    loop {
+      let room = MAX_LINE - format!("{level} {tag} ").len();
+      let (head, tail) = rest.split_at(split_point(rest, room));
+      let head = head.replace('@', "@@");
+      if head.is_empty() {
+        let _ = writeln!(out, "{level} {tag}");
+      } else {
+        let _ = writeln!(out, "{level} {tag} {head}");
+      }
+      if tail.is_empty() {
+        break;
+      }
+      if tag != "CONC" {
+        level += 1;
+        tag = "CONC";
+      }
+      rest = tail;
+    }
+  }
+}
+
+// Byte offset to cut `value` at so the escaped head fits in `room`. Readers may trim the ends
+// of CONC lines, so the cut avoids landing next to a space where it can.
+fn split_point(value: &str, room: usize) -> usize {
+  let mut used = 0;
+  let mut cut = value.len();
+  for (offset, c) in value.char_indices() {
+    used += if c == '@' { 2 } else { c.len_utf8() };
+    if used > room {
+      cut = offset;
+      break;
+    }
+  }
+  if cut == value.len() {
+    return cut;
+  }
+  let beside_space = |at: usize| value[..at].ends_with(' ') || value[at..].starts_with(' ');
+  let mut adjusted = cut;
+  while adjusted > 0 && beside_space(adjusted) {
+    adjusted = value[..adjusted]
+      .char_indices()
+      .next_back()
+      .map_or(0, |(offset, _)| offset);
+  }
+  if adjusted == 0 {
+    cut
+  } else {
+    adjusted
+  }
+}
+
+#[derive(Debug, Default)]
+struct Node {
+  xref: Option<String>,
+  tag: String,
+  value: String,
+  children: Vec<Node>,
+}
+
+impl Node {
+  fn child(&self, tag: &str) -> Option<&Node> {
+    self.children.iter().find(|child| child.tag == tag)
+  }
+
+  fn values<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    self
+      .children
+      .iter()
+      .filter(move |child| child.tag == tag)
+      .map(|child| child.value.as_str())
+  }
+}
+
+fn parse(text: &str) -> Result<Vec<Node>, String> {
+  let mut roots: Vec<Node> = Vec::new();
+  // Path of open nodes by level; `stack[n]` is the node at level n.
+  let mut stack: Vec<Node> = Vec::new();
+  for (number, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+    let line = line.trim_start();
+    if line.trim_end().is_empty() {
+      continue;
+    }
+    let mut parts = line.splitn(2, ' ');
+    let level: usize = parts
+      .next()
+      .and_then(|level| level.parse().ok())
+      .ok_or_else(|| format!("Line {} is not a GEDCOM line.", number + 1))?;
+    let mut rest = parts.next().unwrap_or_default();
+    let mut xref = None;
+    if rest.starts_with('@') {
+      let (pointer, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+      xref = Some(pointer.to_string());
+      rest = tail;
+    }
+    let (tag, value) = rest.split_once(' ').unwrap_or((rest, ""));
+    if level > stack.len() {
+      return Err(format!("Line {} skips a level.", number + 1));
+    }
+    match tag {
+      "CONC" | "CONT" if level > 0 && level == stack.len() => {
+        let parent = stack.last_mut().expect("level checked above");
+        if tag == "CONT" {
+          parent.value.push('\n');
+        }
+        parent.value.push_str(&value.replace("@@", "@"));
+        continue;
+      }
+      _ => {}
+    }
+    close_to(&mut stack, &mut roots, level);
+    stack.push(Node {
+      xref,
+      tag: tag.to_uppercase(),
+      value: value.replace("@@", "@"),
+      children: Vec::new(),
+    });
+  }
+  close_to(&mut stack, &mut roots, 0);
+  Ok(roots)
+}
+
+fn close_to(stack: &mut Vec<Node>, roots: &mut Vec<Node>, level: usize) {
+  while stack.len() > level {
+    let node = stack.pop().expect("stack is longer than level");
+    match stack.last_mut() {
+      Some(parent) => parent.children.push(node),
+      None => roots.push(node),
+    }
+  }
+}
+
+struct Individual {
+  name: String,
+  gender: Gender,
+  uuid: String,
+  portrait: Option<String>,
+  // Index into the new records, or None when the _UID matched an existing character.
+  record: Option<usize>,
+}
+
+pub fn import_gedcom(library: &Library, text: &str) -> Result<GedcomImport, String> {
+  let roots = parse(text)?;
+  if roots.first().map(|node| node.tag.as_str()) != Some("HEAD") {
+    return Err("This file is not a GEDCOM file.".to_string());
+  }
+  let stamp = now_millis();
+  let mut individuals: BTreeMap<String, Individual> = BTreeMap::new();
+  let mut records: Vec<CharacterRecord> = Vec::new();
+  let mut reused = Vec::new();
+  for node in roots.iter().filter(|node| node.tag == "INDI") {
+    let Some(xref) = &node.xref else {
+      continue;
+    };
+    let uid = node
+      .child("_UID")
+      .and_then(|uid| Uuid::parse_str(uid.value.trim()).ok())
+      .map(|uuid| uuid.to_string());
+    if let Some(existing) = uid.as_deref().and_then(|uuid| library.find_by_uuid(uuid)) {
+      let snapshot = existing.snapshot().unwrap_or_default();
+      reused.push(existing.title.clone());
+      individuals.insert(
+        xref.clone(),
+        Individual {
+          name: snapshot
+            .name()
+            .map_or_else(|| existing.title.clone(), str::to_string),
+          gender: gender_of(&existing),
+          uuid: uid.unwrap_or_default(),
+          portrait: snapshot.portrait,
+          record: None,
+        },
+      );
+      continue;
+    }
+
+    let name = node
+      .child("NAME")
+      .map(|name| name.value.replace('/', " "))
+      .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "))
+      .filter(|name| !name.is_empty())
+      .unwrap_or_else(|| "Unnamed".to_string());
+    let (gender, sex) = match node.child("SEX").map(|sex| sex.value.trim()) {
+      Some("M") => (Gender::Male, "Male"),
+      Some("F") => (Gender::Female, "Female"),
+      _ => (Gender::Unknown, ""),
+    };
+    let uuid = uid.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(uuid.clone()),
+      ..CharacterSnapshot::default()
+    };
+    for field in IDENTITY_FIELDS {
+      snapshot.identity.insert(field.to_string(), String::new());
+    }
+    snapshot.identity.insert("Name".to_string(), name.clone());
+    snapshot
+      .identity
+      .insert("Gender".to_string(), sex.to_string());
+    if let Some(date) = node.child("BIRT").and_then(|birth| birth.child("DATE")) {
+      snapshot
+        .identity
+        .insert("Birthday".to_string(), sheet_date(&date.value));
+    }
+    records.push(CharacterRecord {
+      id: format!("tab-{stamp}-{}", records.len()),
+      title: name.clone(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..CharacterRecord::default()
+    });
+    individuals.insert(
+      xref.clone(),
+      Individual {
+        name,
+        gender,
+        uuid,
+        portrait: None,
+        record: Some(records.len() - 1),
+      },
+    );
+  }
+
+  let mut families = 0;
+  for node in roots.iter().filter(|node| node.tag == "FAM") {
+    let lookup = |tag: &'static str| -> Vec<&Individual> {
+      node
+        .values(tag)
+        .filter_map(|xref| individuals.get(xref.trim()))
+        .collect()
+    };
+    let partners: Vec<&Individual> = lookup("HUSB").into_iter().chain(lookup("WIFE")).collect();
+    let children = lookup("CHIL");
+    families += 1;
+    let mut links: Vec<(&Individual, &Individual, Role)> = Vec::new();
+    for (position, partner) in partners.iter().enumerate() {
+      for other in &partners[position + 1..] {
+        links.push((partner, other, Role::Spouse));
+        links.push((other, partner, Role::Spouse));
+      }
+      for child in &children {
+        links.push((partner, child, Role::Child));
+        links.push((child, partner, Role::Parent));
+      }
+    }
+    for (position, child) in children.iter().enumerate() {
+      for other in &children[position + 1..] {
+        links.push((child, other, Role::Sibling));
+        links.push((other, child, Role::Sibling));
+      }
+    }
+    for (owner, target, role) in links {
+      let Some(record) = owner.record.map(|index| &mut records[index]) else {
+        continue;
+      };
+      let entries = record.relationships.get_mut(RelationshipKind::Family);
+      if entries
+        .iter()
+        .any(|entry| entry.target_uuid.as_deref() == Some(target.uuid.as_str()))
+      {
+        continue;
+      }
+      let label = FamilyLabel {
+        prefix: "",
+        role,
+        in_law: false,
+      };
+      entries.push(RelationshipEntry {
+        id: Uuid::new_v4().to_string(),
+        name: target.name.clone(),
+        portrait: target.portrait.clone(),
+        relation: Some(label.render(target.gender)),
+        source_file: None,
+        target_uuid: Some(target.uuid.clone()),
+        added_at: stamp,
+      });
+    }
+  }
+
+  let records = records
+    .into_iter()
+    .map(|record| library.save(record))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(GedcomImport {
+    records,
+    reused,
+    families,
+  })
+}
+
+#[tauri::command]
+pub fn gedcom_export(
+  library: tauri::State<'_, Library>,
+  entitlements: tauri::State<'_, Entitlements>,
+  path: String,
+) -> Result<GedcomExport, String> {
+  entitlements.require_premium()?;
+  let model = FamilyModel::build(&library.records());
+  write_atomic(Path::new(&path), to_gedcom(&model).as_bytes())?;
+  Ok(GedcomExport {
+    path,
+    individuals: model.people.len(),
+    families: model.families.len(),
+    omitted: model.omitted.into_iter().map(|link| link.reason).collect(),
+  })
+}
+
+#[tauri::command]
+pub fn gedcom_import(
+  library: tauri::State<'_, Library>,
+  entitlements: tauri::State<'_, Entitlements>,
+  path: String,
+) -> Result<GedcomImport, String> {
+  entitlements.require_premium()?;
+  let raw = fs::read(&path).map_err(|e| e.to_string())?;
+  import_gedcom(&library, &String::from_utf8_lossy(&raw))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn person(id: &str, name: &str, gender: &str, birthday: &str) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(Uuid::new_v4().to_string()),
+      ..CharacterSnapshot::default()
+    };
+    for field in IDENTITY_FIELDS {
+      snapshot.identity.insert(field.to_string(), String::new());
+    }
+    snapshot
+      .identity
+      .insert("Name".to_string(), name.to_string());
+    snapshot
+      .identity
+      .insert("Gender".to_string(), gender.to_string());
+    snapshot
+      .identity
+      .insert("Birthday".to_string(), birthday.to_string());
+    CharacterRecord {
+      id: id.to_string(),
+      title: name.to_string(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..CharacterRecord::default()
+    }
+  }
+
+  fn link(from: &mut CharacterRecord, to: &CharacterRecord, relation: &str) {
+    from
+      .relationships
+      .get_mut(RelationshipKind::Family)
+      .push(RelationshipEntry {
+        id: Uuid::new_v4().to_string(),
+        name: to.title.clone(),
+        portrait: None,
+        relation: Some(relation.to_string()),
+        source_file: None,
+        target_uuid: to.uuid().map(str::to_string),
+        added_at: 0,
+      });
+  }
+
+  // People and families as sorted lines, so two models can be compared.
+  fn summary(model: &FamilyModel) -> Vec<String> {
+    let mut lines: Vec<String> = model
+      .people
+      .iter()
+      .map(|person| {
+        format!(
+          "{} {} {:?} {:?}",
+          person.uuid, person.name, person.gender, person.birthday
+        )
+      })
+      .chain(
+        model
+          .families
+          .iter()
+          .map(|family| format!("{:?} {:?}", family.partners, family.children)),
+      )
+      .collect();
+    lines.sort();
+    lines
+  }
+
+  #[test]
+  fn export_round_trips_through_import() {
+    let long_name = format!("Aria {} Vell", vec!["of@the"; 60].join(" "));
+    let mut father = person("father", "Doran Vell", "Male", "1960-04-02");
+    let mut mother = person("mother", &long_name, "Female", "Third Age 3019");
+    let mut child = person("child", "Kit Vell", "", "1990-03");
+    link(&mut father, &mother, "wife");
+    link(&mut mother, &father, "husband");
+    link(&mut child, &father, "father");
+    link(&mut child, &mother, "mother");
+    let model = FamilyModel::build(&[father, mother, child]);
+    let text = to_gedcom(&model);
+    assert!(text.lines().all(|line| line.len() <= MAX_LINE));
+    assert!(text.contains(" CONC "));
+
+    let scratch = tempfile::tempdir().unwrap();
+    let library = Library::new(scratch.path().to_path_buf());
+    let imported = import_gedcom(&library, &text).unwrap();
+    assert!(imported.reused.is_empty());
+    assert_eq!(imported.families, model.families.len());
+    assert_eq!(
+      summary(&FamilyModel::build(&imported.records)),
+      summary(&model)
+    );
+  }
+
+  #[test]
+  fn long_values_split_away_from_spaces() {
+    let value = format!("{} tail\nsecond line", "word ".repeat(80));
+    let mut out = String::new();
+    write_value(&mut out, 1, "NOTE", &value);
+    assert!(out.lines().all(|line| line.len() <= MAX_LINE));
+    assert!(out
+      .lines()
+      .any(|line| line.starts_with("2 CONT second line")));
+    let roots = parse(&format!("0 HEAD\n0 @N1@ NOTE\n{out}")).unwrap();
+    assert_eq!(roots[1].child("NOTE").unwrap().value, value);
+  }
+}
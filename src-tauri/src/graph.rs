@@ -0,0 +1,270 @@
+use crate::character::{RelationshipKind, TabData};
+use crate::entitlement::Entitlements;
+use crate::library::{CharacterRecord, Library};
+use crate::links::LinkIndex;
+use crate::storage::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GraphFormat {
+  Dot,
+  Gexf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+  pub id: String,
+  pub label: String,
+  // False for relationship targets that have no sheet in the library.
+  pub in_library: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+  pub source: String,
+  pub target: String,
+  pub kind: RelationshipKind,
+  pub relation: Option<String>,
+}
+
+impl GraphEdge {
+  fn label(&self) -> &str {
+    self.relation.as_deref().unwrap_or(self.kind.key())
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationshipGraph {
+  pub nodes: Vec<GraphNode>,
+  pub edges: Vec<GraphEdge>,
+}
+
+pub fn build_graph(records: &[CharacterRecord]) -> RelationshipGraph {
+  let index = LinkIndex::from_records(records);
+  let mut graph = RelationshipGraph::default();
+  for record in records {
+    let Some(TabData::Sheet(snapshot)) = &record.data else {
+      continue;
+    };
+    graph.nodes.push(GraphNode {
+      id: node_id(record),
+      label: snapshot
+        .name()
+        .map_or_else(|| record.title.clone(), str::to_string),
+      in_library: true,
+    });
+  }
+  for record in records {
+    if !matches!(record.data, Some(TabData::Sheet(_))) {
+      continue;
+    }
+    for kind in RelationshipKind::ALL {
+      for entry in record.relationships.get(kind) {
+        let target = match index.resolve(entry) {
+          Some(target) => target.uuid.clone(),
+          None => {
+            let id = format!("external:{}", entry.id);
+            graph.nodes.push(GraphNode {
+              id: id.clone(),
+              label: entry.name.clone(),
+              in_library: false,
+            });
+            id
+          }
+        };
+        graph.edges.push(GraphEdge {
+          source: node_id(record),
+          target,
+          kind,
+          relation: entry
+            .relation
+            .as_deref()
+            .map(str::trim)
+            .filter(|relation| !relation.is_empty())
+            .map(str::to_string),
+        });
+      }
+    }
+  }
+  graph
+}
+
+fn node_id(record: &CharacterRecord) -> String {
+  record
+    .uuid()
+    .map_or_else(|| record.id.clone(), str::to_string)
+}
+
+fn kind_rgb(kind: RelationshipKind) -> (u8, u8, u8) {
+  match kind {
+    RelationshipKind::Family => (76, 120, 168),
+    RelationshipKind::Friends => (89, 161, 79),
+    RelationshipKind::Love => (225, 87, 89),
+    RelationshipKind::Hate => (59, 59, 59),
+  }
+}
+
+fn kind_hex(kind: RelationshipKind) -> String {
+  let (r, g, b) = kind_rgb(kind);
+  format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub fn to_dot(graph: &RelationshipGraph) -> String {
+  let mut out = String::from("digraph relationships {\n  node [shape=box, style=rounded];\n");
+  for node in &graph.nodes {
+    let style = if node.in_library {
+      ""
+    } else {
+      ", style=dashed"
+    };
+    let _ = writeln!(
+      out,
+      "  \"{}\" [label=\"{}\"{style}];",
+      dot_escape(&node.id),
+      dot_escape(&node.label)
+    );
+  }
+  for edge in &graph.edges {
+    let _ = writeln!(
+      out,
+      "  \"{}\" -> \"{}\" [label=\"{}\", kind=\"{}\", color=\"{}\"];",
+      dot_escape(&edge.source),
+      dot_escape(&edge.target),
+      dot_escape(edge.label()),
+      edge.kind.key(),
+      kind_hex(edge.kind)
+    );
+  }
+  out.push_str("}\n");
+  out
+}
+
+pub fn to_gexf(graph: &RelationshipGraph) -> String {
+  let mut out = String::new();
+  out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  out.push_str(
+    "<gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">\n",
+  );
+  out.push_str("  <meta>\n    <creator>Character Creation</creator>\n  </meta>\n");
+  out.push_str("  <graph defaultedgetype=\"directed\" mode=\"static\">\n");
+  out.push_str("    <attributes class=\"node\">\n");
+  out.push_str("      <attribute id=\"inLibrary\" title=\"inLibrary\" type=\"boolean\"/>\n");
+  out.push_str("    </attributes>\n");
+  out.push_str("    <attributes class=\"edge\">\n");
+  out.push_str("      <attribute id=\"kind\" title=\"kind\" type=\"string\"/>\n");
+  out.push_str("      <attribute id=\"relation\" title=\"relation\" type=\"string\"/>\n");
+  out.push_str("    </attributes>\n");
+  out.push_str("    <nodes>\n");
+  for node in &graph.nodes {
+    let _ = writeln!(
+      out,
+      "      <node id=\"{}\" label=\"{}\">",
+      xml_escape(&node.id),
+      xml_escape(&node.label)
+    );
+    let _ = writeln!(
+      out,
+      "        <attvalues><attvalue for=\"inLibrary\" value=\"{}\"/></attvalues>",
+      node.in_library
+    );
+    out.push_str("      </node>\n");
+  }
+  out.push_str("    </nodes>\n    <edges>\n");
+  for (index, edge) in graph.edges.iter().enumerate() {
+    let _ = writeln!(
+      out,
+      "      <edge id=\"{index}\" source=\"{}\" target=\"{}\" label=\"{}\">",
+      xml_escape(&edge.source),
+      xml_escape(&edge.target),
+      xml_escape(edge.label())
+    );
+    let _ = writeln!(
+      out,
+      "        <attvalues><attvalue for=\"kind\" value=\"{}\"/><attvalue for=\"relation\" value=\"{}\"/></attvalues>",
+      edge.kind.key(),
+      xml_escape(edge.relation.as_deref().unwrap_or_default())
+    );
+    let (r, g, b) = kind_rgb(edge.kind);
+    let _ = writeln!(out, "        <viz:color r=\"{r}\" g=\"{g}\" b=\"{b}\"/>");
+    out.push_str("      </edge>\n");
+  }
+  out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+  out
+}
+
+fn dot_escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+// Control characters other than tab and line breaks are not allowed in XML 1.0, not even as
+// character references, so they are dropped.
+pub fn xml_escape(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&apos;"),
+      '\t' | '\n' | '\r' => out.push(c),
+      '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {}
+      _ => out.push(c),
+    }
+  }
+  out
+}
+
+#[tauri::command]
+pub fn graph_build(library: tauri::State<'_, Library>) -> RelationshipGraph {
+  build_graph(&library.records())
+}
+
+#[tauri::command]
+pub fn graph_export(
+  library: tauri::State<'_, Library>,
+  entitlements: tauri::State<'_, Entitlements>,
+  format: GraphFormat,
+  path: String,
+) -> Result<(), String> {
+  entitlements.require_premium()?;
+  let graph = build_graph(&library.records());
+  let text = match format {
+    GraphFormat::Dot => to_dot(&graph),
+    GraphFormat::Gexf => to_gexf(&graph),
+  };
+  write_atomic(Path::new(&path), text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dot_strings_escape_quotes_backslashes_and_newlines() {
+    assert_eq!(
+      dot_escape("Ada \"Countess\"\\Lovelace\nof Ockham"),
+      r#"Ada \"Countess\"\\Lovelace\nof Ockham"#
+    );
+  }
+
+  #[test]
+  fn xml_text_escapes_markup_and_drops_illegal_characters() {
+    assert_eq!(
+      xml_escape("<Ada & 'Bo' \"Cy\">"),
+      "&lt;Ada &amp; &apos;Bo&apos; &quot;Cy&quot;&gt;"
+    );
+    assert_eq!(xml_escape("a\\b\tc\nd\re"), "a\\b\tc\nd\re");
+    assert_eq!(xml_escape("bell\u{7}\u{0}\u{1b}[0m\u{fffe}"), "bell[0m");
+    assert_eq!(xml_escape("Zoë \u{1f600}"), "Zoë \u{1f600}");
+  }
+}
@@ -0,0 +1,451 @@
+use crate::library::{now_millis, CharacterRecord, Library};
+use crate::portraits::PortraitStore;
+use crate::storage::{is_safe_id, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const RETENTION_FILE: &str = "retention.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionKind {
+  Auto,
+  Checkpoint,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Retention {
+  pub max_auto: usize,
+  pub max_checkpoints: usize,
+  pub min_auto_interval_secs: u64,
+}
+
+impl Default for Retention {
+  fn default() -> Self {
+    Self {
+      max_auto: 30,
+      max_checkpoints: 50,
+      min_auto_interval_secs: 10 * 60,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredVersion {
+  pub id: String,
+  pub kind: VersionKind,
+  pub label: Option<String>,
+  pub created_at: u64,
+  pub record: CharacterRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionSummary {
+  pub id: String,
+  pub kind: VersionKind,
+  pub label: Option<String>,
+  pub created_at: u64,
+  pub title: String,
+}
+
+pub struct History {
+  root: PathBuf,
+  retention: Mutex<Retention>,
+}
+
+impl History {
+  pub fn new(root: PathBuf) -> Self {
+    let retention = fs::read_to_string(root.join(RETENTION_FILE))
+      .ok()
+      .and_then(|raw| serde_json::from_str(&raw).ok())
+      .unwrap_or_default();
+    Self {
+      root,
+      retention: Mutex::new(retention),
+    }
+  }
+
+  fn character_dir(&self, character_id: &str) -> Result<PathBuf, String> {
+    if !is_safe_id(character_id) {
+      return Err(format!("Invalid character id {character_id:?}."));
+    }
+    Ok(self.root.join(character_id))
+  }
+
+  fn version_path(&self, character_id: &str, version_id: &str) -> Result<PathBuf, String> {
+    if !is_safe_id(version_id) {
+      return Err(format!("Invalid version id {version_id:?}."));
+    }
+    Ok(
+      self
+        .character_dir(character_id)?
+        .join(format!("{version_id}.json")),
+    )
+  }
+
+  pub fn retention(&self) -> Retention {
+    self.retention.lock().map(|r| *r).unwrap_or_default()
+  }
+
+  pub fn set_retention(&self, retention: Retention) -> Result<(), String> {
+    write_json_atomic(&self.root.join(RETENTION_FILE), &retention)?;
+    *self.retention.lock().map_err(|e| e.to_string())? = retention;
+    Ok(())
+  }
+
+  fn versions(&self, character_id: &str) -> Result<Vec<StoredVersion>, String> {
+    let dir = self.character_dir(character_id)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+      return Ok(Vec::new());
+    };
+    let mut versions: Vec<StoredVersion> = entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+      .filter_map(|path| fs::read_to_string(path).ok())
+      .filter_map(|raw| serde_json::from_str(&raw).ok())
+      .collect();
+    versions.sort_by_key(|version| std::cmp::Reverse(version.created_at));
+    Ok(versions)
+  }
+
+  pub fn list(&self, character_id: &str) -> Result<Vec<VersionSummary>, String> {
+    Ok(
+      self
+        .versions(character_id)?
+        .into_iter()
+        .map(|version| VersionSummary {
+          id: version.id,
+          kind: version.kind,
+          label: version.label,
+          created_at: version.created_at,
+          title: version.record.title,
+        })
+        .collect(),
+    )
+  }
+
+  pub fn get(&self, character_id: &str, version_id: &str) -> Result<StoredVersion, String> {
+    let raw = fs::read_to_string(self.version_path(character_id, version_id)?)
+      .map_err(|e| e.to_string())?;
+    serde_json::from_str(&raw).map_err(|e| e.to_string())
+  }
+
+  // Records an automatic version unless the sheet is unchanged or the last one is too recent.
+  pub fn record_auto(&self, record: &CharacterRecord) -> Result<Option<VersionSummary>, String> {
+    let versions = self.versions(&record.id)?;
+    if versions
+      .first()
+      .is_some_and(|latest| same_content(&latest.record, record))
+    {
+      return Ok(None);
+    }
+    let interval = self.retention().min_auto_interval_secs.saturating_mul(1000);
+    let last_auto = versions
+      .iter()
+      .find(|version| version.kind == VersionKind::Auto);
+    if last_auto.is_some_and(|auto| now_millis().saturating_sub(auto.created_at) < interval) {
+      return Ok(None);
+    }
+    self.record(record, VersionKind::Auto, None).map(Some)
+  }
+
+  pub fn record(
+    &self,
+    record: &CharacterRecord,
+    kind: VersionKind,
+    label: Option<String>,
+  ) -> Result<VersionSummary, String> {
+    let created_at = now_millis();
+    let suffix = match kind {
+      VersionKind::Auto => "auto",
+      VersionKind::Checkpoint => "checkpoint",
+    };
+    let mut id = format!("{created_at}-{suffix}");
+    let mut attempt = 1;
+    while self.version_path(&record.id, &id)?.exists() {
+      attempt += 1;
+      id = format!("{created_at}-{suffix}-{attempt}");
+    }
+    let version = StoredVersion {
+      id: id.clone(),
+      kind,
+      label: label.filter(|label| !label.trim().is_empty()),
+      created_at,
+      record: record.clone(),
+    };
+    write_json_atomic(&self.version_path(&record.id, &id)?, &version)?;
+    self.prune(&record.id)?;
+    Ok(VersionSummary {
+      id,
+      kind,
+      label: version.label,
+      created_at,
+      title: record.title.clone(),
+    })
+  }
+
+  fn prune(&self, character_id: &str) -> Result<(), String> {
+    let retention = self.retention();
+    let versions = self.versions(character_id)?;
+    for (kind, keep) in [
+      (VersionKind::Auto, retention.max_auto),
+      (VersionKind::Checkpoint, retention.max_checkpoints),
+    ] {
+      for version in versions
+        .iter()
+        .filter(|version| version.kind == kind)
+        .skip(keep)
+      {
+        let path = self.version_path(character_id, &version.id)?;
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn restore(
+    &self,
+    library: &Library,
+    character_id: &str,
+    version_id: &str,
+  ) -> Result<CharacterRecord, String> {
+    let version = self.get(character_id, version_id)?;
+    // Kept with the automatic versions so repeated restores cannot push out named checkpoints.
+    if let Ok(current) = library.open(character_id) {
+      let label = format!("Before restoring {version_id}");
+      self.record(&current, VersionKind::Auto, Some(label))?;
+    }
+    let mut record = version.record;
+    record.id = character_id.to_string();
+    library.save(record)
+  }
+
+  // Saved the way `library_save` saves, so the checkpoint holds portrait hashes, not images.
+  pub fn checkpoint(
+    &self,
+    library: &Library,
+    portraits: &PortraitStore,
+    mut record: CharacterRecord,
+    label: Option<String>,
+  ) -> Result<VersionSummary, String> {
+    portraits.intern_record(&mut record)?;
+    let record = library.save(record)?;
+    self.record(&record, VersionKind::Checkpoint, label)
+  }
+
+  pub fn purge(&self, character_id: &str) -> Result<(), String> {
+    let dir = self.character_dir(character_id)?;
+    if dir.exists() {
+      fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+  }
+}
+
+fn same_content(a: &CharacterRecord, b: &CharacterRecord) -> bool {
+  a.title == b.title
+    && a.data == b.data
+    && a.relationships == b.relationships
+    && a.relationship_selection == b.relationship_selection
+}
+
+#[tauri::command]
+pub fn history_list(
+  history: tauri::State<'_, History>,
+  character_id: String,
+) -> Result<Vec<VersionSummary>, String> {
+  history.list(&character_id)
+}
+
+#[tauri::command]
+pub fn history_preview(
+  history: tauri::State<'_, History>,
+  character_id: String,
+  version_id: String,
+) -> Result<StoredVersion, String> {
+  history.get(&character_id, &version_id)
+}
+
+#[tauri::command]
+pub fn history_restore(
+  history: tauri::State<'_, History>,
+  library: tauri::State<'_, Library>,
+  character_id: String,
+  version_id: String,
+) -> Result<CharacterRecord, String> {
+  history.restore(&library, &character_id, &version_id)
+}
+
+#[tauri::command]
+pub fn history_checkpoint(
+  history: tauri::State<'_, History>,
+  library: tauri::State<'_, Library>,
+  portraits: tauri::State<'_, PortraitStore>,
+  record: CharacterRecord,
+  label: Option<String>,
+) -> Result<VersionSummary, String> {
+  history.checkpoint(&library, &portraits, record, label)
+}
+
+#[tauri::command]
+pub fn history_retention(history: tauri::State<'_, History>) -> Retention {
+  history.retention()
+}
+
+#[tauri::command]
+pub fn history_set_retention(
+  history: tauri::State<'_, History>,
+  retention: Retention,
+) -> Result<(), String> {
+  history.set_retention(retention)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::{CharacterSnapshot, TabData};
+  use crate::portraits::{encode_data_url, is_portrait_hash};
+  use image::{DynamicImage, ImageFormat, RgbImage};
+  use std::io::Cursor;
+  use std::thread;
+  use std::time::Duration;
+  use tempfile::TempDir;
+
+  fn scratch(retention: Retention) -> (TempDir, History, Library) {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history"));
+    history.set_retention(retention).unwrap();
+    let library = Library::new(dir.path().join("library"));
+    (dir, history, library)
+  }
+
+  fn sheet(title: &str) -> CharacterRecord {
+    CharacterRecord {
+      id: "ada".into(),
+      title: title.into(),
+      data: Some(TabData::Sheet(Box::default())),
+      ..Default::default()
+    }
+  }
+
+  // Versions are ordered by creation time, so keep them a millisecond apart.
+  fn record(history: &History, title: &str, kind: VersionKind) {
+    thread::sleep(Duration::from_millis(2));
+    history
+      .record(&sheet(title), kind, Some(title.into()))
+      .unwrap();
+  }
+
+  fn labels(history: &History, kind: VersionKind) -> Vec<String> {
+    history
+      .list("ada")
+      .unwrap()
+      .into_iter()
+      .filter(|version| version.kind == kind)
+      .filter_map(|version| version.label)
+      .collect()
+  }
+
+  #[test]
+  fn automatic_versions_wait_for_the_interval_and_a_change() {
+    let (_dir, history, _) = scratch(Retention {
+      min_auto_interval_secs: 60 * 60,
+      ..Retention::default()
+    });
+    assert!(history.record_auto(&sheet("One")).unwrap().is_some());
+    assert!(history.record_auto(&sheet("Two")).unwrap().is_none());
+    // Checkpoints do not hold automatic versions back.
+    record(&history, "Named", VersionKind::Checkpoint);
+    assert!(history.record_auto(&sheet("Three")).unwrap().is_none());
+
+    history
+      .set_retention(Retention {
+        min_auto_interval_secs: 0,
+        ..Retention::default()
+      })
+      .unwrap();
+    assert!(history.record_auto(&sheet("Named")).unwrap().is_none());
+    assert!(history.record_auto(&sheet("Four")).unwrap().is_some());
+    assert_eq!(history.list("ada").unwrap().len(), 3);
+  }
+
+  #[test]
+  fn each_kind_is_pruned_to_its_own_cap() {
+    let (_dir, history, _) = scratch(Retention {
+      max_auto: 2,
+      max_checkpoints: 1,
+      min_auto_interval_secs: 0,
+    });
+    for title in ["a1", "a2", "a3"] {
+      record(&history, title, VersionKind::Auto);
+    }
+    for title in ["c1", "c2"] {
+      record(&history, title, VersionKind::Checkpoint);
+    }
+    assert_eq!(labels(&history, VersionKind::Auto), ["a3", "a2"]);
+    assert_eq!(labels(&history, VersionKind::Checkpoint), ["c2"]);
+  }
+
+  #[test]
+  fn restoring_keeps_the_current_sheet_as_an_automatic_version() {
+    let (_dir, history, library) = scratch(Retention {
+      max_checkpoints: 1,
+      ..Retention::default()
+    });
+    library.save(sheet("Original")).unwrap();
+    record(&history, "Original", VersionKind::Checkpoint);
+    let checkpoint = history.list("ada").unwrap()[0].id.clone();
+    library.save(sheet("Edited")).unwrap();
+
+    for _ in 0..2 {
+      thread::sleep(Duration::from_millis(2));
+      let restored = history.restore(&library, "ada", &checkpoint).unwrap();
+      assert_eq!(restored.title, "Original");
+    }
+    assert_eq!(library.open("ada").unwrap().title, "Original");
+    assert_eq!(labels(&history, VersionKind::Checkpoint), ["Original"]);
+    let before = format!("Before restoring {checkpoint}");
+    assert_eq!(
+      labels(&history, VersionKind::Auto),
+      [before.as_str(), before.as_str()]
+    );
+    let versions = history.list("ada").unwrap();
+    let titles: Vec<&str> = versions
+      .iter()
+      .filter(|version| version.kind == VersionKind::Auto)
+      .map(|version| version.title.as_str())
+      .collect();
+    assert_eq!(titles, ["Original", "Edited"]);
+  }
+
+  #[test]
+  fn checkpoints_hold_portrait_hashes() {
+    let (dir, history, library) = scratch(Retention::default());
+    let portraits = PortraitStore::new(dir.path().join("portraits"));
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(8, 8))
+      .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+      .unwrap();
+    let record = CharacterRecord {
+      data: Some(TabData::Sheet(Box::new(CharacterSnapshot {
+        portrait: Some(encode_data_url(&png)),
+        ..Default::default()
+      }))),
+      ..sheet("Ada")
+    };
+    let summary = history
+      .checkpoint(&library, &portraits, record, None)
+      .unwrap();
+    let stored = history.get("ada", &summary.id).unwrap().record;
+    let portrait = stored.snapshot().unwrap().portrait.unwrap();
+    assert!(is_portrait_hash(&portrait));
+    assert!(portraits.get(&portrait).is_ok());
+    assert_eq!(library.open("ada").unwrap(), stored);
+  }
+}
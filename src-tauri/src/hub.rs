@@ -0,0 +1,402 @@
+use crate::launcher::{
+  self, LaunchAllowlist, LaunchError, LaunchErrorCode, LaunchHandle, Processes,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const HUB_CONFIG_FILE: &str = "hub.json";
+const IPC_TIMEOUT: Duration = Duration::from_secs(2);
+#[cfg(windows)]
+const DEFAULT_PIPE: &str = r"\\.\pipe\enderfall-hub";
+
+// Where the Hub writes its install path and IPC endpoint; shared by every Enderfall app.
+fn shared_dir() -> Option<PathBuf> {
+  tauri::api::path::data_dir().map(|dir| dir.join("Enderfall"))
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HubConfig {
+  path: Option<String>,
+  endpoint: Option<String>,
+}
+
+fn read_config(dir: &Path) -> HubConfig {
+  fs::read_to_string(dir.join(HUB_CONFIG_FILE))
+    .ok()
+    .and_then(|raw| serde_json::from_str(&raw).ok())
+    .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn install_candidates() -> Vec<PathBuf> {
+  ["LOCALAPPDATA", "ProgramFiles", "ProgramFiles(x86)"]
+    .iter()
+    .filter_map(|var| std::env::var_os(var))
+    .map(PathBuf::from)
+    .flat_map(|root| {
+      [
+        root
+          .join("Programs")
+          .join("Enderfall Hub")
+          .join("Enderfall Hub.exe"),
+        root.join("Enderfall Hub").join("Enderfall Hub.exe"),
+      ]
+    })
+    .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn install_candidates() -> Vec<PathBuf> {
+  let bundle = PathBuf::from("Enderfall Hub.app")
+    .join("Contents")
+    .join("MacOS")
+    .join("Enderfall Hub");
+  let mut roots = vec![PathBuf::from("/Applications")];
+  roots.extend(tauri::api::path::home_dir().map(|home| home.join("Applications")));
+  roots.into_iter().map(|root| root.join(&bundle)).collect()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn install_candidates() -> Vec<PathBuf> {
+  let mut candidates = vec![
+    PathBuf::from("/usr/bin/enderfall-hub"),
+    PathBuf::from("/usr/local/bin/enderfall-hub"),
+    PathBuf::from("/opt/Enderfall Hub/enderfall-hub"),
+  ];
+  candidates.extend(
+    tauri::api::path::home_dir().map(|home| home.join(".local").join("bin").join("enderfall-hub")),
+  );
+  candidates
+}
+
+#[cfg(windows)]
+fn default_endpoint() -> String {
+  DEFAULT_PIPE.to_string()
+}
+
+#[cfg(unix)]
+fn default_endpoint() -> String {
+  shared_dir()
+    .unwrap_or_else(std::env::temp_dir)
+    .join("hub.sock")
+    .to_string_lossy()
+    .to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct Hub {
+  pub executable: Option<PathBuf>,
+  pub endpoint: String,
+}
+
+pub fn discover() -> Hub {
+  discover_in(shared_dir().as_deref(), install_candidates())
+}
+
+// The config file in `dir` wins over the well-known install locations.
+fn discover_in(dir: Option<&Path>, candidates: Vec<PathBuf>) -> Hub {
+  let config = dir.map(read_config).unwrap_or_default();
+  let executable = config
+    .path
+    .map(PathBuf::from)
+    .filter(|path| path.is_file())
+    .or_else(|| candidates.into_iter().find(|path| path.is_file()));
+  Hub {
+    executable,
+    endpoint: config.endpoint.unwrap_or_else(default_endpoint),
+  }
+}
+
+// One JSON object per line in each direction, one request per connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HubRequest {
+  Ping,
+  #[serde(rename_all = "camelCase")]
+  Focus {
+    app_id: String,
+  },
+  #[serde(rename_all = "camelCase")]
+  LaunchToken {
+    app_id: String,
+  },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HubResponse {
+  pub ok: bool,
+  #[serde(default)]
+  pub error: Option<String>,
+  #[serde(default)]
+  pub token: Option<Value>,
+}
+
+#[cfg(unix)]
+fn connect(endpoint: &str) -> io::Result<std::os::unix::net::UnixStream> {
+  let stream = std::os::unix::net::UnixStream::connect(endpoint)?;
+  stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+  stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+  Ok(stream)
+}
+
+// Named pipes open like files. When every pipe instance is busy, wait up to IPC_TIMEOUT for
+// one to free up rather than reporting the Hub as not running.
+#[cfg(windows)]
+fn connect(endpoint: &str) -> io::Result<fs::File> {
+  use std::ffi::OsStr;
+  use std::os::windows::ffi::OsStrExt;
+  #[link(name = "kernel32")]
+  extern "system" {
+    fn WaitNamedPipeW(name: *const u16, timeout: u32) -> i32;
+  }
+  const ERROR_PIPE_BUSY: i32 = 231;
+  let name: Vec<u16> = OsStr::new(endpoint)
+    .encode_wide()
+    .chain(std::iter::once(0))
+    .collect();
+  let deadline = std::time::Instant::now() + IPC_TIMEOUT;
+  loop {
+    match fs::OpenOptions::new().read(true).write(true).open(endpoint) {
+      Err(error) if error.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() || unsafe { WaitNamedPipeW(name.as_ptr(), left.as_millis() as u32) } == 0
+        {
+          return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Enderfall Hub is busy.",
+          ));
+        }
+      }
+      result => return result,
+    }
+  }
+}
+
+fn exchange(mut stream: impl Read + Write, request: &HubRequest) -> io::Result<HubResponse> {
+  let mut line = serde_json::to_vec(request)?;
+  line.push(b'\n');
+  stream.write_all(&line)?;
+  stream.flush()?;
+  let mut reply = String::new();
+  BufReader::new(stream).read_line(&mut reply)?;
+  Ok(serde_json::from_str(&reply)?)
+}
+
+impl Hub {
+  // Err means nobody is listening; a Hub that refuses still answers Ok with `ok: false`.
+  pub fn request(&self, request: &HubRequest) -> io::Result<HubResponse> {
+    exchange(connect(&self.endpoint)?, request)
+  }
+
+  pub fn is_running(&self) -> bool {
+    self
+      .request(&HubRequest::Ping)
+      .is_ok_and(|response| response.ok)
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HubStatus {
+  pub installed: bool,
+  pub executable: Option<String>,
+  pub running: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum HubOpened {
+  Focused,
+  Launched { handle: LaunchHandle },
+}
+
+#[tauri::command]
+pub async fn hub_status() -> HubStatus {
+  let hub = discover();
+  HubStatus {
+    installed: hub.executable.is_some(),
+    executable: hub
+      .executable
+      .as_ref()
+      .map(|path| path.to_string_lossy().to_string()),
+    running: hub.is_running(),
+  }
+}
+
+// Focuses a running Hub and returns None, or returns the executable to start when nothing
+// answers on the endpoint.
+fn focus_or_locate(hub: Hub, app_id: String) -> Result<Option<PathBuf>, LaunchError> {
+  match hub.request(&HubRequest::Focus { app_id }) {
+    Ok(response) if response.ok => return Ok(None),
+    Ok(response) => {
+      return Err(LaunchError::new(
+        LaunchErrorCode::HubRefused,
+        response
+          .error
+          .unwrap_or_else(|| "Enderfall Hub refused to focus.".to_string()),
+      ))
+    }
+    Err(_) => {}
+  }
+  let executable = hub.executable.ok_or_else(|| {
+    LaunchError::new(LaunchErrorCode::NotFound, "Enderfall Hub is not installed.")
+  })?;
+  Ok(Some(executable))
+}
+
+// Focuses a running Hub, and only starts one when nothing answers on the endpoint.
+#[tauri::command]
+pub async fn hub_open(
+  app: tauri::AppHandle,
+  window: tauri::Window,
+  allowlist: tauri::State<'_, LaunchAllowlist>,
+  processes: tauri::State<'_, Processes>,
+  app_id: String,
+) -> Result<HubOpened, LaunchError> {
+  let Some(executable) = focus_or_locate(discover(), app_id)? else {
+    return Ok(HubOpened::Focused);
+  };
+  let path = executable.to_string_lossy().to_string();
+  let handle = launcher::launch_path(app, window, allowlist, processes, path).await?;
+  Ok(HubOpened::Launched { handle })
+}
+
+// The stand-in from examples/hub_stand_in.rs, which cargo builds next to the test binary.
+#[cfg(test)]
+pub mod stand_in {
+  use std::path::PathBuf;
+
+  pub fn exe() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let examples = exe.parent().unwrap().parent().unwrap().join("examples");
+    examples.join(format!("hub_stand_in{}", std::env::consts::EXE_SUFFIX))
+  }
+
+  pub fn endpoint(name: &str) -> String {
+    if cfg!(windows) {
+      format!(r"\\.\pipe\enderfall-hub-test-{name}-{}", std::process::id())
+    } else {
+      std::env::temp_dir()
+        .join(format!("hub-test-{name}-{}.sock", std::process::id()))
+        .to_string_lossy()
+        .to_string()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::stand_in::{self, endpoint};
+  use super::*;
+  use std::process::{Child, Command, Stdio};
+
+  struct StandIn(Child);
+
+  impl StandIn {
+    fn start(endpoint: &str, extra: &[&str]) -> Self {
+      let mut child = Command::new(stand_in::exe())
+        .args(["--endpoint", endpoint])
+        .args(extra)
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("cargo test builds the hub_stand_in example");
+      let mut stderr = BufReader::new(child.stderr.take().unwrap());
+      let mut line = String::new();
+      stderr.read_line(&mut line).unwrap();
+      assert!(line.contains("listening"), "{line}");
+      std::thread::spawn(move || io::copy(&mut stderr, &mut io::sink()));
+      Self(child)
+    }
+  }
+
+  impl Drop for StandIn {
+    fn drop(&mut self) {
+      let _ = self.0.kill();
+      let _ = self.0.wait();
+    }
+  }
+
+  #[test]
+  fn config_file_wins_over_install_locations() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path();
+    let configured = dir.join("configured-hub");
+    let installed = dir.join("installed-hub");
+    fs::write(&configured, "").unwrap();
+    fs::write(&installed, "").unwrap();
+    let config = serde_json::json!({ "path": configured, "endpoint": "custom-endpoint" });
+    fs::write(dir.join(HUB_CONFIG_FILE), config.to_string()).unwrap();
+    let hub = discover_in(Some(dir), vec![installed.clone()]);
+    assert_eq!(hub.executable.as_deref(), Some(configured.as_path()));
+    assert_eq!(hub.endpoint, "custom-endpoint");
+
+    // A configured path that has gone missing falls back to the install locations.
+    fs::remove_file(&configured).unwrap();
+    let hub = discover_in(Some(dir), vec![dir.join("missing"), installed.clone()]);
+    assert_eq!(hub.executable.as_deref(), Some(installed.as_path()));
+    let hub = discover_in(None, Vec::new());
+    assert!(hub.executable.is_none());
+    assert_eq!(hub.endpoint, default_endpoint());
+  }
+
+  #[test]
+  fn running_hub_is_focused_and_hands_out_tokens() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path();
+    let token = serde_json::json!({ "payload": "p", "signature": "s" });
+    fs::write(dir.join("token.json"), token.to_string()).unwrap();
+    let endpoint = endpoint("focus");
+    let _stand_in = StandIn::start(
+      &endpoint,
+      &["--token", dir.join("token.json").to_str().unwrap()],
+    );
+    let hub = Hub {
+      executable: None,
+      endpoint,
+    };
+    assert!(hub.is_running());
+    let response = hub
+      .request(&HubRequest::LaunchToken {
+        app_id: "test".to_string(),
+      })
+      .unwrap();
+    assert_eq!(response.token, Some(token));
+    // Focusing needs no executable, so nothing would be launched.
+    assert!(matches!(focus_or_locate(hub, "test".to_string()), Ok(None)));
+  }
+
+  #[test]
+  fn refusing_hub_is_not_relaunched() {
+    let endpoint = endpoint("refuse");
+    let _stand_in = StandIn::start(&endpoint, &["--refuse"]);
+    let hub = Hub {
+      executable: Some(PathBuf::from("hub")),
+      endpoint,
+    };
+    let error = focus_or_locate(hub, "test".to_string()).unwrap_err();
+    assert_eq!(error.code, LaunchErrorCode::HubRefused);
+  }
+
+  #[test]
+  fn silent_endpoint_falls_back_to_launching() {
+    let hub = Hub {
+      executable: Some(PathBuf::from("hub")),
+      endpoint: endpoint("silent"),
+    };
+    assert!(!hub.is_running());
+    let launch = focus_or_locate(hub.clone(), "test".to_string()).unwrap();
+    assert_eq!(launch, Some(PathBuf::from("hub")));
+    let hub = Hub {
+      executable: None,
+      ..hub
+    };
+    let error = focus_or_locate(hub, "test".to_string()).unwrap_err();
+    assert_eq!(error.code, LaunchErrorCode::NotFound);
+  }
+}
@@ -0,0 +1,379 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader, Limits, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+// Longest edge of a stored portrait; the sheet never shows one larger.
+pub const MAX_PORTRAIT_EDGE: u32 = 1024;
+// Square thumbnails for relationship dropdown rows and the relationship avatar.
+pub const THUMBNAIL_SIZES: [u32; 2] = [64, 128];
+const JPEG_QUALITY: u8 = 85;
+// Refuses decompression bombs before any pixels are allocated.
+const MAX_SOURCE_EDGE: u32 = 16_384;
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_BODY_SERIAL: u16 = 0xa431;
+const TAG_LENS_SERIAL: u16 = 0xa435;
+
+// In source pixels, after the EXIF orientation has been applied, i.e. as shown to the user.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CropRect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Metadata {
+  Exif,
+  Location,
+  Camera,
+  Xmp,
+  Iptc,
+}
+
+// What a stored portrait no longer carries compared to the file the user picked.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataReport {
+  pub removed: Vec<Metadata>,
+  // The EXIF orientation was baked into the pixels before the tag was dropped.
+  pub rotated: bool,
+}
+
+pub struct ProcessedPortrait {
+  pub main: Vec<u8>,
+  pub thumbnails: Vec<(u32, Vec<u8>)>,
+  pub metadata: MetadataReport,
+}
+
+fn read_u16(data: &[u8], at: usize, little: bool) -> Option<u16> {
+  let bytes: [u8; 2] = data.get(at..at + 2)?.try_into().ok()?;
+  Some(if little {
+    u16::from_le_bytes(bytes)
+  } else {
+    u16::from_be_bytes(bytes)
+  })
+}
+
+fn read_u32(data: &[u8], at: usize, little: bool) -> Option<u32> {
+  let bytes: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+  Some(if little {
+    u32::from_le_bytes(bytes)
+  } else {
+    u32::from_be_bytes(bytes)
+  })
+}
+
+// (tag, value) for each entry of the TIFF directory at `offset`; the value is only meaningful
+// for the pointer tags, which is all we follow.
+fn ifd_entries(tiff: &[u8], offset: usize, little: bool) -> Vec<(u16, u32)> {
+  let count = read_u16(tiff, offset, little).unwrap_or(0) as usize;
+  (0..count)
+    .map_while(|index| {
+      let entry = offset + 2 + index * 12;
+      Some((
+        read_u16(tiff, entry, little)?,
+        read_u32(tiff, entry + 8, little)?,
+      ))
+    })
+    .collect()
+}
+
+// Names the sensitive parts of an EXIF block: GPS coordinates and camera make, model or serials.
+fn exif_details(chunk: &[u8]) -> Vec<Metadata> {
+  let tiff = chunk.strip_prefix(b"Exif\0\0").unwrap_or(chunk);
+  let little = match tiff.get(..4) {
+    Some(b"II*\0") => true,
+    Some(b"MM\0*") => false,
+    _ => return Vec::new(),
+  };
+  let Some(first) = read_u32(tiff, 4, little) else {
+    return Vec::new();
+  };
+  let mut tags = ifd_entries(tiff, first as usize, little);
+  if let Some(&(_, exif)) = tags.iter().find(|(tag, _)| *tag == TAG_EXIF_IFD) {
+    tags.extend(ifd_entries(tiff, exif as usize, little));
+  }
+  let has = |wanted: &[u16]| tags.iter().any(|(tag, _)| wanted.contains(tag));
+  let mut details = Vec::new();
+  if has(&[TAG_GPS_IFD]) {
+    details.push(Metadata::Location);
+  }
+  if has(&[TAG_MAKE, TAG_MODEL, TAG_BODY_SERIAL, TAG_LENS_SERIAL]) {
+    details.push(Metadata::Camera);
+  }
+  details
+}
+
+// Decodes with the EXIF orientation applied, and notes the metadata that re-encoding will drop.
+pub fn decode(bytes: &[u8]) -> Result<(DynamicImage, MetadataReport), String> {
+  let mut reader = ImageReader::new(Cursor::new(bytes))
+    .with_guessed_format()
+    .map_err(|e| e.to_string())?;
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_SOURCE_EDGE);
+  limits.max_image_height = Some(MAX_SOURCE_EDGE);
+  reader.limits(limits);
+  let read_error = |e: image::ImageError| format!("Could not read that image: {e}");
+  let mut decoder = reader.into_decoder().map_err(read_error)?;
+  let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+  let mut report = MetadataReport::default();
+  if let Some(exif) = decoder.exif_metadata().ok().flatten() {
+    report.removed.push(Metadata::Exif);
+    report.removed.extend(exif_details(&exif));
+  }
+  if decoder.xmp_metadata().ok().flatten().is_some() {
+    report.removed.push(Metadata::Xmp);
+  }
+  if decoder.iptc_metadata().ok().flatten().is_some() {
+    report.removed.push(Metadata::Iptc);
+  }
+  let mut image = DynamicImage::from_decoder(decoder).map_err(read_error)?;
+  if orientation != Orientation::NoTransforms {
+    image.apply_orientation(orientation);
+    report.rotated = true;
+  }
+  Ok((image, report))
+}
+
+fn crop(image: &DynamicImage, rect: CropRect) -> Result<DynamicImage, String> {
+  let x = rect.x.min(image.width());
+  let y = rect.y.min(image.height());
+  let width = rect.width.min(image.width() - x);
+  let height = rect.height.min(image.height() - y);
+  if width == 0 || height == 0 {
+    return Err("The crop rectangle does not overlap the image.".to_string());
+  }
+  Ok(image.crop_imm(x, y, width, height))
+}
+
+fn is_translucent(image: &DynamicImage) -> bool {
+  image.color().has_alpha() && image.to_rgba8().pixels().any(|pixel| pixel[3] < u8::MAX)
+}
+
+// Photos become JPEG; images that use transparency stay PNG so their edges survive.
+fn encode(image: &DynamicImage) -> Result<Vec<u8>, String> {
+  let mut out = Vec::new();
+  if is_translucent(image) {
+    image
+      .to_rgba8()
+      .write_with_encoder(PngEncoder::new_with_quality(
+        &mut out,
+        CompressionType::Best,
+        PngFilter::Adaptive,
+      ))
+  } else {
+    image
+      .to_rgb8()
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+  }
+  .map_err(|e| e.to_string())?;
+  Ok(out)
+}
+
+pub fn thumbnail(image: &DynamicImage, size: u32) -> Result<Vec<u8>, String> {
+  encode(&image.resize_to_fill(size, size, FilterType::CatmullRom))
+}
+
+// An opaque JPEG for documents that cannot show transparency; transparent areas become white.
+pub fn print_jpeg(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
+  let (mut image, _) = decode(bytes)?;
+  if image.width() > MAX_PORTRAIT_EDGE || image.height() > MAX_PORTRAIT_EDGE {
+    image = image.resize(MAX_PORTRAIT_EDGE, MAX_PORTRAIT_EDGE, FilterType::Lanczos3);
+  }
+  let rgba = image.to_rgba8();
+  let flat = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+    let pixel = rgba.get_pixel(x, y);
+    let alpha = u16::from(pixel[3]);
+    Rgb(
+      [0, 1, 2]
+        .map(|channel| ((u16::from(pixel[channel]) * alpha + 255 * (255 - alpha)) / 255) as u8),
+    )
+  });
+  let mut out = Vec::new();
+  flat
+    .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))
+    .map_err(|e| e.to_string())?;
+  Ok((out, flat.width(), flat.height()))
+}
+
+pub fn process(bytes: &[u8], crop_rect: Option<CropRect>) -> Result<ProcessedPortrait, String> {
+  let (mut image, metadata) = decode(bytes)?;
+  if let Some(rect) = crop_rect {
+    image = crop(&image, rect)?;
+  }
+  if image.width() > MAX_PORTRAIT_EDGE || image.height() > MAX_PORTRAIT_EDGE {
+    image = image.resize(MAX_PORTRAIT_EDGE, MAX_PORTRAIT_EDGE, FilterType::Lanczos3);
+  }
+  // Always re-encoded: the decoder only reports the metadata it knows, and anything else in the
+  // file (comments, vendor segments, trailing data) must not reach the library.
+  let main = encode(&image)?;
+  let thumbnails = THUMBNAIL_SIZES
+    .iter()
+    .map(|&size| Ok((size, thumbnail(&image, size)?)))
+    .collect::<Result<_, String>>()?;
+  Ok(ProcessedPortrait {
+    main,
+    thumbnails,
+    metadata,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RED: Rgb<u8> = Rgb([220, 20, 20]);
+  const BLUE: Rgb<u8> = Rgb([20, 20, 220]);
+
+  fn jpeg(image: RgbImage) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(image)
+      .to_rgb8()
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 95))
+      .unwrap();
+    jpeg
+  }
+
+  // Inserts a marker segment straight after SOI.
+  fn with_segment(jpeg: &[u8], marker: u8, payload: &[u8]) -> Vec<u8> {
+    let length = (payload.len() + 2) as u16;
+    let mut out = jpeg[..2].to_vec();
+    out.extend([0xff, marker]);
+    out.extend(length.to_be_bytes());
+    out.extend(payload);
+    out.extend(&jpeg[2..]);
+    out
+  }
+
+  fn with_exif(jpeg: &[u8], entries: &[[u8; 12]]) -> Vec<u8> {
+    let mut app1 = b"Exif\0\0II*\0\x08\0\0\0".to_vec();
+    app1.extend((entries.len() as u16).to_le_bytes());
+    for entry in entries {
+      app1.extend(entry);
+    }
+    app1.extend([0, 0, 0, 0]);
+    with_segment(jpeg, 0xe1, &app1)
+  }
+
+  fn plain_photo() -> Vec<u8> {
+    jpeg(RgbImage::from_pixel(8, 8, RED))
+  }
+
+  // A 16x8 JPEG, red on the left and blue on the right, tagged to be shown rotated 90 degrees
+  // clockwise by a camera that also wrote its make.
+  fn rotated_photo() -> Vec<u8> {
+    let image = RgbImage::from_fn(16, 8, |x, _| if x < 8 { RED } else { BLUE });
+    with_exif(
+      &jpeg(image),
+      &[
+        // Orientation: SHORT, 6 (rotate 90 clockwise).
+        [0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0],
+        // Make: ASCII, 4 bytes stored inline.
+        [0x0f, 0x01, 2, 0, 4, 0, 0, 0, b'I', b'n', b'k', 0],
+      ],
+    )
+  }
+
+  fn is_close(pixel: &Rgb<u8>, expected: Rgb<u8>) -> bool {
+    pixel
+      .0
+      .iter()
+      .zip(expected.0)
+      .all(|(a, b)| a.abs_diff(b) < 40)
+  }
+
+  #[test]
+  fn exif_orientation_is_applied_and_reported() {
+    let (image, report) = decode(&rotated_photo()).unwrap();
+    assert_eq!((image.width(), image.height()), (8, 16));
+    let image = image.to_rgb8();
+    // The left half of the source is now the top half.
+    assert!(is_close(image.get_pixel(4, 3), RED));
+    assert!(is_close(image.get_pixel(4, 12), BLUE));
+    assert!(report.rotated);
+    assert_eq!(report.removed, [Metadata::Exif, Metadata::Camera]);
+
+    let processed = process(&rotated_photo(), None).unwrap();
+    let (stored, stored_report) = decode(&processed.main).unwrap();
+    assert_eq!((stored.width(), stored.height()), (8, 16));
+    assert!(!stored_report.rotated && stored_report.removed.is_empty());
+  }
+
+  #[test]
+  fn crop_uses_oriented_coordinates() {
+    let rect = CropRect {
+      x: 0,
+      y: 0,
+      width: 8,
+      height: 8,
+    };
+    let processed = process(&rotated_photo(), Some(rect)).unwrap();
+    let (image, _) = decode(&processed.main).unwrap();
+    assert_eq!((image.width(), image.height()), (8, 8));
+    assert!(image.to_rgb8().pixels().all(|pixel| is_close(pixel, RED)));
+
+    // Rectangles are clamped to the image, and one that misses it entirely is refused.
+    let (source, _) = decode(&rotated_photo()).unwrap();
+    let clamped = crop(
+      &source,
+      CropRect {
+        x: 4,
+        y: 10,
+        width: 100,
+        height: 100,
+      },
+    )
+    .unwrap();
+    assert_eq!((clamped.width(), clamped.height()), (4, 6));
+    let outside = CropRect {
+      x: 8,
+      y: 0,
+      width: 4,
+      height: 4,
+    };
+    assert!(crop(&source, outside).is_err());
+  }
+
+  #[test]
+  fn gps_and_xmp_are_reported() {
+    // A GPS directory pointer; the directory itself need not be readable to count.
+    let located = with_exif(
+      &plain_photo(),
+      &[[0x25, 0x88, 4, 0, 1, 0, 0, 0, 0, 0, 0, 0]],
+    );
+    let (_, report) = decode(&located).unwrap();
+    assert_eq!(report.removed, [Metadata::Exif, Metadata::Location]);
+
+    let mut xmp = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+    xmp.extend(br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"/>"#);
+    let tagged = with_segment(&plain_photo(), 0xe1, &xmp);
+    let (_, report) = decode(&tagged).unwrap();
+    assert_eq!(report.removed, [Metadata::Xmp]);
+    let processed = process(&tagged, None).unwrap();
+    assert!(decode(&processed.main).unwrap().1.removed.is_empty());
+  }
+
+  #[test]
+  fn clean_images_are_still_re_encoded() {
+    let commented = with_segment(&plain_photo(), 0xfe, b"shot at 12 Hidden Lane");
+    let (_, report) = decode(&commented).unwrap();
+    assert!(report.removed.is_empty() && !report.rotated);
+    let processed = process(&commented, None).unwrap();
+    let hidden = processed
+      .main
+      .windows(b"Hidden Lane".len())
+      .any(|window| window == b"Hidden Lane");
+    assert!(!hidden);
+    assert_eq!(process(&commented, None).unwrap().main, processed.main);
+  }
+}
@@ -0,0 +1,490 @@
+use crate::library::{now_millis, CharacterRecord};
+use crate::storage::write_atomic;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const JOURNAL_FILE: &str = "autosave.journal";
+// Held exclusively while the app runs, so a second instance cannot write over the journal.
+const LOCK_FILE: &str = "journal.lock";
+const COMPACT_AFTER: u64 = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalEntry {
+  seq: u64,
+  tab_id: String,
+  at: u64,
+  #[serde(default)]
+  replace: bool,
+  // The tab's state at this point is in the library already.
+  #[serde(default)]
+  saved: bool,
+  patch: Value,
+}
+
+struct JournaledTab {
+  at: u64,
+  state: Value,
+  saved: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredTab {
+  pub tab_id: String,
+  pub title: Option<String>,
+  pub journaled_at: u64,
+  pub state: Value,
+}
+
+#[derive(Default)]
+struct JournalState {
+  file: Option<File>,
+  seq: u64,
+  since_compaction: u64,
+  tabs: BTreeMap<String, JournaledTab>,
+  recovery: Vec<RecoveredTab>,
+}
+
+pub struct Journal {
+  dir: PathBuf,
+  // None when another instance holds the journal; this one then runs without autosave.
+  lock: Option<File>,
+  state: Mutex<JournalState>,
+}
+
+impl Journal {
+  pub fn open(dir: PathBuf) -> Result<Self, String> {
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let lock = File::create(dir.join(LOCK_FILE)).map_err(|e| e.to_string())?;
+    let lock = match lock.try_lock() {
+      Ok(()) => Some(lock),
+      Err(TryLockError::WouldBlock) => None,
+      Err(TryLockError::Error(e)) => return Err(e.to_string()),
+    };
+    let mut state = JournalState::default();
+    if lock.is_some() {
+      // Whatever is left unsaved was not in the library when the app last stopped, whether it
+      // crashed or quit before a save went through.
+      replay(&dir.join(JOURNAL_FILE), &mut state);
+      state.recovery = state
+        .tabs
+        .iter()
+        .filter(|(_, tab)| !tab.saved)
+        .map(|(tab_id, tab)| RecoveredTab {
+          tab_id: tab_id.clone(),
+          title: tab
+            .state
+            .get("title")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+          journaled_at: tab.at,
+          state: tab.state.clone(),
+        })
+        .collect();
+    }
+    let journal = Self {
+      dir,
+      lock,
+      state: Mutex::new(state),
+    };
+    if journal.lock.is_some() {
+      let mut state = journal.state.lock().map_err(|e| e.to_string())?;
+      journal.rewrite(&mut state)?;
+    }
+    Ok(journal)
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  pub fn append(&self, tab_id: String, patch: Value, replace: bool) -> Result<(), String> {
+    let mut state = self.state.lock().map_err(|e| e.to_string())?;
+    state.seq += 1;
+    let entry = JournalEntry {
+      seq: state.seq,
+      tab_id,
+      at: now_millis(),
+      replace,
+      saved: false,
+      patch,
+    };
+    apply_entry(&mut state.tabs, &entry);
+    let mut line = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+    let file = state.file.as_mut().ok_or_else(|| {
+      if self.lock.is_some() {
+        "Autosave journal is closed.".to_string()
+      } else {
+        "Autosave journal is in use by another instance.".to_string()
+      }
+    })?;
+    file.write_all(&line).map_err(|e| e.to_string())?;
+    file.sync_data().map_err(|e| e.to_string())?;
+    state.since_compaction += 1;
+    if state.since_compaction >= COMPACT_AFTER {
+      self.rewrite(&mut state)?;
+    }
+    Ok(())
+  }
+
+  // Once the library holds exactly what was journaled for a tab, its entries are compacted away
+  // and it is no longer offered for recovery. Edits journaled after `record` was sent keep it.
+  pub fn mark_saved(&self, record: &CharacterRecord) -> Result<(), String> {
+    let mut state = self.state.lock().map_err(|e| e.to_string())?;
+    let Some(tab) = state.tabs.get_mut(&record.id) else {
+      return Ok(());
+    };
+    if tab.saved || !matches_record(&tab.state, record) {
+      return Ok(());
+    }
+    tab.saved = true;
+    if state.file.is_some() {
+      self.rewrite(&mut state)?;
+    }
+    Ok(())
+  }
+
+  pub fn recovery(&self) -> Vec<RecoveredTab> {
+    self
+      .state
+      .lock()
+      .map(|state| state.recovery.clone())
+      .unwrap_or_default()
+  }
+
+  pub fn resolve_recovery(&self, restore: &[String]) -> Result<Vec<RecoveredTab>, String> {
+    let mut state = self.state.lock().map_err(|e| e.to_string())?;
+    let recovery = std::mem::take(&mut state.recovery);
+    let (restored, discarded): (Vec<RecoveredTab>, Vec<RecoveredTab>) = recovery
+      .into_iter()
+      .partition(|tab| restore.contains(&tab.tab_id));
+    for tab in discarded {
+      state.tabs.remove(&tab.tab_id);
+    }
+    self.rewrite(&mut state)?;
+    Ok(restored)
+  }
+
+  // Called on a clean exit. Saves are debounced, so an edit made just before quitting may not
+  // be in the library yet; it stays journaled, as does any recovery the user has not resolved.
+  // The journal is removed only when nothing is left in it.
+  pub fn close(&self) {
+    if self.lock.is_none() {
+      return;
+    }
+    if let Ok(mut state) = self.state.lock() {
+      let pending: Vec<String> = state
+        .recovery
+        .iter()
+        .map(|tab| tab.tab_id.clone())
+        .collect();
+      state
+        .tabs
+        .retain(|tab_id, tab| !tab.saved || pending.contains(tab_id));
+      if state.tabs.is_empty() {
+        state.file = None;
+        let _ = fs::remove_file(self.dir.join(JOURNAL_FILE));
+      } else {
+        let _ = self.rewrite(&mut state);
+        state.file = None;
+      }
+    }
+  }
+
+  // Collapses the journal to one full entry per tab and reopens it for appending.
+  fn rewrite(&self, state: &mut JournalState) -> Result<(), String> {
+    let mut raw = Vec::new();
+    let tabs: Vec<(String, u64, Value, bool)> = state
+      .tabs
+      .iter()
+      .map(|(tab_id, tab)| (tab_id.clone(), tab.at, tab.state.clone(), tab.saved))
+      .collect();
+    for (tab_id, at, value, saved) in tabs {
+      state.seq += 1;
+      let entry = JournalEntry {
+        seq: state.seq,
+        tab_id,
+        at,
+        replace: true,
+        saved,
+        patch: value,
+      };
+      raw.extend(serde_json::to_vec(&entry).map_err(|e| e.to_string())?);
+      raw.push(b'\n');
+    }
+    let path = self.dir.join(JOURNAL_FILE);
+    state.file = None;
+    write_atomic(&path, &raw)?;
+    state.file = Some(
+      OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?,
+    );
+    state.since_compaction = 0;
+    Ok(())
+  }
+}
+
+fn replay(path: &Path, state: &mut JournalState) {
+  let Ok(file) = File::open(path) else {
+    return;
+  };
+  // A torn final line from a crash mid-write simply fails to parse and is skipped.
+  for line in BufReader::new(file).lines().map_while(Result::ok) {
+    if let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) {
+      state.seq = state.seq.max(entry.seq);
+      apply_entry(&mut state.tabs, &entry);
+    }
+  }
+}
+
+fn apply_entry(tabs: &mut BTreeMap<String, JournaledTab>, entry: &JournalEntry) {
+  if entry.patch.is_null() {
+    tabs.remove(&entry.tab_id);
+    return;
+  }
+  let tab = tabs
+    .entry(entry.tab_id.clone())
+    .or_insert_with(|| JournaledTab {
+      at: entry.at,
+      state: Value::Object(Map::new()),
+      saved: false,
+    });
+  tab.at = entry.at;
+  tab.saved = entry.saved;
+  if entry.replace {
+    tab.state = entry.patch.clone();
+  } else {
+    merge_patch(&mut tab.state, &entry.patch);
+  }
+}
+
+// The journal holds the tab as the UI sends it; read it the way the library reads a record.
+fn matches_record(state: &Value, record: &CharacterRecord) -> bool {
+  let mut state = state.clone();
+  let Some(object) = state.as_object_mut() else {
+    return false;
+  };
+  object.insert("id".to_string(), Value::String(record.id.clone()));
+  object.insert("updatedAt".to_string(), Value::from(record.updated_at));
+  serde_json::from_value::<CharacterRecord>(state).is_ok_and(|journaled| &journaled == record)
+}
+
+// RFC 7386 JSON merge patch.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+  let Value::Object(patch) = patch else {
+    *target = patch.clone();
+    return;
+  };
+  if !target.is_object() {
+    *target = Value::Object(Map::new());
+  }
+  let target = target.as_object_mut().expect("target is an object");
+  for (key, value) in patch {
+    if value.is_null() {
+      target.remove(key);
+    } else {
+      merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+    }
+  }
+}
+
+#[tauri::command]
+pub fn journal_append(
+  journal: tauri::State<'_, Journal>,
+  tab_id: String,
+  patch: Value,
+  replace: bool,
+) -> Result<(), String> {
+  journal.append(tab_id, patch, replace)
+}
+
+#[tauri::command]
+pub fn journal_recovery(journal: tauri::State<'_, Journal>) -> Vec<RecoveredTab> {
+  journal.recovery()
+}
+
+#[tauri::command]
+pub fn journal_resolve_recovery(
+  journal: tauri::State<'_, Journal>,
+  restore: Vec<String>,
+) -> Result<Vec<RecoveredTab>, String> {
+  journal.resolve_recovery(&restore)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  use tempfile::TempDir;
+
+  fn scratch() -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = dir.path().join("journal");
+    (dir, journal)
+  }
+
+  // Reopening while the first journal is still alive would fail to take the lock, so tests
+  // simulate a crash by dropping it without `close`.
+  fn crash(journal: Journal) {
+    drop(journal);
+  }
+
+  #[test]
+  fn replays_patches_after_a_torn_write() {
+    let (_scratch, dir) = scratch();
+    let journal = Journal::open(dir.clone()).unwrap();
+    journal
+      .append(
+        "a".into(),
+        json!({ "title": "Ada", "data": { "x": 1 } }),
+        true,
+      )
+      .unwrap();
+    journal
+      .append("a".into(), json!({ "data": { "y": 2 } }), false)
+      .unwrap();
+    journal
+      .append("b".into(), json!({ "title": "Bo" }), true)
+      .unwrap();
+    crash(journal);
+    let mut file = OpenOptions::new()
+      .append(true)
+      .open(dir.join(JOURNAL_FILE))
+      .unwrap();
+    file
+      .write_all(br#"{"seq":99,"tabId":"a","at":1,"patch":{"data":{"#)
+      .unwrap();
+    drop(file);
+
+    let journal = Journal::open(dir.clone()).unwrap();
+    let recovery = journal.recovery();
+    assert_eq!(recovery.len(), 2);
+    assert_eq!(recovery[0].tab_id, "a");
+    assert_eq!(recovery[0].title.as_deref(), Some("Ada"));
+    assert_eq!(recovery[0].state["data"], json!({ "x": 1, "y": 2 }));
+    assert_eq!(recovery[1].state, json!({ "title": "Bo" }));
+    journal.close();
+  }
+
+  #[test]
+  fn closed_tabs_are_not_recovered() {
+    let (_scratch, dir) = scratch();
+    let journal = Journal::open(dir.clone()).unwrap();
+    journal
+      .append("a".into(), json!({ "title": "A" }), true)
+      .unwrap();
+    journal.append("a".into(), Value::Null, true).unwrap();
+    crash(journal);
+    let journal = Journal::open(dir.clone()).unwrap();
+    assert!(journal.recovery().is_empty());
+    journal.close();
+  }
+
+  #[test]
+  fn saved_tabs_are_not_recovered_until_edited_again() {
+    let (_scratch, dir) = scratch();
+    let journal = Journal::open(dir.clone()).unwrap();
+    let tab = json!({
+      "title": "Ada",
+      "data": { "version": 2, "identity": { "Name": "Ada" } },
+      "relationships": { "family": [], "friends": [], "love": [], "hate": [] },
+      "relationshipSelection": {},
+    });
+    journal.append("a".into(), tab.clone(), true).unwrap();
+    journal.append("b".into(), tab.clone(), true).unwrap();
+    let mut record: CharacterRecord = serde_json::from_value(tab).unwrap();
+    record.id = "a".into();
+    journal.mark_saved(&record).unwrap();
+    record.id = "b".into();
+    record.title = "Something else".into();
+    journal.mark_saved(&record).unwrap();
+    journal
+      .append("a".into(), json!({ "title": "Ada" }), false)
+      .unwrap();
+    crash(journal);
+
+    let journal = Journal::open(dir.clone()).unwrap();
+    let ids: Vec<String> = journal
+      .recovery()
+      .into_iter()
+      .map(|tab| tab.tab_id)
+      .collect();
+    assert_eq!(ids, ["a", "b"]);
+    journal.resolve_recovery(&[]).unwrap();
+    journal
+      .append("c".into(), json!({ "title": "C" }), true)
+      .unwrap();
+    let mut saved: CharacterRecord = serde_json::from_value(json!({ "title": "C" })).unwrap();
+    saved.id = "c".into();
+    journal.mark_saved(&saved).unwrap();
+    crash(journal);
+    let journal = Journal::open(dir.clone()).unwrap();
+    assert!(journal.recovery().is_empty());
+    journal.close();
+  }
+
+  #[test]
+  fn clean_exit_keeps_what_the_library_does_not_have() {
+    let (_scratch, dir) = scratch();
+    let journal = Journal::open(dir.clone()).unwrap();
+    journal
+      .append("a".into(), json!({ "title": "Ada" }), true)
+      .unwrap();
+    journal
+      .append("b".into(), json!({ "title": "Bo" }), true)
+      .unwrap();
+    let mut saved: CharacterRecord = serde_json::from_value(json!({ "title": "Bo" })).unwrap();
+    saved.id = "b".into();
+    journal.mark_saved(&saved).unwrap();
+    journal.close();
+    drop(journal);
+
+    // Quitting before the debounced save of "a" went through.
+    let journal = Journal::open(dir.clone()).unwrap();
+    let ids: Vec<String> = journal
+      .recovery()
+      .into_iter()
+      .map(|tab| tab.tab_id)
+      .collect();
+    assert_eq!(ids, ["a"]);
+    // Quitting again without answering keeps the offer.
+    journal.close();
+    drop(journal);
+    let journal = Journal::open(dir.clone()).unwrap();
+    assert_eq!(journal.recovery().len(), 1);
+
+    journal.resolve_recovery(&[]).unwrap();
+    journal.close();
+    assert!(!dir.join(JOURNAL_FILE).exists());
+  }
+
+  #[test]
+  fn a_second_instance_leaves_the_journal_alone() {
+    let (_scratch, dir) = scratch();
+    let first = Journal::open(dir.clone()).unwrap();
+    first
+      .append("a".into(), json!({ "title": "A" }), true)
+      .unwrap();
+    let second = Journal::open(dir.clone()).unwrap();
+    assert!(second.recovery().is_empty());
+    assert!(second
+      .append("b".into(), json!({ "title": "B" }), true)
+      .is_err());
+    second.close();
+    assert!(dir.join(JOURNAL_FILE).exists());
+    crash(first);
+    let reopened = Journal::open(dir.clone()).unwrap();
+    assert_eq!(reopened.recovery().len(), 1);
+    reopened.close();
+  }
+}
@@ -0,0 +1,803 @@
+use crate::library::now_millis;
+use crate::storage::write_json_atomic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::Manager;
+
+pub const LAUNCH_EXIT_EVENT: &str = "launch-exit";
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LaunchErrorCode {
+  NotFound,
+  NotAFile,
+  // The file at a registered path no longer matches the hash it was registered with.
+  HashMismatch,
+  Declined,
+  // The request sets an environment variable that controls which code gets loaded.
+  UnsafeEnvironment,
+  SpawnFailed,
+  Registry,
+  // The handle is unknown or its process has already exited.
+  NotRunning,
+  FocusFailed,
+  TerminateFailed,
+  // Enderfall Hub is running but turned the request down.
+  HubRefused,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchError {
+  pub code: LaunchErrorCode,
+  pub message: String,
+}
+
+impl LaunchError {
+  pub fn new(code: LaunchErrorCode, message: impl Into<String>) -> Self {
+    Self {
+      code,
+      message: message.into(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedApp {
+  pub name: String,
+  // Canonical path, so symlinks and `..` segments cannot stand in for a registered app.
+  pub path: String,
+  pub sha256: String,
+  pub added_at: u64,
+  #[serde(default)]
+  pub policy: LaunchPolicy,
+}
+
+// What the user has agreed a trusted app may be started with.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchPolicy {
+  // Confirmed argument lists; launching without arguments is always allowed.
+  #[serde(default)]
+  pub args: Vec<Vec<String>>,
+  #[serde(default)]
+  pub env: BTreeMap<String, String>,
+  // Where the program starts, its own folder when unset. Only trusted-apps.json can set this;
+  // the webview never chooses a working directory.
+  #[serde(default)]
+  pub cwd: Option<String>,
+}
+
+impl LaunchPolicy {
+  fn covers(&self, request: &LaunchRequest) -> bool {
+    (request.args.is_empty() || self.args.contains(&request.args))
+      && request
+        .env
+        .iter()
+        .all(|(key, value)| self.env.get(key) == Some(value))
+  }
+
+  fn allow(&mut self, request: &LaunchRequest) {
+    if !request.args.is_empty() && !self.args.contains(&request.args) {
+      self.args.push(request.args.clone());
+    }
+    self.env.extend(request.env.clone());
+  }
+}
+
+// Variables that decide which libraries or interpreters a program loads, or where it looks
+// for other executables.
+const UNSAFE_ENV: [&str; 9] = [
+  "PATH",
+  "PATHEXT",
+  "COMSPEC",
+  "NODE_OPTIONS",
+  "PYTHONPATH",
+  "PYTHONHOME",
+  "PERL5LIB",
+  "RUBYOPT",
+  "JAVA_TOOL_OPTIONS",
+];
+const UNSAFE_ENV_PREFIXES: [&str; 2] = ["LD_", "DYLD_"];
+
+fn check_env(env: &BTreeMap<String, String>) -> Result<(), LaunchError> {
+  for key in env.keys() {
+    // Windows treats variable names case-insensitively.
+    let upper = key.to_uppercase();
+    if key.is_empty()
+      || key.contains(['=', '\0'])
+      || UNSAFE_ENV.contains(&upper.as_str())
+      || UNSAFE_ENV_PREFIXES
+        .iter()
+        .any(|prefix| upper.starts_with(prefix))
+    {
+      return Err(LaunchError::new(
+        LaunchErrorCode::UnsafeEnvironment,
+        format!("Launched programs cannot be given {key}."),
+      ));
+    }
+  }
+  Ok(())
+}
+
+// What the allowlist knows about a program.
+#[derive(Debug, Clone)]
+pub enum Trust {
+  Unknown,
+  Trusted(TrustedApp),
+  // Registered, but the file no longer has the hash it was trusted with, as after an update.
+  Changed(TrustedApp),
+}
+
+pub struct LaunchAllowlist {
+  path: PathBuf,
+  apps: Mutex<Vec<TrustedApp>>,
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+  sha256_reader(&mut File::open(path)?)
+}
+
+fn sha256_reader(reader: &mut impl Read) -> io::Result<String> {
+  let mut hasher = Sha256::new();
+  io::copy(reader, &mut hasher)?;
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Windows refuses writes, renames and deletes while this handle is open, so the program that
+// was hashed is the one that starts. Elsewhere the hash is simply checked as late as possible.
+#[cfg(windows)]
+fn open_for_launch(path: &Path) -> io::Result<File> {
+  use std::os::windows::fs::OpenOptionsExt;
+  const FILE_SHARE_READ: u32 = 0x1;
+  fs::OpenOptions::new()
+    .read(true)
+    .share_mode(FILE_SHARE_READ)
+    .open(path)
+}
+
+#[cfg(not(windows))]
+fn open_for_launch(path: &Path) -> io::Result<File> {
+  File::open(path)
+}
+
+fn display_name(path: &Path) -> String {
+  path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+// A launch the user or the allowlist has vouched for, with the hash the program had then.
+#[derive(Debug, Clone)]
+pub struct Authorized {
+  pub path: PathBuf,
+  pub sha256: String,
+  pub args: Vec<String>,
+  pub env: BTreeMap<String, String>,
+  pub cwd: PathBuf,
+}
+
+// Resolves a requested launch target to the real file it names.
+pub fn resolve_target(path: &str) -> Result<PathBuf, LaunchError> {
+  let canonical = fs::canonicalize(path)
+    .map_err(|_| LaunchError::new(LaunchErrorCode::NotFound, format!("{path} was not found.")))?;
+  if !canonical.is_file() {
+    return Err(LaunchError::new(
+      LaunchErrorCode::NotAFile,
+      format!("{path} is not a program."),
+    ));
+  }
+  Ok(canonical)
+}
+
+impl LaunchAllowlist {
+  pub fn open(path: PathBuf) -> Self {
+    let apps = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| serde_json::from_str(&raw).ok())
+      .unwrap_or_default();
+    Self {
+      path,
+      apps: Mutex::new(apps),
+    }
+  }
+
+  pub fn apps(&self) -> Vec<TrustedApp> {
+    self
+      .apps
+      .lock()
+      .map(|apps| apps.clone())
+      .unwrap_or_default()
+  }
+
+  fn registry_error(message: impl fmt::Display) -> LaunchError {
+    LaunchError::new(LaunchErrorCode::Registry, message.to_string())
+  }
+
+  // Checks a canonical path and the hash of its contents against the registered apps.
+  pub fn trusted(&self, canonical: &Path, sha256: &str) -> Result<Trust, LaunchError> {
+    let key = canonical.to_string_lossy();
+    let apps = self.apps.lock().map_err(Self::registry_error)?;
+    Ok(match apps.iter().find(|app| app.path == key) {
+      None => Trust::Unknown,
+      Some(app) if app.sha256 == sha256 => Trust::Trusted(app.clone()),
+      Some(app) => Trust::Changed(app.clone()),
+    })
+  }
+
+  // Trusts the program at `canonical`, or widens its policy, to cover `request`. A program that
+  // changed starts over with only what `request` asks for.
+  pub fn register(
+    &self,
+    canonical: &Path,
+    sha256: &str,
+    request: &LaunchRequest,
+  ) -> Result<TrustedApp, LaunchError> {
+    let path = canonical.to_string_lossy().to_string();
+    let mut apps = self.apps.lock().map_err(Self::registry_error)?;
+    let mut app = apps
+      .iter()
+      .find(|existing| existing.path == path && existing.sha256 == sha256)
+      .cloned()
+      .unwrap_or_else(|| TrustedApp {
+        name: display_name(canonical),
+        path,
+        sha256: sha256.to_string(),
+        added_at: now_millis(),
+        policy: LaunchPolicy::default(),
+      });
+    app.policy.allow(request);
+    apps.retain(|existing| existing.path != app.path);
+    apps.push(app.clone());
+    write_json_atomic(&self.path, &*apps).map_err(Self::registry_error)?;
+    Ok(app)
+  }
+
+  pub fn remove(&self, path: &str) -> Result<(), LaunchError> {
+    let mut apps = self.apps.lock().map_err(Self::registry_error)?;
+    apps.retain(|app| app.path != path);
+    write_json_atomic(&self.path, &*apps).map_err(Self::registry_error)
+  }
+}
+
+fn describe(request: &LaunchRequest) -> String {
+  let mut lines = Vec::new();
+  if !request.args.is_empty() {
+    lines.push(format!("Arguments: {}", request.args.join(" ")));
+  }
+  for (key, value) in &request.env {
+    lines.push(format!("Environment: {key}={value}"));
+  }
+  lines.join("\n")
+}
+
+// Unknown programs, and trusted ones asked to run with arguments or environment the user has
+// not seen, only run after the user says so in a native dialog the webview cannot answer. The
+// file is hashed before asking, so swapping it while the dialog is open is caught at spawn.
+pub fn authorize(
+  allowlist: &LaunchAllowlist,
+  window: &tauri::Window,
+  request: &LaunchRequest,
+) -> Result<Authorized, LaunchError> {
+  check_env(&request.env)?;
+  let canonical = resolve_target(&request.path)?;
+  let sha256 = sha256_file(&canonical).map_err(LaunchAllowlist::registry_error)?;
+  let policy = match allowlist.trusted(&canonical, &sha256)? {
+    Trust::Trusted(app) if app.policy.covers(request) => app.policy,
+    trust => {
+      let (title, intro) = match &trust {
+        Trust::Trusted(app) => (
+          "Launch with new settings?",
+          format!(
+            "Character Creation was asked to start {} with settings you have not confirmed before:",
+            app.name
+          ),
+        ),
+        Trust::Changed(app) => (
+          "Launch changed program?",
+          format!(
+            "{} has changed since you trusted it, for example because it was updated. Character Creation was asked to start it:",
+            app.name
+          ),
+        ),
+        Trust::Unknown => (
+          "Launch unrecognised program?",
+          "Character Creation was asked to start a program it does not recognise:".to_string(),
+        ),
+      };
+      let confirmed = tauri::api::dialog::blocking::ask(
+        Some(window),
+        title,
+        format!(
+          "{intro}\n\n{}\n{}\n\nOnly continue if you trust it. It will be remembered as a trusted Enderfall app.",
+          canonical.display(),
+          describe(request)
+        ),
+      );
+      if !confirmed {
+        return Err(LaunchError::new(
+          LaunchErrorCode::Declined,
+          "Launch cancelled.",
+        ));
+      }
+      allowlist.register(&canonical, &sha256, request)?.policy
+    }
+  };
+  let cwd = match policy.cwd {
+    Some(cwd) => PathBuf::from(cwd),
+    None => canonical
+      .parent()
+      .map(Path::to_path_buf)
+      .unwrap_or_default(),
+  };
+  Ok(Authorized {
+    path: canonical,
+    sha256,
+    args: request.args.clone(),
+    env: request.env.clone(),
+    cwd,
+  })
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchRequest {
+  pub path: String,
+  #[serde(default)]
+  pub args: Vec<String>,
+  #[serde(default)]
+  pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchHandle {
+  pub id: u64,
+  pub pid: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchExit {
+  pub id: u64,
+  // None when the process was killed by a signal or could not be waited on.
+  pub code: Option<i32>,
+  pub success: bool,
+}
+
+// Children we launched, keyed by the handle id handed to the UI.
+#[derive(Default)]
+pub struct Processes {
+  next_id: AtomicU64,
+  children: Arc<Mutex<HashMap<u64, Child>>>,
+}
+
+impl Processes {
+  pub fn spawn(
+    &self,
+    target: &Authorized,
+    on_exit: impl FnOnce(LaunchExit) + Send + 'static,
+  ) -> Result<LaunchHandle, LaunchError> {
+    let mut command = Command::new(&target.path);
+    command
+      .args(&target.args)
+      .envs(&target.env)
+      .current_dir(&target.cwd);
+    if !target.cwd.is_dir() {
+      return Err(LaunchError::new(
+        LaunchErrorCode::NotFound,
+        format!("Working directory {} was not found.", target.cwd.display()),
+      ));
+    }
+    // Held until the child has started; see open_for_launch.
+    let mut program = open_for_launch(&target.path)
+      .map_err(|e| LaunchError::new(LaunchErrorCode::SpawnFailed, e.to_string()))?;
+    let actual = sha256_reader(&mut program)
+      .map_err(|e| LaunchError::new(LaunchErrorCode::SpawnFailed, e.to_string()))?;
+    if actual != target.sha256 {
+      return Err(LaunchError::new(
+        LaunchErrorCode::HashMismatch,
+        format!(
+          "{} changed while it was being launched.",
+          display_name(&target.path)
+        ),
+      ));
+    }
+    let child = command
+      .spawn()
+      .map_err(|e| LaunchError::new(LaunchErrorCode::SpawnFailed, e.to_string()))?;
+    drop(program);
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let pid = child.id();
+    self
+      .children
+      .lock()
+      .map_err(LaunchAllowlist::registry_error)?
+      .insert(id, child);
+    let children = Arc::clone(&self.children);
+    // Polls rather than blocking in wait() so terminate() can still reach the child.
+    thread::spawn(move || loop {
+      thread::sleep(EXIT_POLL_INTERVAL);
+      let Ok(mut children) = children.lock() else {
+        return;
+      };
+      let Some(child) = children.get_mut(&id) else {
+        return;
+      };
+      let status = match child.try_wait() {
+        Ok(None) => continue,
+        Ok(Some(status)) => Some(status),
+        Err(_) => None,
+      };
+      children.remove(&id);
+      drop(children);
+      on_exit(LaunchExit {
+        id,
+        code: status.and_then(|status| status.code()),
+        success: status.is_some_and(|status| status.success()),
+      });
+      return;
+    });
+    Ok(LaunchHandle { id, pid })
+  }
+
+  fn pid(&self, id: u64) -> Result<u32, LaunchError> {
+    self
+      .children
+      .lock()
+      .map_err(LaunchAllowlist::registry_error)?
+      .get(&id)
+      .map(Child::id)
+      .ok_or_else(|| not_running(id))
+  }
+
+  pub fn focus(&self, id: u64) -> Result<(), LaunchError> {
+    focus_process(self.pid(id)?)
+  }
+
+  // The exit still arrives through the watcher, so the UI hears about it once.
+  pub fn terminate(&self, id: u64) -> Result<(), LaunchError> {
+    let mut children = self
+      .children
+      .lock()
+      .map_err(LaunchAllowlist::registry_error)?;
+    let child = children.get_mut(&id).ok_or_else(|| not_running(id))?;
+    child
+      .kill()
+      .map_err(|e| LaunchError::new(LaunchErrorCode::TerminateFailed, e.to_string()))
+  }
+}
+
+fn not_running(id: u64) -> LaunchError {
+  LaunchError::new(
+    LaunchErrorCode::NotRunning,
+    format!("Launch {id} is not running."),
+  )
+}
+
+fn focus_failed(message: impl Into<String>) -> LaunchError {
+  LaunchError::new(LaunchErrorCode::FocusFailed, message)
+}
+
+#[cfg(windows)]
+fn focus_process(pid: u32) -> Result<(), LaunchError> {
+  type Hwnd = isize;
+  #[link(name = "user32")]
+  extern "system" {
+    fn EnumWindows(callback: extern "system" fn(Hwnd, isize) -> i32, param: isize) -> i32;
+    fn GetWindowThreadProcessId(window: Hwnd, process_id: *mut u32) -> u32;
+    fn IsWindowVisible(window: Hwnd) -> i32;
+    fn ShowWindow(window: Hwnd, command: i32) -> i32;
+    fn SetForegroundWindow(window: Hwnd) -> i32;
+  }
+  const SW_RESTORE: i32 = 9;
+  struct Search {
+    pid: u32,
+    found: Hwnd,
+  }
+  extern "system" fn visit(window: Hwnd, param: isize) -> i32 {
+    let search = unsafe { &mut *(param as *mut Search) };
+    let mut owner = 0;
+    unsafe { GetWindowThreadProcessId(window, &mut owner) };
+    if owner == search.pid && unsafe { IsWindowVisible(window) } != 0 {
+      search.found = window;
+      return 0;
+    }
+    1
+  }
+  let mut search = Search { pid, found: 0 };
+  unsafe { EnumWindows(visit, &mut search as *mut Search as isize) };
+  if search.found == 0 {
+    return Err(focus_failed("The launched program has no visible window."));
+  }
+  unsafe {
+    ShowWindow(search.found, SW_RESTORE);
+    SetForegroundWindow(search.found);
+  }
+  Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn focus_process(pid: u32) -> Result<(), LaunchError> {
+  let script =
+    format!("tell application \"System Events\" to set frontmost of (first process whose unix id is {pid}) to true");
+  let status = Command::new("osascript")
+    .args(["-e", &script])
+    .status()
+    .map_err(|e| focus_failed(e.to_string()))?;
+  if !status.success() {
+    return Err(focus_failed(
+      "The launched program could not be brought to the front.",
+    ));
+  }
+  Ok(())
+}
+
+// X11 only; Wayland compositors do not let other clients raise windows.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn focus_process(pid: u32) -> Result<(), LaunchError> {
+  let status = Command::new("xdotool")
+    .args(["search", "--pid", &pid.to_string(), "windowactivate"])
+    .status()
+    .map_err(|_| focus_failed("Focusing other programs needs xdotool."))?;
+  if !status.success() {
+    return Err(focus_failed("The launched program has no window to focus."));
+  }
+  Ok(())
+}
+
+// Async so the confirmation dialog never blocks the main thread.
+#[tauri::command]
+pub async fn launch_process(
+  app: tauri::AppHandle,
+  window: tauri::Window,
+  allowlist: tauri::State<'_, LaunchAllowlist>,
+  processes: tauri::State<'_, Processes>,
+  request: LaunchRequest,
+) -> Result<LaunchHandle, LaunchError> {
+  let target = authorize(&allowlist, &window, &request)?;
+  processes.spawn(&target, move |exit| {
+    let _ = app.emit_all(LAUNCH_EXIT_EVENT, exit);
+  })
+}
+
+#[tauri::command]
+pub async fn launch_path(
+  app: tauri::AppHandle,
+  window: tauri::Window,
+  allowlist: tauri::State<'_, LaunchAllowlist>,
+  processes: tauri::State<'_, Processes>,
+  path: String,
+) -> Result<LaunchHandle, LaunchError> {
+  let request = LaunchRequest {
+    path,
+    ..LaunchRequest::default()
+  };
+  launch_process(app, window, allowlist, processes, request).await
+}
+
+#[tauri::command]
+pub fn launch_focus(processes: tauri::State<'_, Processes>, id: u64) -> Result<(), LaunchError> {
+  processes.focus(id)
+}
+
+#[tauri::command]
+pub fn launch_terminate(
+  processes: tauri::State<'_, Processes>,
+  id: u64,
+) -> Result<(), LaunchError> {
+  processes.terminate(id)
+}
+
+#[tauri::command]
+pub fn launch_trusted_apps(allowlist: tauri::State<'_, LaunchAllowlist>) -> Vec<TrustedApp> {
+  allowlist.apps()
+}
+
+#[tauri::command]
+pub fn launch_untrust(
+  allowlist: tauri::State<'_, LaunchAllowlist>,
+  path: String,
+) -> Result<(), LaunchError> {
+  allowlist.remove(&path)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hub::stand_in;
+  use std::sync::mpsc;
+
+  fn env(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+      .iter()
+      .map(|(key, value)| (key.to_string(), value.to_string()))
+      .collect()
+  }
+
+  fn request(path: &Path, args: &[&str], pairs: &[(&str, &str)]) -> LaunchRequest {
+    LaunchRequest {
+      path: path.to_string_lossy().to_string(),
+      args: args.iter().map(|arg| arg.to_string()).collect(),
+      env: env(pairs),
+    }
+  }
+
+  #[test]
+  fn loader_variables_are_refused_in_any_case() {
+    assert!(check_env(&env(&[("ENDERFALL_PROFILE", "dev")])).is_ok());
+    for key in [
+      "PATH",
+      "Path",
+      "ld_preload",
+      "LD_LIBRARY_PATH",
+      "Dyld_Insert_Libraries",
+    ] {
+      let error = check_env(&env(&[(key, "x")])).unwrap_err();
+      assert_eq!(error.code, LaunchErrorCode::UnsafeEnvironment, "{key}");
+    }
+    for key in ["", "A=B", "A\0B"] {
+      assert!(check_env(&env(&[(key, "x")])).is_err());
+    }
+  }
+
+  #[test]
+  fn policy_covers_only_what_was_allowed() {
+    let path = Path::new("hub");
+    let mut policy = LaunchPolicy::default();
+    assert!(policy.covers(&request(path, &[], &[])));
+    let profile = request(path, &["--profile", "a"], &[("MODE", "dev")]);
+    assert!(!policy.covers(&profile));
+    policy.allow(&profile);
+    assert!(policy.covers(&profile));
+    assert!(policy.covers(&request(path, &[], &[("MODE", "dev")])));
+    assert!(!policy.covers(&request(path, &["--profile", "b"], &[])));
+    assert!(!policy.covers(&request(path, &[], &[("MODE", "prod")])));
+    policy.allow(&profile);
+    assert_eq!(policy.args.len(), 1);
+  }
+
+  #[test]
+  fn trust_follows_the_canonical_path_and_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("bin")).unwrap();
+    let program = dir.path().join("hub");
+    fs::write(&program, b"first build").unwrap();
+    let allowlist = LaunchAllowlist::open(dir.path().join("trusted-apps.json"));
+
+    let canonical = resolve_target(dir.path().join("bin/../hub").to_str().unwrap()).unwrap();
+    assert_eq!(canonical, fs::canonicalize(&program).unwrap());
+    let sha256 = sha256_file(&canonical).unwrap();
+    assert!(matches!(
+      allowlist.trusted(&canonical, &sha256).unwrap(),
+      Trust::Unknown
+    ));
+    let asked = request(&program, &["--quiet"], &[]);
+    allowlist.register(&canonical, &sha256, &asked).unwrap();
+
+    // Survives a restart.
+    let allowlist = LaunchAllowlist::open(dir.path().join("trusted-apps.json"));
+    let Trust::Trusted(app) = allowlist.trusted(&canonical, &sha256).unwrap() else {
+      panic!("expected the program to be trusted");
+    };
+    assert_eq!(app.name, "hub");
+    assert!(app.policy.covers(&asked));
+
+    fs::write(&program, b"second build").unwrap();
+    let updated = sha256_file(&canonical).unwrap();
+    assert!(matches!(
+      allowlist.trusted(&canonical, &updated).unwrap(),
+      Trust::Changed(_)
+    ));
+    // Confirming the update trusts the new build with only what was asked this time.
+    allowlist
+      .register(&canonical, &updated, &request(&program, &[], &[]))
+      .unwrap();
+    let Trust::Trusted(app) = allowlist.trusted(&canonical, &updated).unwrap() else {
+      panic!("expected the update to be trusted");
+    };
+    assert!(!app.policy.covers(&asked));
+    assert!(matches!(
+      allowlist.trusted(&canonical, &sha256).unwrap(),
+      Trust::Changed(_)
+    ));
+    assert_eq!(allowlist.apps().len(), 1);
+  }
+
+  #[test]
+  fn a_program_swapped_after_authorizing_is_not_started() {
+    let dir = tempfile::tempdir().unwrap();
+    let program = dir.path().join("hub");
+    fs::write(&program, b"what the user approved").unwrap();
+    let target = Authorized {
+      sha256: sha256_file(&program).unwrap(),
+      path: program.clone(),
+      args: Vec::new(),
+      env: BTreeMap::new(),
+      cwd: dir.path().to_path_buf(),
+    };
+    fs::write(&program, b"something else").unwrap();
+    let error = Processes::default().spawn(&target, |_| {}).unwrap_err();
+    assert_eq!(error.code, LaunchErrorCode::HashMismatch);
+  }
+
+  // Runs the Hub stand-in, which exits at once with code 2 when given an option it does not know.
+  fn stand_in(args: &[&str]) -> Authorized {
+    let path = stand_in::exe();
+    Authorized {
+      sha256: sha256_file(&path).unwrap(),
+      cwd: path.parent().unwrap().to_path_buf(),
+      args: args.iter().map(|arg| arg.to_string()).collect(),
+      env: BTreeMap::new(),
+      path,
+    }
+  }
+
+  fn spawn(
+    processes: &Processes,
+    target: &Authorized,
+  ) -> (LaunchHandle, mpsc::Receiver<LaunchExit>) {
+    let (sender, exits) = mpsc::channel();
+    let handle = processes
+      .spawn(target, move |exit| {
+        let _ = sender.send(exit);
+      })
+      .unwrap();
+    (handle, exits)
+  }
+
+  fn assert_reported_once(exits: &mpsc::Receiver<LaunchExit>, handle: &LaunchHandle) -> LaunchExit {
+    let exit = exits.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(exit.id, handle.id);
+    // The callback has run and been dropped, so nothing else can arrive.
+    assert!(matches!(
+      exits.recv_timeout(EXIT_POLL_INTERVAL * 4),
+      Err(mpsc::RecvTimeoutError::Disconnected)
+    ));
+    exit
+  }
+
+  #[test]
+  fn exits_are_reported_once_with_their_code() {
+    let processes = Processes::default();
+    let (handle, exits) = spawn(&processes, &stand_in(&["--unknown-option"]));
+    let exit = assert_reported_once(&exits, &handle);
+    assert_eq!(exit.code, Some(2));
+    assert!(!exit.success);
+    for result in [processes.terminate(handle.id), processes.focus(handle.id)] {
+      assert_eq!(result.unwrap_err().code, LaunchErrorCode::NotRunning);
+    }
+  }
+
+  #[test]
+  fn terminated_processes_are_reported_once() {
+    let processes = Processes::default();
+    let endpoint = stand_in::endpoint("terminate");
+    let (first, _) = spawn(&processes, &stand_in(&["--unknown-option"]));
+    let (handle, exits) = spawn(&processes, &stand_in(&["--endpoint", &endpoint]));
+    assert_ne!(first.id, handle.id);
+    processes.terminate(handle.id).unwrap();
+    let exit = assert_reported_once(&exits, &handle);
+    assert!(!exit.success);
+    assert_eq!(
+      processes.terminate(handle.id).unwrap_err().code,
+      LaunchErrorCode::NotRunning
+    );
+    if cfg!(unix) {
+      let _ = fs::remove_file(endpoint);
+    }
+  }
+}
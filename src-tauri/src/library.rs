@@ -0,0 +1,425 @@
+use crate::character::{CharacterSnapshot, RelationshipSelection, Relationships, TabData};
+use crate::history::History;
+use crate::journal::Journal;
+use crate::migrate;
+use crate::portraits::PortraitStore;
+use crate::rules;
+use crate::storage::{is_safe_id, write_json_atomic};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+const SESSION_FILE: &str = "session.json";
+// Legacy tabs that could not be read are kept here rather than lost with the localStorage copy.
+const SKIPPED_LEGACY_FILE: &str = "legacy-tabs-skipped.json";
+const CHARACTERS_DIR: &str = "characters";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CharacterRecord {
+  pub id: String,
+  pub title: String,
+  pub data: Option<TabData>,
+  pub relationships: Relationships,
+  pub relationship_selection: RelationshipSelection,
+  pub updated_at: u64,
+}
+
+impl CharacterRecord {
+  pub fn uuid(&self) -> Option<&str> {
+    match &self.data {
+      Some(TabData::Sheet(snapshot)) => snapshot.uuid.as_deref(),
+      _ => None,
+    }
+  }
+
+  // The tab keeps relationships beside the sheet; fold them back in for whole-character views.
+  pub fn snapshot(&self) -> Option<CharacterSnapshot> {
+    let Some(TabData::Sheet(snapshot)) = &self.data else {
+      return None;
+    };
+    let mut snapshot = snapshot.as_ref().clone();
+    snapshot.relationships = Some(self.relationships.clone());
+    snapshot.relationship_selection = Some(self.relationship_selection.clone());
+    Some(snapshot)
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Session {
+  pub active_id: Option<String>,
+  pub tabs: Vec<String>,
+  pub legacy_imported: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySummary {
+  pub id: String,
+  pub title: String,
+  pub name: Option<String>,
+  pub updated_at: u64,
+  pub open: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredSession {
+  pub active_id: Option<String>,
+  pub tabs: Vec<CharacterRecord>,
+  // Legacy tabs left out of this import, with the reason.
+  pub skipped: Vec<String>,
+}
+
+pub struct Library {
+  root: PathBuf,
+  lock: Mutex<()>,
+}
+
+impl Library {
+  pub fn new(root: PathBuf) -> Self {
+    Self {
+      root,
+      lock: Mutex::new(()),
+    }
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  pub fn characters_dir(&self) -> PathBuf {
+    self.root.join(CHARACTERS_DIR)
+  }
+
+  fn record_path(&self, id: &str) -> Result<PathBuf, String> {
+    if !is_safe_id(id) {
+      return Err(format!("Invalid character id {id:?}."));
+    }
+    Ok(self.characters_dir().join(format!("{id}.json")))
+  }
+
+  pub fn read_session(&self) -> Session {
+    fs::read_to_string(self.root.join(SESSION_FILE))
+      .ok()
+      .and_then(|raw| serde_json::from_str(&raw).ok())
+      .unwrap_or_default()
+  }
+
+  fn write_session(&self, session: &Session) -> Result<(), String> {
+    write_json_atomic(&self.root.join(SESSION_FILE), session)
+  }
+
+  pub fn save_session(&self, active_id: Option<String>, tabs: Vec<String>) -> Result<(), String> {
+    let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+    let mut session = self.read_session();
+    session.active_id = active_id;
+    session.tabs = tabs;
+    self.write_session(&session)
+  }
+
+  pub fn ids(&self) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(self.characters_dir()) else {
+      return Vec::new();
+    };
+    let mut ids: Vec<String> = entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+      .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+      .collect();
+    ids.sort();
+    ids
+  }
+
+  pub fn records(&self) -> Vec<CharacterRecord> {
+    self
+      .ids()
+      .into_iter()
+      .filter_map(|id| self.open(&id).ok())
+      .collect()
+  }
+
+  pub fn find_by_uuid(&self, uuid: &str) -> Option<CharacterRecord> {
+    self
+      .records()
+      .into_iter()
+      .find(|record| record.uuid() == Some(uuid))
+  }
+
+  pub fn list(&self) -> Vec<LibrarySummary> {
+    let session = self.read_session();
+    let mut summaries: Vec<LibrarySummary> = self
+      .records()
+      .into_iter()
+      .map(|record| LibrarySummary {
+        name: match &record.data {
+          Some(TabData::Sheet(snapshot)) => snapshot.name().map(str::to_string),
+          _ => None,
+        },
+        open: session.tabs.contains(&record.id),
+        id: record.id,
+        title: record.title,
+        updated_at: record.updated_at,
+      })
+      .collect();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.updated_at));
+    summaries
+  }
+
+  pub fn open(&self, id: &str) -> Result<CharacterRecord, String> {
+    let raw = fs::read_to_string(self.record_path(id)?).map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+    record_from_value(value)
+  }
+
+  pub fn save(&self, mut record: CharacterRecord) -> Result<CharacterRecord, String> {
+    if let Some(TabData::Sheet(snapshot)) = &record.data {
+      rules::validate_snapshot(snapshot).into_result()?;
+    }
+    record.updated_at = now_millis();
+    let path = self.record_path(&record.id)?;
+    let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+    if let Some(TabData::Sheet(snapshot)) = &mut record.data {
+      if snapshot.uuid.is_none() {
+        // Keep the identity a previous save already handed out before minting a new one.
+        let previous = fs::read_to_string(&path)
+          .ok()
+          .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+          .and_then(|value| value.pointer("/data/uuid")?.as_str().map(str::to_string));
+        snapshot.uuid = Some(previous.unwrap_or_else(|| Uuid::new_v4().to_string()));
+      }
+    }
+    write_json_atomic(&path, &record)?;
+    Ok(record)
+  }
+
+  pub fn delete(&self, id: &str) -> Result<(), String> {
+    let path = self.record_path(id)?;
+    let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+    if path.exists() {
+      fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    let mut session = self.read_session();
+    if session.tabs.iter().any(|tab| tab == id) {
+      session.tabs.retain(|tab| tab != id);
+      if session.active_id.as_deref() == Some(id) {
+        session.active_id = session.tabs.first().cloned();
+      }
+      self.write_session(&session)?;
+    }
+    Ok(())
+  }
+
+  pub fn restore(&self, legacy_tabs: Option<&str>) -> Result<RestoredSession, String> {
+    let (session, skipped) = {
+      let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+      let mut session = self.read_session();
+      let mut skipped = Vec::new();
+      if !session.legacy_imported {
+        if let Some(raw) = legacy_tabs.filter(|raw| !raw.trim().is_empty()) {
+          skipped = self.import_legacy_tabs(raw, &mut session)?;
+        }
+        session.legacy_imported = true;
+        self.write_session(&session)?;
+      }
+      (session, skipped)
+    };
+    let tabs: Vec<CharacterRecord> = session
+      .tabs
+      .iter()
+      .filter_map(|id| self.open(id).ok())
+      .collect();
+    let active_id = session
+      .active_id
+      .filter(|id| tabs.iter().any(|tab| &tab.id == id))
+      .or_else(|| tabs.first().map(|tab| tab.id.clone()));
+    Ok(RestoredSession {
+      active_id,
+      tabs,
+      skipped,
+    })
+  }
+
+  // One-time import of the `cc-sheet-tabs` localStorage payload written by older builds. Every
+  // tab is read before anything is written, and tabs without an id get one from their position,
+  // so an import that fails part way can be retried without duplicating what it already wrote.
+  // Tabs that cannot be read are set aside and reported instead of failing the rest.
+  fn import_legacy_tabs(&self, raw: &str, session: &mut Session) -> Result<Vec<String>, String> {
+    let parsed: Value = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    let tabs = parsed
+      .get("tabs")
+      .and_then(Value::as_array)
+      .cloned()
+      .unwrap_or_default();
+    let stamp = now_millis();
+    let mut records = Vec::new();
+    let mut skipped = Vec::new();
+    let mut skipped_tabs = Vec::new();
+    for (index, mut tab) in tabs.into_iter().enumerate() {
+      let id = tab
+        .get("id")
+        .and_then(Value::as_str)
+        .filter(|id| is_safe_id(id))
+        .map_or_else(|| format!("legacy-tab-{index}"), str::to_string);
+      let original = tab.clone();
+      if let Some(object) = tab.as_object_mut() {
+        object.insert("id".to_string(), Value::String(id.clone()));
+      }
+      match record_from_value(tab) {
+        Ok(mut record) => {
+          if record.title.trim().is_empty() {
+            record.title = format!("Untitled {}", index + 1);
+          }
+          record.updated_at = stamp;
+          records.push(record);
+        }
+        Err(err) => {
+          let title = original
+            .get("title")
+            .and_then(Value::as_str)
+            .filter(|title| !title.trim().is_empty())
+            .map_or_else(|| format!("Tab {}", index + 1), str::to_string);
+          skipped.push(format!("{title}: {err}"));
+          skipped_tabs.push(original);
+        }
+      }
+    }
+    if !skipped_tabs.is_empty() {
+      write_json_atomic(&self.root.join(SKIPPED_LEGACY_FILE), &skipped_tabs)?;
+    }
+    for record in records {
+      write_json_atomic(&self.record_path(&record.id)?, &record)?;
+      if !session.tabs.contains(&record.id) {
+        session.tabs.push(record.id);
+      }
+    }
+    if let Some(active) = parsed.get("activeId").and_then(Value::as_str) {
+      if session.tabs.iter().any(|tab| tab == active) {
+        session.active_id = Some(active.to_string());
+      }
+    }
+    Ok(skipped)
+  }
+}
+
+fn record_from_value(mut value: Value) -> Result<CharacterRecord, String> {
+  if let Some(data) = value.get_mut("data").filter(|data| !data.is_null()) {
+    *data = migrate::migrate_value(data.take())?.snapshot;
+  }
+  serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+pub fn now_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_millis() as u64)
+    .unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn library_list(library: tauri::State<'_, Library>) -> Vec<LibrarySummary> {
+  library.list()
+}
+
+#[tauri::command]
+pub fn library_open(
+  library: tauri::State<'_, Library>,
+  id: String,
+) -> Result<CharacterRecord, String> {
+  library.open(&id)
+}
+
+#[tauri::command]
+pub fn library_save(
+  library: tauri::State<'_, Library>,
+  history: tauri::State<'_, History>,
+  portraits: tauri::State<'_, PortraitStore>,
+  journal: tauri::State<'_, Journal>,
+  mut record: CharacterRecord,
+) -> Result<CharacterRecord, String> {
+  let sent = record.clone();
+  portraits.intern_record(&mut record)?;
+  let record = library.save(record)?;
+  history.record_auto(&record)?;
+  // The save went through either way; at worst the tab is offered for recovery after a crash.
+  let _ = journal.mark_saved(&sent);
+  Ok(record)
+}
+
+#[tauri::command]
+pub fn library_delete(
+  library: tauri::State<'_, Library>,
+  history: tauri::State<'_, History>,
+  id: String,
+) -> Result<(), String> {
+  library.delete(&id)?;
+  history.purge(&id)
+}
+
+#[tauri::command]
+pub fn library_save_session(
+  library: tauri::State<'_, Library>,
+  active_id: Option<String>,
+  tab_ids: Vec<String>,
+) -> Result<(), String> {
+  library.save_session(active_id, tab_ids)
+}
+
+#[tauri::command]
+pub fn library_restore_session(
+  library: tauri::State<'_, Library>,
+  legacy_tabs: Option<String>,
+) -> Result<RestoredSession, String> {
+  library.restore(legacy_tabs.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use tempfile::TempDir;
+
+  fn scratch() -> (TempDir, Library) {
+    let dir = tempfile::tempdir().unwrap();
+    let library = Library::new(dir.path().join("library"));
+    (dir, library)
+  }
+
+  #[test]
+  fn legacy_import_sets_bad_tabs_aside() {
+    let (_dir, library) = scratch();
+    let legacy = r#"{
+      "activeId": "b",
+      "tabs": [
+        { "title": "First", "data": { "identity": { "Name": "Ada" } } },
+        { "id": "broken", "title": "Broken", "data": [1, 2] },
+        { "id": "b", "title": "Second", "data": { "version": 2 } }
+      ]
+    }"#;
+    let restored = library.restore(Some(legacy)).unwrap();
+    let ids: Vec<&str> = restored.tabs.iter().map(|tab| tab.id.as_str()).collect();
+    assert_eq!(ids, ["legacy-tab-0", "b"]);
+    assert_eq!(restored.active_id.as_deref(), Some("b"));
+    assert_eq!(restored.skipped.len(), 1);
+    assert!(restored.skipped[0].starts_with("Broken: "));
+    assert!(library.root().join(SKIPPED_LEGACY_FILE).exists());
+  }
+
+  #[test]
+  fn retried_legacy_import_does_not_duplicate_tabs() {
+    let (_dir, library) = scratch();
+    let legacy = r#"{ "tabs": [{ "title": "First", "data": { "version": 2 } }] }"#;
+    library.restore(Some(legacy)).unwrap();
+    // As if the session write had failed after the records were written.
+    fs::remove_file(library.root().join(SESSION_FILE)).unwrap();
+    let restored = library.restore(Some(legacy)).unwrap();
+    assert_eq!(restored.tabs.len(), 1);
+    assert_eq!(library.ids(), ["legacy-tab-0"]);
+  }
+}
@@ -0,0 +1,324 @@
+use crate::character::{RelationshipEntry, RelationshipKind, Relationships, TabData};
+use crate::library::{CharacterRecord, Library};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkTarget {
+  pub id: String,
+  pub uuid: String,
+  pub name: String,
+  #[serde(skip)]
+  pub portrait: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum LinkStatus {
+  Linked,
+  // The target still exists but its name or portrait moved on since the entry was cached.
+  #[serde(rename_all = "camelCase")]
+  Stale {
+    name_changed: bool,
+    portrait_changed: bool,
+  },
+  Dangling,
+  // Added before UUIDs existed; `target` is the one library character with that name, if any.
+  Unlinked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkCheck {
+  pub category: RelationshipKind,
+  pub entry_id: String,
+  pub entry_name: String,
+  pub target: Option<LinkTarget>,
+  #[serde(flatten)]
+  pub status: LinkStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkReport {
+  pub links: Vec<LinkCheck>,
+  pub stale: usize,
+  pub dangling: usize,
+  pub unlinked: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshedLinks {
+  pub relationships: Relationships,
+  pub changed: bool,
+  pub report: LinkReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryLinks {
+  pub id: String,
+  pub title: String,
+  pub report: LinkReport,
+}
+
+pub struct LinkIndex {
+  targets: Vec<LinkTarget>,
+}
+
+impl LinkIndex {
+  pub fn build(library: &Library) -> Self {
+    Self::from_records(&library.records())
+  }
+
+  pub fn from_records(records: &[CharacterRecord]) -> Self {
+    let targets = records
+      .iter()
+      .filter_map(|record| {
+        let Some(TabData::Sheet(snapshot)) = &record.data else {
+          return None;
+        };
+        Some(LinkTarget {
+          id: record.id.clone(),
+          uuid: snapshot.uuid.clone()?,
+          name: snapshot
+            .name()
+            .map_or_else(|| record.title.clone(), str::to_string),
+          portrait: snapshot.portrait.clone(),
+        })
+      })
+      .collect();
+    Self { targets }
+  }
+
+  fn by_uuid(&self, uuid: &str) -> Option<&LinkTarget> {
+    self.targets.iter().find(|target| target.uuid == uuid)
+  }
+
+  fn by_name(&self, name: &str) -> Option<&LinkTarget> {
+    let name = name.trim().to_lowercase();
+    let mut matches = self
+      .targets
+      .iter()
+      .filter(|target| target.name.trim().to_lowercase() == name);
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first)
+  }
+
+  // Linked entries resolve by UUID; legacy ones only when exactly one character has that name.
+  pub fn resolve(&self, entry: &RelationshipEntry) -> Option<&LinkTarget> {
+    match &entry.target_uuid {
+      Some(uuid) => self.by_uuid(uuid),
+      None => self.by_name(&entry.name),
+    }
+  }
+
+  fn check_entry(&self, category: RelationshipKind, entry: &RelationshipEntry) -> LinkCheck {
+    let (target, status) = match &entry.target_uuid {
+      Some(uuid) => match self.by_uuid(uuid) {
+        Some(target) => {
+          let name_changed = entry.name != target.name;
+          let portrait_changed = entry.portrait != target.portrait;
+          let status = if name_changed || portrait_changed {
+            LinkStatus::Stale {
+              name_changed,
+              portrait_changed,
+            }
+          } else {
+            LinkStatus::Linked
+          };
+          (Some(target.clone()), status)
+        }
+        None => (None, LinkStatus::Dangling),
+      },
+      None => (self.by_name(&entry.name).cloned(), LinkStatus::Unlinked),
+    };
+    LinkCheck {
+      category,
+      entry_id: entry.id.clone(),
+      entry_name: entry.name.clone(),
+      target,
+      status,
+    }
+  }
+
+  pub fn check(&self, relationships: &Relationships) -> LinkReport {
+    let mut report = LinkReport::default();
+    for category in RelationshipKind::ALL {
+      for entry in relationships.get(category) {
+        let check = self.check_entry(category, entry);
+        match check.status {
+          LinkStatus::Linked => {}
+          LinkStatus::Stale { .. } => report.stale += 1,
+          LinkStatus::Dangling => report.dangling += 1,
+          LinkStatus::Unlinked => report.unlinked += 1,
+        }
+        report.links.push(check);
+      }
+    }
+    report
+  }
+
+  // Copies the current name and portrait into linked entries and links unambiguous legacy ones.
+  pub fn refresh(&self, relationships: &Relationships) -> RefreshedLinks {
+    let mut refreshed = relationships.clone();
+    for category in RelationshipKind::ALL {
+      for entry in refreshed.get_mut(category) {
+        if let Some(target) = self.resolve(entry) {
+          entry.target_uuid = Some(target.uuid.clone());
+          entry.name = target.name.clone();
+          entry.portrait = target.portrait.clone();
+        }
+      }
+    }
+    RefreshedLinks {
+      changed: &refreshed != relationships,
+      report: self.check(&refreshed),
+      relationships: refreshed,
+    }
+  }
+}
+
+#[tauri::command]
+pub fn links_check(library: tauri::State<'_, Library>, relationships: Relationships) -> LinkReport {
+  LinkIndex::build(&library).check(&relationships)
+}
+
+#[tauri::command]
+pub fn links_refresh(
+  library: tauri::State<'_, Library>,
+  relationships: Relationships,
+) -> RefreshedLinks {
+  LinkIndex::build(&library).refresh(&relationships)
+}
+
+#[tauri::command]
+pub fn links_audit(library: tauri::State<'_, Library>) -> Vec<LibraryLinks> {
+  let records = library.records();
+  let index = LinkIndex::from_records(&records);
+  records
+    .into_iter()
+    .map(|record| LibraryLinks {
+      report: index.check(&record.relationships),
+      id: record.id,
+      title: record.title,
+    })
+    .filter(|links| !links.report.links.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::CharacterSnapshot;
+
+  fn record(id: &str, uuid: &str, name: &str, portrait: Option<&str>) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(uuid.into()),
+      portrait: portrait.map(str::to_string),
+      ..Default::default()
+    };
+    snapshot.identity.insert("Name".into(), name.into());
+    CharacterRecord {
+      id: id.into(),
+      title: name.into(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..Default::default()
+    }
+  }
+
+  fn entry(id: &str, name: &str, uuid: Option<&str>, portrait: Option<&str>) -> RelationshipEntry {
+    RelationshipEntry {
+      id: id.into(),
+      name: name.into(),
+      portrait: portrait.map(str::to_string),
+      target_uuid: uuid.map(str::to_string),
+      ..Default::default()
+    }
+  }
+
+  fn index() -> LinkIndex {
+    LinkIndex::from_records(&[
+      record("c1", "u-ada", "Ada", Some("ada.png")),
+      record("c2", "u-bo1", "Bo", None),
+      record("c3", "u-bo2", "bo ", None),
+      record("c4", "u-cy", "Cy", None),
+    ])
+  }
+
+  #[test]
+  fn resolves_by_uuid_before_name() {
+    let index = index();
+    let by_uuid = index.resolve(&entry("e1", "Cy", Some("u-ada"), None));
+    assert_eq!(by_uuid.map(|target| target.id.as_str()), Some("c1"));
+    let by_name = index.resolve(&entry("e2", " cy", None, None));
+    assert_eq!(by_name.map(|target| target.id.as_str()), Some("c4"));
+    assert!(index
+      .resolve(&entry("e3", "Ada", Some("u-gone"), None))
+      .is_none());
+  }
+
+  #[test]
+  fn ambiguous_names_do_not_resolve() {
+    let index = index();
+    assert!(index.by_name("Bo").is_none());
+    assert!(index.resolve(&entry("e1", "Bo", None, None)).is_none());
+    assert!(index.by_name("Nobody").is_none());
+  }
+
+  #[test]
+  fn reports_stale_names_and_portraits() {
+    let index = index();
+    let status =
+      |entry: RelationshipEntry| index.check_entry(RelationshipKind::Friends, &entry).status;
+    assert!(matches!(
+      status(entry("e1", "Ada", Some("u-ada"), Some("ada.png"))),
+      LinkStatus::Linked
+    ));
+    assert!(matches!(
+      status(entry("e2", "Ada L.", Some("u-ada"), Some("ada.png"))),
+      LinkStatus::Stale {
+        name_changed: true,
+        portrait_changed: false
+      }
+    ));
+    assert!(matches!(
+      status(entry("e3", "Ada", Some("u-ada"), None)),
+      LinkStatus::Stale {
+        name_changed: false,
+        portrait_changed: true
+      }
+    ));
+    assert!(matches!(
+      status(entry("e4", "Ada", Some("u-gone"), None)),
+      LinkStatus::Dangling
+    ));
+    assert!(matches!(
+      status(entry("e5", "Bo", None, None)),
+      LinkStatus::Unlinked
+    ));
+  }
+
+  #[test]
+  fn refresh_rewrites_cached_names_and_portraits() {
+    let relationships = Relationships {
+      family: vec![entry("e1", "Old name", Some("u-ada"), None)],
+      friends: vec![entry("e2", "cy", None, None), entry("e3", "Bo", None, None)],
+      ..Default::default()
+    };
+    let refreshed = index().refresh(&relationships);
+    assert!(refreshed.changed);
+    let ada = &refreshed.relationships.family[0];
+    assert_eq!(ada.name, "Ada");
+    assert_eq!(ada.portrait.as_deref(), Some("ada.png"));
+    let cy = &refreshed.relationships.friends[0];
+    assert_eq!(
+      (cy.name.as_str(), cy.target_uuid.as_deref()),
+      ("Cy", Some("u-cy"))
+    );
+    assert_eq!(refreshed.relationships.friends[1], relationships.friends[1]);
+    assert_eq!((refreshed.report.stale, refreshed.report.unlinked), (0, 1));
+    assert!(!index().refresh(&refreshed.relationships).changed);
+  }
+}
@@ -1,6 +1,32 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod bundle;
+mod character;
+mod cli;
+mod diff;
+mod entitlement;
+mod export;
+mod family;
+mod gc;
+mod gedcom;
+mod graph;
+mod history;
+mod hub;
+mod imaging;
+mod journal;
+mod launcher;
+mod library;
+mod links;
+mod merge;
+mod migrate;
+mod pdf;
+mod portraits;
+mod reciprocity;
+mod rules;
+mod storage;
+mod tree;
+
 use std::path::Path;
 use tauri::Manager;
 
@@ -25,37 +51,111 @@ fn apply_window_icon(app: &tauri::App) {
   }
 }
 
-#[tauri::command]
-fn launch_path(path: String) -> Result<(), String> {
-  let target = Path::new(&path);
-  if !target.exists() {
-    return Err("File not found.".to_string());
-  }
-  std::process::Command::new(target)
-    .spawn()
-    .map_err(|e| e.to_string())?;
-  Ok(())
-}
-
 fn main() {
+  let context = tauri::generate_context!();
+  let args: Vec<String> = std::env::args().collect();
+  if let Some(code) = cli::run(&args, context.config()) {
+    std::process::exit(code);
+  }
   let builder = tauri::Builder::default();
   let builder = if cfg!(debug_assertions) {
     builder
   } else {
-    builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+    builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
       if let Some(window) = app.get_window("main") {
         let _ = window.show();
         let _ = window.set_focus();
       }
+      let files = cli::file_args(&args, Path::new(&cwd));
+      if !files.is_empty() {
+        app.state::<cli::PendingFiles>().push(files);
+        let _ = app.emit_all(cli::OPEN_FILES_EVENT, ());
+      }
     }))
   };
-  let builder = builder.setup(|app| {
+  let builder = builder.setup(move |app| {
     #[cfg(target_os = "windows")]
     apply_window_icon(app);
+    let cwd = std::env::current_dir().unwrap_or_default();
+    app.manage(cli::PendingFiles::new(cli::file_args(&args, &cwd)));
+    let data_dir = storage::enderfall_dir(app.path_resolver().app_data_dir())?;
+    app.manage(library::Library::new(data_dir.join("library")));
+    app.manage(portraits::PortraitStore::new(data_dir.join("portraits")));
+    app.manage(history::History::new(data_dir.join("library").join("history")));
+    app.manage(journal::Journal::open(data_dir.join("journal"))?);
+    // Opt-in; otherwise unreferenced portraits stay until `gc --delete` or the portrait_gc
+    // command removes them.
+    if gc::GcSettings::open(&data_dir).sweep_on_startup {
+      let gc_dir = data_dir.clone();
+      std::thread::spawn(move || {
+        let _ = gc::sweep_data_dir(&gc_dir, true);
+      });
+    }
+    app.manage(launcher::LaunchAllowlist::open(data_dir.join("trusted-apps.json")));
+    app.manage(launcher::Processes::default());
+    app.manage(entitlement::Entitlements::open(data_dir));
     Ok(())
   });
   builder
-    .invoke_handler(tauri::generate_handler![launch_path])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .register_uri_scheme_protocol(portraits::PORTRAIT_PROTOCOL, |app, request| {
+      portraits::respond(&app.state::<portraits::PortraitStore>(), request.uri())
+    })
+    .invoke_handler(tauri::generate_handler![
+      launcher::launch_path,
+      launcher::launch_process,
+      launcher::launch_focus,
+      launcher::launch_terminate,
+      launcher::launch_trusted_apps,
+      launcher::launch_untrust,
+      bundle::bundle_export,
+      bundle::bundle_import,
+      character::load_character,
+      cli::cli_take_pending,
+      character::save_character,
+      diff::diff_characters,
+      diff::diff_library,
+      entitlement::entitlement_refresh,
+      gedcom::gedcom_export,
+      gedcom::gedcom_import,
+      graph::graph_build,
+      graph::graph_export,
+      history::history_list,
+      history::history_preview,
+      history::history_restore,
+      history::history_checkpoint,
+      history::history_retention,
+      history::history_set_retention,
+      hub::hub_status,
+      hub::hub_open,
+      journal::journal_append,
+      journal::journal_recovery,
+      journal::journal_resolve_recovery,
+      merge::merge_characters,
+      migrate::migrate_character,
+      portraits::portrait_store,
+      portraits::portrait_data_url,
+      gc::portrait_gc,
+      export::export_pdf,
+      library::library_list,
+      library::library_open,
+      library::library_save,
+      library::library_delete,
+      library::library_save_session,
+      library::library_restore_session,
+      links::links_check,
+      links::links_refresh,
+      links::links_audit,
+      reciprocity::reciprocity_lint,
+      reciprocity::reciprocity_fix,
+      rules::validate_character,
+      tree::tree_layout,
+      tree::tree_export
+    ])
+    .build(context)
+    .expect("error while running tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event {
+        app.state::<journal::Journal>().close();
+      }
+    });
 }
@@ -0,0 +1,427 @@
+use crate::character::{CharacterSnapshot, SNAPSHOT_VERSION};
+use crate::rules::{self, ValidationReport};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+// A field is addressed by its path through the snapshot, e.g. `["skills", "stats", "Cooking"]`.
+// Notes are addressed by slot (`["notes", "2"]`) and relationships by entry id
+// (`["relationships", "family", "<id>"]`); both merge as whole values.
+type FieldPath = Vec<String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeSide {
+  Base,
+  Ours,
+  Theirs,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+  pub path: FieldPath,
+  pub base: Option<Value>,
+  pub ours: Option<Value>,
+  pub theirs: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictResolution {
+  pub path: FieldPath,
+  pub take: Option<MergeSide>,
+  // Used when `take` is absent; `null` removes the field.
+  #[serde(default)]
+  pub value: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+  pub snapshot: CharacterSnapshot,
+  pub merged_from_ours: Vec<FieldPath>,
+  pub merged_from_theirs: Vec<FieldPath>,
+  pub conflicts: Vec<MergeConflict>,
+  pub validation: ValidationReport,
+}
+
+// Unresolved conflicts keep our side so the returned snapshot is always usable.
+pub fn merge_snapshots(
+  base: &CharacterSnapshot,
+  ours: &CharacterSnapshot,
+  theirs: &CharacterSnapshot,
+  resolutions: &[ConflictResolution],
+) -> Result<MergeResult, String> {
+  let base_value = serde_json::to_value(base).map_err(|e| e.to_string())?;
+  let ours_value = serde_json::to_value(ours).map_err(|e| e.to_string())?;
+  let theirs_value = serde_json::to_value(theirs).map_err(|e| e.to_string())?;
+  let base_fields = flatten(&base_value);
+  let ours_fields = flatten(&ours_value);
+  let theirs_fields = flatten(&theirs_value);
+
+  let paths: BTreeSet<&FieldPath> = base_fields
+    .keys()
+    .chain(ours_fields.keys())
+    .chain(theirs_fields.keys())
+    .collect();
+  let mut merged = BTreeMap::new();
+  let mut merged_from_ours = Vec::new();
+  let mut merged_from_theirs = Vec::new();
+  let mut conflicts = Vec::new();
+  for path in paths {
+    let b = base_fields.get(path);
+    let o = ours_fields.get(path);
+    let t = theirs_fields.get(path);
+    let chosen = if o == t {
+      o
+    } else if o == b {
+      merged_from_theirs.push(path.clone());
+      t
+    } else if t == b {
+      merged_from_ours.push(path.clone());
+      o
+    } else if let Some(resolution) = resolutions.iter().find(|r| &r.path == path) {
+      match resolution.take {
+        Some(MergeSide::Base) => b,
+        Some(MergeSide::Ours) => o,
+        Some(MergeSide::Theirs) => t,
+        None => Some(&resolution.value).filter(|value| !value.is_null()),
+      }
+    } else {
+      conflicts.push(MergeConflict {
+        path: path.clone(),
+        base: b.cloned(),
+        ours: o.cloned(),
+        theirs: t.cloned(),
+      });
+      o
+    };
+    if let Some(value) = chosen {
+      merged.insert(path.clone(), value.clone());
+    }
+  }
+
+  let order = relationship_order(&[&ours_value, &theirs_value, &base_value]);
+  let snapshot: CharacterSnapshot =
+    serde_json::from_value(unflatten(merged, &order, ours.notes.len()))
+      .map_err(|e| e.to_string())?;
+  Ok(MergeResult {
+    validation: rules::validate_snapshot(&snapshot),
+    snapshot,
+    merged_from_ours,
+    merged_from_theirs,
+    conflicts,
+  })
+}
+
+fn flatten(snapshot: &Value) -> BTreeMap<FieldPath, Value> {
+  let mut fields = BTreeMap::new();
+  let Some(root) = snapshot.as_object() else {
+    return fields;
+  };
+  for (key, value) in root {
+    match key.as_str() {
+      "version" => {}
+      "notes" => {
+        for (index, note) in value.as_array().into_iter().flatten().enumerate() {
+          if !is_blank_note(note) {
+            fields.insert(vec![key.clone(), index.to_string()], note.clone());
+          }
+        }
+      }
+      "relationships" => {
+        for (category, entries) in value.as_object().into_iter().flatten() {
+          for entry in entries.as_array().into_iter().flatten() {
+            if let Some(id) = entry.get("id").and_then(Value::as_str) {
+              fields.insert(
+                vec![key.clone(), category.clone(), id.to_string()],
+                entry.clone(),
+              );
+            }
+          }
+        }
+      }
+      _ => flatten_object(vec![key.clone()], value, &mut fields),
+    }
+  }
+  fields
+}
+
+fn flatten_object(path: FieldPath, value: &Value, fields: &mut BTreeMap<FieldPath, Value>) {
+  match value {
+    Value::Null => {}
+    Value::Object(object) => {
+      for (key, child) in object {
+        let mut child_path = path.clone();
+        child_path.push(key.clone());
+        flatten_object(child_path, child, fields);
+      }
+    }
+    _ => {
+      fields.insert(path, value.clone());
+    }
+  }
+}
+
+fn is_blank_note(note: &Value) -> bool {
+  !["title", "text"].iter().any(|key| {
+    note
+      .get(key)
+      .and_then(Value::as_str)
+      .is_some_and(|text| !text.trim().is_empty())
+  })
+}
+
+// Relationship lists are ordered on the sheet; keep our order, then anything only they added.
+fn relationship_order(sides: &[&Value]) -> BTreeMap<String, Vec<String>> {
+  let mut order: BTreeMap<String, Vec<String>> = BTreeMap::new();
+  for side in sides {
+    let categories = side.get("relationships").and_then(Value::as_object);
+    for (category, entries) in categories.into_iter().flatten() {
+      let ids = order.entry(category.clone()).or_default();
+      for entry in entries.as_array().into_iter().flatten() {
+        if let Some(id) = entry.get("id").and_then(Value::as_str) {
+          if !ids.iter().any(|known| known == id) {
+            ids.push(id.to_string());
+          }
+        }
+      }
+    }
+  }
+  order
+}
+
+fn unflatten(
+  fields: BTreeMap<FieldPath, Value>,
+  order: &BTreeMap<String, Vec<String>>,
+  note_slots: usize,
+) -> Value {
+  let mut root = Map::new();
+  let mut notes: BTreeMap<usize, Value> = BTreeMap::new();
+  let mut relationships: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+  for (path, value) in fields {
+    match path.as_slice() {
+      [notes_key, index] if notes_key == "notes" => {
+        if let Ok(index) = index.parse() {
+          notes.insert(index, value);
+        }
+      }
+      [key, category, id] if key == "relationships" => {
+        relationships
+          .entry(category.clone())
+          .or_default()
+          .insert(id.clone(), value);
+      }
+      _ => insert_at(&mut root, &path, value),
+    }
+  }
+
+  let slots = notes
+    .keys()
+    .next_back()
+    .map_or(note_slots, |last| note_slots.max(last + 1));
+  let notes: Vec<Value> = (0..slots)
+    .map(|index| {
+      notes
+        .remove(&index)
+        .unwrap_or_else(|| serde_json::json!({ "title": "", "text": "" }))
+    })
+    .collect();
+  root.insert("notes".to_string(), Value::Array(notes));
+
+  if !relationships.is_empty() {
+    let mut categories = Map::new();
+    for (category, mut entries) in relationships {
+      let ids = order.get(&category).cloned().unwrap_or_default();
+      let list: Vec<Value> = ids.iter().filter_map(|id| entries.remove(id)).collect();
+      categories.insert(category, Value::Array(list));
+    }
+    root.insert("relationships".to_string(), Value::Object(categories));
+  }
+  root.insert("version".to_string(), Value::from(SNAPSHOT_VERSION));
+  Value::Object(root)
+}
+
+fn insert_at(root: &mut Map<String, Value>, path: &[String], value: Value) {
+  let Some((last, parents)) = path.split_last() else {
+    return;
+  };
+  let mut object = root;
+  for key in parents {
+    let slot = object
+      .entry(key.clone())
+      .or_insert_with(|| Value::Object(Map::new()));
+    if !slot.is_object() {
+      *slot = Value::Object(Map::new());
+    }
+    object = slot.as_object_mut().expect("slot is an object");
+  }
+  object.insert(last.clone(), value);
+}
+
+#[tauri::command]
+pub fn merge_characters(
+  base: CharacterSnapshot,
+  ours: CharacterSnapshot,
+  theirs: CharacterSnapshot,
+  resolutions: Option<Vec<ConflictResolution>>,
+) -> Result<MergeResult, String> {
+  merge_snapshots(&base, &ours, &theirs, &resolutions.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::{Note, RelationshipEntry, Relationships};
+
+  fn path(parts: &[&str]) -> FieldPath {
+    parts.iter().map(|part| part.to_string()).collect()
+  }
+
+  fn sheet(stats: &[(&str, u8)]) -> CharacterSnapshot {
+    let mut snapshot = CharacterSnapshot::default();
+    snapshot.identity.insert("Name".into(), "Ada".into());
+    for (stat, value) in stats {
+      snapshot.skills.stats.insert(stat.to_string(), *value);
+    }
+    snapshot
+  }
+
+  fn friend(id: &str, name: &str) -> RelationshipEntry {
+    RelationshipEntry {
+      id: id.into(),
+      name: name.into(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn takes_each_sides_own_changes() {
+    let base = sheet(&[("Cooking", 1), ("Combat", 1)]);
+    let ours = sheet(&[("Cooking", 3), ("Combat", 1)]);
+    let theirs = sheet(&[("Cooking", 1), ("Combat", 2)]);
+    let result = merge_snapshots(&base, &ours, &theirs, &[]).unwrap();
+    assert!(result.conflicts.is_empty());
+    assert_eq!(result.snapshot.skills.stats["Cooking"], 3);
+    assert_eq!(result.snapshot.skills.stats["Combat"], 2);
+    assert_eq!(
+      result.merged_from_ours,
+      vec![path(&["skills", "stats", "Cooking"])]
+    );
+    assert_eq!(
+      result.merged_from_theirs,
+      vec![path(&["skills", "stats", "Combat"])]
+    );
+    assert!(result.validation.valid);
+  }
+
+  #[test]
+  fn reports_conflicts_and_keeps_ours_until_resolved() {
+    let base = sheet(&[("Cooking", 1)]);
+    let ours = sheet(&[("Cooking", 3)]);
+    let theirs = sheet(&[("Cooking", 2)]);
+    let result = merge_snapshots(&base, &ours, &theirs, &[]).unwrap();
+    assert_eq!(result.conflicts.len(), 1);
+    let conflict = &result.conflicts[0];
+    assert_eq!(conflict.path, path(&["skills", "stats", "Cooking"]));
+    assert_eq!(
+      (&conflict.base, &conflict.ours, &conflict.theirs),
+      (&Some(1.into()), &Some(3.into()), &Some(2.into()))
+    );
+    assert_eq!(result.snapshot.skills.stats["Cooking"], 3);
+  }
+
+  #[test]
+  fn applies_resolutions() {
+    let base = sheet(&[("Cooking", 1), ("Combat", 1)]);
+    let ours = sheet(&[("Cooking", 3), ("Combat", 3)]);
+    let theirs = sheet(&[("Cooking", 2), ("Combat", 2)]);
+    let resolutions = [
+      ConflictResolution {
+        path: path(&["skills", "stats", "Cooking"]),
+        take: Some(MergeSide::Theirs),
+        value: Value::Null,
+      },
+      ConflictResolution {
+        path: path(&["skills", "stats", "Combat"]),
+        take: None,
+        value: 4.into(),
+      },
+    ];
+    let result = merge_snapshots(&base, &ours, &theirs, &resolutions).unwrap();
+    assert!(result.conflicts.is_empty());
+    assert_eq!(result.snapshot.skills.stats["Cooking"], 2);
+    assert_eq!(result.snapshot.skills.stats["Combat"], 4);
+  }
+
+  #[test]
+  fn a_removal_against_an_edit_conflicts() {
+    let base = sheet(&[("Cooking", 1)]);
+    let ours = sheet(&[]);
+    let theirs = sheet(&[("Cooking", 2)]);
+    let result = merge_snapshots(&base, &ours, &theirs, &[]).unwrap();
+    assert_eq!(result.conflicts.len(), 1);
+    assert_eq!(result.conflicts[0].ours, None);
+    assert!(!result.snapshot.skills.stats.contains_key("Cooking"));
+  }
+
+  #[test]
+  fn merges_notes_by_slot_and_relationships_by_id() {
+    let mut base = sheet(&[]);
+    base.notes = vec![Note::default(), Note::default()];
+    base.relationships = Some(Relationships {
+      friends: vec![friend("a", "Bo")],
+      ..Default::default()
+    });
+    let mut ours = base.clone();
+    ours.notes[0].text = "Ours".into();
+    ours.relationships.as_mut().unwrap().friends[0].name = "Bob".into();
+    let mut theirs = base.clone();
+    theirs.notes[1].text = "Theirs".into();
+    theirs
+      .relationships
+      .as_mut()
+      .unwrap()
+      .friends
+      .push(friend("b", "Cy"));
+    let result = merge_snapshots(&base, &ours, &theirs, &[]).unwrap();
+    assert!(result.conflicts.is_empty());
+    let notes: Vec<&str> = result
+      .snapshot
+      .notes
+      .iter()
+      .map(|n| n.text.as_str())
+      .collect();
+    assert_eq!(notes, ["Ours", "Theirs"]);
+    let friends: Vec<&str> = result
+      .snapshot
+      .relationships
+      .as_ref()
+      .unwrap()
+      .friends
+      .iter()
+      .map(|entry| entry.name.as_str())
+      .collect();
+    assert_eq!(friends, ["Bob", "Cy"]);
+  }
+
+  #[test]
+  fn both_sides_editing_one_relationship_conflicts_as_a_whole() {
+    let mut base = sheet(&[]);
+    base.relationships = Some(Relationships {
+      love: vec![friend("a", "Bo")],
+      ..Default::default()
+    });
+    let mut ours = base.clone();
+    ours.relationships.as_mut().unwrap().love[0].name = "Bob".into();
+    let mut theirs = base.clone();
+    theirs.relationships.as_mut().unwrap().love[0].relation = Some("Spouse".into());
+    let result = merge_snapshots(&base, &ours, &theirs, &[]).unwrap();
+    assert_eq!(result.conflicts.len(), 1);
+    assert_eq!(
+      result.conflicts[0].path,
+      path(&["relationships", "love", "a"])
+    );
+  }
+}
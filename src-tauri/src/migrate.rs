@@ -0,0 +1,462 @@
+use crate::character::{Section, SNAPSHOT_VERSION};
+use crate::rules::{self, SLIDER_MAX};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+// Stat labels that were renamed when the sheet moved from the static page to StatDots.
+const RENAMED_STATS: [(Section, &str, &str); 2] = [
+  (Section::Skills, "Animal Handling", "Animal Care"),
+  (Section::Skills, "Child Handling", "Child Care"),
+];
+
+const LEGACY_SLIDER_MAX: f64 = 100.0;
+
+enum LegacyField {
+  Note(&'static str),
+  Identity(&'static str),
+  Dots(Section, &'static str, u8),
+  Slider(Section, &'static str),
+  Trait(Section, &'static str),
+}
+
+// Field order of `.frame input, .frame textarea` on the static sheet that wrote positional
+// `fields` exports.
+const LEGACY_FIELDS: &[LegacyField] = &[
+  LegacyField::Note("Personality"),
+  LegacyField::Note("Hobbies"),
+  LegacyField::Note("Food-Related"),
+  LegacyField::Note("Habits"),
+  LegacyField::Note("Quirks"),
+  LegacyField::Note("Extras"),
+  LegacyField::Identity("Name"),
+  LegacyField::Identity("Nickname"),
+  LegacyField::Identity("Race/Species"),
+  LegacyField::Identity("Age"),
+  LegacyField::Identity("Gender"),
+  LegacyField::Identity("Birthday"),
+  LegacyField::Identity("Class/Job"),
+  LegacyField::Identity("Height"),
+  LegacyField::Dots(Section::Body, "Strength", 6),
+  LegacyField::Dots(Section::Body, "Dexterity", 6),
+  LegacyField::Dots(Section::Body, "Health", 6),
+  LegacyField::Dots(Section::Body, "Energy", 6),
+  LegacyField::Dots(Section::Body, "Beauty", 6),
+  LegacyField::Dots(Section::Body, "Style", 6),
+  LegacyField::Dots(Section::Skills, "Perception", 4),
+  LegacyField::Dots(Section::Skills, "Communication", 4),
+  LegacyField::Dots(Section::Skills, "Persuasion", 4),
+  LegacyField::Dots(Section::Skills, "Mediation", 4),
+  LegacyField::Dots(Section::Skills, "Literacy", 4),
+  LegacyField::Dots(Section::Skills, "Creativity", 4),
+  LegacyField::Dots(Section::Skills, "Cooking", 4),
+  LegacyField::Dots(Section::Skills, "Combat", 4),
+  LegacyField::Dots(Section::Skills, "Survival", 4),
+  LegacyField::Dots(Section::Skills, "Stealth", 4),
+  LegacyField::Dots(Section::Skills, "Tech Savvy", 4),
+  LegacyField::Dots(Section::Skills, "Street Smarts", 4),
+  LegacyField::Dots(Section::Skills, "Seduction", 4),
+  LegacyField::Dots(Section::Skills, "Animal Handling", 4),
+  LegacyField::Dots(Section::Skills, "Child Handling", 4),
+  LegacyField::Dots(Section::Priorities, "Justice", 4),
+  LegacyField::Dots(Section::Priorities, "Truth", 4),
+  LegacyField::Dots(Section::Priorities, "Power", 4),
+  LegacyField::Dots(Section::Priorities, "Fame", 4),
+  LegacyField::Dots(Section::Priorities, "Wealth", 4),
+  LegacyField::Dots(Section::Priorities, "Family", 4),
+  LegacyField::Dots(Section::Priorities, "Friends", 4),
+  LegacyField::Dots(Section::Priorities, "Love", 4),
+  LegacyField::Dots(Section::Priorities, "Home", 4),
+  LegacyField::Dots(Section::Priorities, "Health", 4),
+  LegacyField::Dots(Section::Priorities, "Approval", 4),
+  LegacyField::Dots(Section::Mind, "Intelligence", 5),
+  LegacyField::Dots(Section::Mind, "Happiness", 5),
+  LegacyField::Dots(Section::Mind, "Spirituality", 5),
+  LegacyField::Dots(Section::Mind, "Confidence", 5),
+  LegacyField::Dots(Section::Mind, "Humor", 5),
+  LegacyField::Dots(Section::Mind, "Anxiety", 5),
+  LegacyField::Dots(Section::Mind, "Patience", 5),
+  LegacyField::Dots(Section::Mind, "Passion", 5),
+  LegacyField::Slider(Section::Mind, "Nice / Mean"),
+  LegacyField::Slider(Section::Mind, "Brave / Cowardly"),
+  LegacyField::Slider(Section::Mind, "Pacifist / Violent"),
+  LegacyField::Slider(Section::Mind, "Thoughtful / Impulsive"),
+  LegacyField::Slider(Section::Mind, "Agreeable / Contrary"),
+  LegacyField::Slider(Section::Mind, "Idealistic / Pragmatic"),
+  LegacyField::Slider(Section::Mind, "Frugal / Big Spender"),
+  LegacyField::Slider(Section::Mind, "Extrovert / Introvert"),
+  LegacyField::Slider(Section::Mind, "Collected / Wild"),
+  LegacyField::Trait(Section::Mind, "Ambitious"),
+  LegacyField::Trait(Section::Mind, "Possessive"),
+  LegacyField::Trait(Section::Mind, "Stubborn"),
+  LegacyField::Trait(Section::Mind, "Jealous"),
+  LegacyField::Trait(Section::Mind, "Decisive"),
+  LegacyField::Trait(Section::Mind, "Perfectionist"),
+  LegacyField::Dots(Section::Social, "Charisma", 5),
+  LegacyField::Dots(Section::Social, "Empathy", 5),
+  LegacyField::Dots(Section::Social, "Generosity", 5),
+  LegacyField::Dots(Section::Social, "Wealth", 5),
+  LegacyField::Dots(Section::Social, "Aggression", 5),
+  LegacyField::Dots(Section::Social, "Libido", 5),
+  LegacyField::Slider(Section::Social, "Honest / Deceptive"),
+  LegacyField::Slider(Section::Social, "Leader / Follower"),
+  LegacyField::Slider(Section::Social, "Polite / Rude"),
+  LegacyField::Slider(Section::Social, "Political / Indifferent"),
+  LegacyField::Trait(Section::Social, "Cool"),
+  LegacyField::Trait(Section::Social, "Flirty"),
+  LegacyField::Trait(Section::Social, "Cute"),
+  LegacyField::Trait(Section::Social, "Obedient"),
+  LegacyField::Trait(Section::Social, "Fun"),
+  LegacyField::Trait(Section::Social, "Forgiving"),
+  LegacyField::Trait(Section::Social, "Gullible"),
+  LegacyField::Trait(Section::Social, "Scary"),
+];
+
+struct Migration {
+  from: u32,
+  description: &'static str,
+  apply: fn(&mut Map<String, Value>),
+}
+
+const MIGRATIONS: [Migration; 2] = [
+  Migration {
+    from: 0,
+    description: "Expand positional `fields` export into keyed sections",
+    apply: expand_positional_fields,
+  },
+  Migration {
+    from: 1,
+    description: "Normalise `.dot-row`/`.stat-chip` stats and rescale 0-100 sliders",
+    apply: normalise_legacy_sections,
+  },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedMigration {
+  pub from: u32,
+  pub to: u32,
+  pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+  pub snapshot: Value,
+  pub blank: bool,
+  pub from_version: u32,
+  pub to_version: u32,
+  pub applied: Vec<AppliedMigration>,
+}
+
+pub fn migrate_value(mut value: Value) -> Result<MigrationReport, String> {
+  let detected = detect_version(&value)?;
+  let Some(root) = value.as_object_mut() else {
+    return Err("Character snapshot must be a JSON object.".to_string());
+  };
+  let blank = root
+    .get("__blank")
+    .and_then(Value::as_bool)
+    .unwrap_or(false);
+  let mut version = detected;
+  let mut applied = Vec::new();
+  if !blank {
+    while version < SNAPSHOT_VERSION {
+      let migration = MIGRATIONS
+        .iter()
+        .find(|migration| migration.from == version)
+        .ok_or_else(|| format!("No migration from snapshot version {version}."))?;
+      (migration.apply)(root);
+      applied.push(AppliedMigration {
+        from: version,
+        to: version + 1,
+        description: migration.description.to_string(),
+      });
+      version += 1;
+    }
+  }
+  if version <= SNAPSHOT_VERSION {
+    version = SNAPSHOT_VERSION;
+    root.insert("version".to_string(), json!(version));
+  }
+  Ok(MigrationReport {
+    snapshot: value,
+    blank,
+    from_version: detected,
+    to_version: version,
+    applied,
+  })
+}
+
+// Versions too large for u32 are refused rather than truncated, which could wrap them round to a
+// supported one.
+fn detect_version(value: &Value) -> Result<u32, String> {
+  let stamped = match value.get("version").and_then(Value::as_u64) {
+    Some(version) => Some(u32::try_from(version).map_err(|_| {
+      format!("Snapshot version {version} is newer than the supported version {SNAPSHOT_VERSION}.")
+    })?),
+    None => None,
+  };
+  if value
+    .get("__blank")
+    .and_then(Value::as_bool)
+    .unwrap_or(false)
+  {
+    return Ok(stamped.unwrap_or(SNAPSHOT_VERSION));
+  }
+  Ok(match stamped {
+    // The static sheet already stamped its keyed exports as version 2.
+    Some(2) if has_legacy_sections(value) => 1,
+    Some(version) => version,
+    None if value.get("fields").is_some_and(Value::is_array) => 0,
+    None => 1,
+  })
+}
+
+fn has_legacy_sections(value: &Value) -> bool {
+  let renamed = RENAMED_STATS.iter().any(|(section, old, _)| {
+    value
+      .pointer(&format!("/{}/stats", section.key()))
+      .and_then(Value::as_object)
+      .is_some_and(|stats| stats.contains_key(*old))
+  });
+  let wide_sliders = [Section::Mind, Section::Social].iter().any(|section| {
+    value
+      .pointer(&format!("/{}/sliders", section.key()))
+      .and_then(Value::as_object)
+      .is_some_and(|sliders| {
+        sliders
+          .values()
+          .filter_map(number_of)
+          .any(|number| number > f64::from(SLIDER_MAX))
+      })
+  });
+  renamed || wide_sliders
+}
+
+fn number_of(value: &Value) -> Option<f64> {
+  match value {
+    Value::Number(number) => number.as_f64(),
+    Value::String(text) => text.trim().parse().ok(),
+    Value::Bool(flag) => Some(if *flag { 1.0 } else { 0.0 }),
+    _ => None,
+  }
+}
+
+fn truthy(value: &Value) -> bool {
+  match value {
+    Value::Bool(flag) => *flag,
+    Value::Number(number) => number.as_f64().is_some_and(|n| n != 0.0),
+    Value::String(text) => !text.is_empty(),
+    Value::Null => false,
+    _ => true,
+  }
+}
+
+fn section_entry<'a>(
+  root: &'a mut Map<String, Value>,
+  section: Section,
+  key: &str,
+) -> &'a mut Map<String, Value> {
+  let section = root.entry(section.key()).or_insert_with(|| json!({}));
+  if !section.is_object() {
+    *section = json!({});
+  }
+  let child = section
+    .as_object_mut()
+    .expect("section is an object")
+    .entry(key)
+    .or_insert_with(|| json!({}));
+  if !child.is_object() {
+    *child = json!({});
+  }
+  child.as_object_mut().expect("section child is an object")
+}
+
+fn expand_positional_fields(root: &mut Map<String, Value>) {
+  let fields = match root.remove("fields") {
+    Some(Value::Array(fields)) => fields,
+    _ => Vec::new(),
+  };
+  let mut values = fields
+    .iter()
+    .map(|item| item.get("v").unwrap_or(&Value::Null));
+  let mut identity = Map::new();
+  let mut notes = Vec::new();
+  for field in LEGACY_FIELDS {
+    match field {
+      LegacyField::Note(title) => {
+        let text = values.next().and_then(Value::as_str).unwrap_or_default();
+        notes.push(json!({ "title": title, "text": text }));
+      }
+      LegacyField::Identity(key) => {
+        let text = values.next().and_then(Value::as_str).unwrap_or_default();
+        identity.insert(key.to_string(), json!(text));
+      }
+      LegacyField::Dots(section, stat, dots) => {
+        let checked = (0..*dots)
+          .filter(|_| values.next().is_some_and(truthy))
+          .count();
+        section_entry(root, *section, "stats").insert(stat.to_string(), json!(checked));
+      }
+      LegacyField::Slider(section, slider) => {
+        if let Some(number) = values.next().and_then(number_of) {
+          section_entry(root, *section, "sliders").insert(slider.to_string(), json!(number));
+        }
+      }
+      LegacyField::Trait(section, name) => {
+        let checked = values.next().is_some_and(truthy);
+        section_entry(root, *section, "traits").insert(name.to_string(), json!(checked));
+      }
+    }
+  }
+  root.insert("identity".to_string(), Value::Object(identity));
+  root.insert("notes".to_string(), Value::Array(notes));
+}
+
+fn normalise_legacy_sections(root: &mut Map<String, Value>) {
+  for rules in &rules::SECTION_RULES {
+    let Some(section) = root
+      .get_mut(rules.section.key())
+      .and_then(Value::as_object_mut)
+    else {
+      continue;
+    };
+    if let Some(stats) = section.get_mut("stats").and_then(Value::as_object_mut) {
+      let legacy = std::mem::take(stats);
+      for (label, value) in legacy {
+        let label = canonical_label(rules.section, label.trim(), rules.stats);
+        let count = number_of(&value)
+          .unwrap_or(0.0)
+          .round()
+          .clamp(0.0, f64::from(rules.dots));
+        stats.insert(label, json!(count as u8));
+      }
+    }
+    if let Some(sliders) = section.get_mut("sliders").and_then(Value::as_object_mut) {
+      let legacy = std::mem::take(sliders);
+      for (label, value) in legacy {
+        let label = canonical_label(rules.section, label.trim(), rules.sliders);
+        let position = number_of(&value).unwrap_or(LEGACY_SLIDER_MAX / 2.0);
+        let scaled = (position / LEGACY_SLIDER_MAX * f64::from(SLIDER_MAX))
+          .round()
+          .clamp(0.0, f64::from(SLIDER_MAX));
+        sliders.insert(label, json!(scaled as u8));
+      }
+    }
+    if let Some(traits) = section.get_mut("traits").and_then(Value::as_object_mut) {
+      let legacy = std::mem::take(traits);
+      for (label, value) in legacy {
+        let label = canonical_label(rules.section, label.trim(), rules.traits);
+        traits.insert(label, json!(truthy(&value)));
+      }
+    }
+  }
+}
+
+fn canonical_label(section: Section, label: &str, known: &[&str]) -> String {
+  if let Some((_, _, renamed)) = RENAMED_STATS.iter().find(|(renamed_section, old, _)| {
+    *renamed_section == section && old.eq_ignore_ascii_case(label)
+  }) {
+    return renamed.to_string();
+  }
+  known
+    .iter()
+    .find(|candidate| candidate.eq_ignore_ascii_case(label))
+    .map_or_else(|| label.to_string(), |candidate| candidate.to_string())
+}
+
+#[tauri::command]
+pub fn migrate_character(snapshot: Value) -> Result<MigrationReport, String> {
+  migrate_value(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn field(value: Value) -> Value {
+    json!({ "v": value })
+  }
+
+  #[test]
+  fn expands_positional_fields() {
+    let mut fields: Vec<Value> = (0..6).map(|_| field(json!(""))).collect();
+    fields[0] = field(json!("Calm"));
+    fields.push(field(json!("Ada")));
+    fields.extend((0..7).map(|_| field(json!(""))));
+    // Strength: three of six dots checked.
+    fields.extend([true, true, true, false, false, false].map(|dot| field(json!(dot))));
+    let report = migrate_value(json!({ "fields": fields })).unwrap();
+    assert_eq!(report.from_version, 0);
+    assert_eq!(report.to_version, SNAPSHOT_VERSION);
+    assert_eq!(report.applied.len(), 2);
+    let snapshot = &report.snapshot;
+    assert!(snapshot.get("fields").is_none());
+    assert_eq!(snapshot["identity"]["Name"], "Ada");
+    assert_eq!(
+      snapshot["notes"][0],
+      json!({ "title": "Personality", "text": "Calm" })
+    );
+    assert_eq!(snapshot["body"]["stats"]["Strength"], 3);
+    assert_eq!(snapshot["body"]["stats"]["Dexterity"], 0);
+    assert_eq!(snapshot["mind"]["traits"]["Ambitious"], false);
+    assert!(rules::validate_value(snapshot).valid);
+  }
+
+  #[test]
+  fn normalises_legacy_sections() {
+    let report = migrate_value(json!({
+      "skills": { "stats": { "animal handling": "3", " combat ": 9 } },
+      "mind": { "sliders": { "nice / mean": 75 }, "traits": { "stubborn": 1 } },
+    }))
+    .unwrap();
+    assert_eq!(report.from_version, 1);
+    assert_eq!(report.applied.len(), 1);
+    assert_eq!(report.applied[0].from, 1);
+    let snapshot = &report.snapshot;
+    assert_eq!(snapshot["version"], SNAPSHOT_VERSION);
+    assert_eq!(
+      snapshot["skills"]["stats"],
+      json!({ "Animal Care": 3, "Combat": 4 })
+    );
+    assert_eq!(snapshot["mind"]["sliders"], json!({ "Nice / Mean": 8 }));
+    assert_eq!(snapshot["mind"]["traits"], json!({ "Stubborn": true }));
+  }
+
+  #[test]
+  fn treats_legacy_version_two_exports_as_version_one() {
+    let report = migrate_value(json!({
+      "version": 2,
+      "social": { "sliders": { "Polite / Rude": 40 } },
+    }))
+    .unwrap();
+    assert_eq!(report.from_version, 1);
+    assert_eq!(report.snapshot["social"]["sliders"]["Polite / Rude"], 4);
+  }
+
+  #[test]
+  fn leaves_current_snapshots_alone() {
+    let snapshot = json!({
+      "version": SNAPSHOT_VERSION,
+      "mind": { "sliders": { "Nice / Mean": 7 } },
+    });
+    let report = migrate_value(snapshot.clone()).unwrap();
+    assert!(report.applied.is_empty());
+    assert_eq!(report.snapshot, snapshot);
+  }
+
+  #[test]
+  fn keeps_newer_versions_for_validation_to_reject() {
+    let report = migrate_value(json!({ "version": SNAPSHOT_VERSION + 1 })).unwrap();
+    assert_eq!(report.to_version, SNAPSHOT_VERSION + 1);
+    assert!(!rules::validate_value(&report.snapshot).valid);
+  }
+
+  #[test]
+  fn refuses_versions_that_do_not_fit() {
+    // Truncated to 32 bits this would be version 1.
+    let version = (1u64 << 32) + 1;
+    assert!(migrate_value(json!({ "version": version })).is_err());
+    assert!(migrate_value(json!({ "__blank": true, "version": version })).is_err());
+  }
+}
@@ -0,0 +1,486 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+// Advance widths of Helvetica for ASCII 32..=126, in 1/1000 em.
+const HELVETICA_WIDTHS: [u16; 95] = [
+  278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+  556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+  611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+  667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+  222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const MARGIN: f32 = 56.0;
+const BODY_SIZE: f32 = 10.5;
+const BODY_LEADING: f32 = 14.0;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Font {
+  Regular,
+  Bold,
+}
+
+impl Font {
+  fn resource(self) -> &'static str {
+    match self {
+      Font::Regular => "F1",
+      Font::Bold => "F2",
+    }
+  }
+}
+
+pub fn text_width(text: &str, size: f32) -> f32 {
+  text
+    .chars()
+    .map(|c| match c as u32 {
+      code @ 32..=126 => HELVETICA_WIDTHS[(code - 32) as usize],
+      _ => 556,
+    })
+    .map(f32::from)
+    .sum::<f32>()
+    * size
+    / 1000.0
+}
+
+// Cuts the text short with an ellipsis so that it fits in `width`.
+pub fn fit_width(text: &str, size: f32, width: f32) -> String {
+  if text_width(text, size) <= width {
+    return text.to_string();
+  }
+  let mut fitted: String = text.to_string();
+  while !fitted.is_empty() && text_width(&format!("{fitted}..."), size) > width {
+    fitted.pop();
+  }
+  format!("{}...", fitted.trim_end())
+}
+
+// The standard fonts use WinAnsiEncoding, which matches Latin-1 above 0xA0. They have no glyphs
+// beyond that, so other scripts (Greek, Cyrillic, CJK, emoji) print as `?` until a font is
+// embedded.
+fn pdf_string(text: &str) -> String {
+  let mut out = String::from("(");
+  for c in text.chars() {
+    match c {
+      '(' | ')' | '\\' => {
+        out.push('\\');
+        out.push(c);
+      }
+      ' '..='~' => out.push(c),
+      '\u{a0}'..='\u{ff}' => {
+        let _ = write!(out, "\\{:03o}", c as u32);
+      }
+      _ => out.push('?'),
+    }
+  }
+  out.push(')');
+  out
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageSize {
+  #[default]
+  A4,
+  Letter,
+}
+
+impl PageSize {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value.to_lowercase().as_str() {
+      "a4" => Some(PageSize::A4),
+      "letter" => Some(PageSize::Letter),
+      _ => None,
+    }
+  }
+
+  // In points.
+  fn dimensions(self) -> (f32, f32) {
+    match self {
+      PageSize::A4 => (595.0, 842.0),
+      PageSize::Letter => (612.0, 792.0),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Paint {
+  Fill(f32),
+  Stroke(f32),
+}
+
+impl Paint {
+  // Gray level and painting operator.
+  fn ops(self) -> (String, &'static str) {
+    match self {
+      Paint::Fill(gray) => (format!("{gray:.2} g"), "f"),
+      Paint::Stroke(gray) => (format!("{gray:.2} G 0.8 w"), "S"),
+    }
+  }
+}
+
+struct JpegImage {
+  width: u32,
+  height: u32,
+  data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageId(usize);
+
+// A small PDF writer: flowing text plus a few vector primitives and JPEG images, drawn on a
+// cursor that moves down the page. No fonts to embed, no browser, no GPU.
+pub struct PdfWriter {
+  width: f32,
+  height: f32,
+  pages: Vec<String>,
+  current: String,
+  images: Vec<JpegImage>,
+  y: f32,
+}
+
+impl PdfWriter {
+  pub fn new(size: PageSize) -> Self {
+    let (width, height) = size.dimensions();
+    Self {
+      width,
+      height,
+      pages: Vec::new(),
+      current: String::new(),
+      images: Vec::new(),
+      y: height - MARGIN,
+    }
+  }
+
+  pub fn left(&self) -> f32 {
+    MARGIN
+  }
+
+  pub fn content_width(&self) -> f32 {
+    self.width - MARGIN * 2.0
+  }
+
+  // The top of the space not yet used on the current page.
+  pub fn y(&self) -> f32 {
+    self.y
+  }
+
+  // How many pages came before the current one.
+  pub fn page(&self) -> usize {
+    self.pages.len()
+  }
+
+  pub fn advance(&mut self, height: f32) {
+    self.y -= height;
+  }
+
+  // Starts a new page unless `height` still fits on this one.
+  pub fn ensure(&mut self, height: f32) {
+    if self.y - height < MARGIN {
+      self.pages.push(std::mem::take(&mut self.current));
+      self.y = self.height - MARGIN;
+    }
+  }
+
+  pub fn text_at(&mut self, x: f32, y: f32, text: &str, font: Font, size: f32, gray: f32) {
+    let _ = writeln!(
+      self.current,
+      "q {gray:.2} g BT /{} {size} Tf {x:.2} {y:.2} Td {} Tj ET Q",
+      font.resource(),
+      pdf_string(text)
+    );
+  }
+
+  pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, paint: Paint) {
+    let (color, op) = paint.ops();
+    let _ = writeln!(
+      self.current,
+      "q {color} {x:.2} {y:.2} {width:.2} {height:.2} re {op} Q"
+    );
+  }
+
+  pub fn segment(&mut self, from: (f32, f32), to: (f32, f32), gray: f32) {
+    let _ = writeln!(
+      self.current,
+      "q {gray:.2} G 0.8 w {:.2} {:.2} m {:.2} {:.2} l S Q",
+      from.0, from.1, to.0, to.1
+    );
+  }
+
+  // Four Bézier quarter arcs.
+  pub fn circle(&mut self, cx: f32, cy: f32, r: f32, paint: Paint) {
+    let k = r * 0.552_284_8;
+    let (color, op) = paint.ops();
+    let arcs = [
+      [cx + r, cy + k, cx + k, cy + r, cx, cy + r],
+      [cx - k, cy + r, cx - r, cy + k, cx - r, cy],
+      [cx - r, cy - k, cx - k, cy - r, cx, cy - r],
+      [cx + k, cy - r, cx + r, cy - k, cx + r, cy],
+    ];
+    let _ = write!(self.current, "q {color} {:.2} {cy:.2} m", cx + r);
+    for [x1, y1, x2, y2, x3, y3] in arcs {
+      let _ = write!(
+        self.current,
+        " {x1:.2} {y1:.2} {x2:.2} {y2:.2} {x3:.2} {y3:.2} c"
+      );
+    }
+    let _ = writeln!(self.current, " {op} Q");
+  }
+
+  // Baseline JPEG with three components, embedded as is through DCTDecode.
+  pub fn add_jpeg(&mut self, data: Vec<u8>, width: u32, height: u32) -> ImageId {
+    self.images.push(JpegImage {
+      width,
+      height,
+      data,
+    });
+    ImageId(self.images.len() - 1)
+  }
+
+  pub fn image(&mut self, image: ImageId, x: f32, y: f32, width: f32, height: f32) {
+    let _ = writeln!(
+      self.current,
+      "q {width:.2} 0 0 {height:.2} {x:.2} {y:.2} cm /Im{} Do Q",
+      image.0 + 1
+    );
+  }
+
+  fn text(&mut self, text: &str, font: Font, size: f32, leading: f32) {
+    self.ensure(leading);
+    self.y -= leading;
+    let _ = writeln!(
+      self.current,
+      "BT /{} {size} Tf {MARGIN} {:.2} Td {} Tj ET",
+      font.resource(),
+      self.y,
+      pdf_string(text)
+    );
+  }
+
+  pub fn title(&mut self, text: &str) {
+    self.text(text, Font::Bold, 20.0, 28.0);
+  }
+
+  pub fn heading(&mut self, text: &str) {
+    self.ensure(40.0);
+    self.y -= 8.0;
+    self.text(text, Font::Bold, 13.0, 18.0);
+  }
+
+  // Wraps on word boundaries to the page width. A word too wide for a line of its own is broken
+  // wherever it runs out of room.
+  pub fn line(&mut self, text: &str) {
+    let available = self.content_width();
+    let fits = |text: &str| text_width(text, BODY_SIZE) <= available;
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+      let candidate = if current.is_empty() {
+        word.to_string()
+      } else {
+        format!("{current} {word}")
+      };
+      if fits(&candidate) {
+        current = candidate;
+        continue;
+      }
+      if !current.is_empty() {
+        self.text(&current, Font::Regular, BODY_SIZE, BODY_LEADING);
+        current.clear();
+      }
+      for c in word.chars() {
+        current.push(c);
+        if !fits(&current) && current.chars().count() > 1 {
+          current.pop();
+          self.text(&current, Font::Regular, BODY_SIZE, BODY_LEADING);
+          current = c.to_string();
+        }
+      }
+    }
+    self.text(&current, Font::Regular, BODY_SIZE, BODY_LEADING);
+  }
+
+  pub fn finish(mut self) -> Vec<u8> {
+    if !self.current.is_empty() || self.pages.is_empty() {
+      self.pages.push(std::mem::take(&mut self.current));
+    }
+    // Objects: 1 catalog, 2 page tree, 3-4 fonts, the images, then a page and its content per page.
+    let mut objects: Vec<Vec<u8>> = vec![
+      b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+      Vec::new(),
+      b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+      b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+        .to_vec(),
+    ];
+    let mut xobjects = String::new();
+    for (index, image) in self.images.iter().enumerate() {
+      let _ = write!(xobjects, "/Im{} {} 0 R ", index + 1, objects.len() + 1);
+      let mut object = format!(
+        "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+        image.width,
+        image.height,
+        image.data.len()
+      )
+      .into_bytes();
+      object.extend_from_slice(&image.data);
+      object.extend_from_slice(b"\nendstream");
+      objects.push(object);
+    }
+    let mut kids = Vec::new();
+    for content in &self.pages {
+      let page = objects.len() + 1;
+      kids.push(format!("{page} 0 R"));
+      objects.push(
+        format!(
+          "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> /XObject << {xobjects}>> >> /Contents {} 0 R >>",
+          self.width,
+          self.height,
+          page + 1
+        )
+        .into_bytes(),
+      );
+      objects.push(
+        format!(
+          "<< /Length {} >>\nstream\n{content}\nendstream",
+          content.len()
+        )
+        .into_bytes(),
+      );
+    }
+    objects[1] = format!(
+      "<< /Type /Pages /Kids [{}] /Count {} >>",
+      kids.join(" "),
+      kids.len()
+    )
+    .into_bytes();
+
+    let mut out: Vec<u8> = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+      offsets.push(out.len());
+      out.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+      out.extend_from_slice(object);
+      out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    let mut tail = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+      let _ = writeln!(tail, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+      tail,
+      "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+      objects.len() + 1
+    );
+    out.extend_from_slice(tail.as_bytes());
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::{CharacterSnapshot, Note};
+
+  fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack[from..]
+      .windows(needle.len())
+      .position(|window| window == needle)
+      .map(|at| at + from)
+  }
+
+  fn sample() -> Vec<u8> {
+    let mut pdf = PdfWriter::new(PageSize::A4);
+    pdf.title("Ada (Countess)");
+    pdf.line("Caf\u{e9} \\ back");
+    let image = pdf.add_jpeg(vec![0xff, 0xd8, b'\n', 0xff, 0xd9], 1, 1);
+    pdf.image(image, 10.0, 10.0, 20.0, 20.0);
+    pdf.finish()
+  }
+
+  #[test]
+  fn xref_offsets_point_at_their_objects() {
+    let pdf = sample();
+    let xref = find(&pdf, b"\nxref\n", 0).unwrap() + 1;
+    let tail = String::from_utf8_lossy(&pdf[xref..]).to_string();
+    let offsets: Vec<usize> = tail
+      .lines()
+      .skip(3)
+      .take_while(|line| line.ends_with(" n "))
+      .map(|line| line[..10].parse().unwrap())
+      .collect();
+    assert_eq!(offsets.len(), 7);
+    assert!(tail.contains("/Size 8 "));
+    for (index, offset) in offsets.iter().enumerate() {
+      let header = format!("{} 0 obj\n", index + 1);
+      assert!(
+        pdf[*offset..].starts_with(header.as_bytes()),
+        "object {}",
+        index + 1
+      );
+    }
+    let startxref: usize = tail.lines().rev().nth(1).unwrap().parse().unwrap();
+    assert_eq!(startxref, xref);
+  }
+
+  #[test]
+  fn stream_lengths_are_exact() {
+    let pdf = sample();
+    let mut streams = 0;
+    let mut at = 0;
+    while let Some(start) = find(&pdf, b"/Length ", at) {
+      let end = find(&pdf, b" >>\nstream\n", start).unwrap();
+      let length: usize = std::str::from_utf8(&pdf[start + 8..end])
+        .unwrap()
+        .parse()
+        .unwrap();
+      let data = end + b" >>\nstream\n".len();
+      assert!(pdf[data + length..].starts_with(b"\nendstream"));
+      streams += 1;
+      at = data + length;
+    }
+    assert_eq!(streams, 2);
+  }
+
+  #[test]
+  fn strings_escape_delimiters_and_latin1() {
+    assert_eq!(pdf_string(r"(a) \ b"), r"(\(a\) \\ b)");
+    assert_eq!(pdf_string("Caf\u{e9}\u{a0}\u{ff}"), r"(Caf\351\240\377)");
+    assert_eq!(pdf_string("\u{3a9}\u{1f600}"), "(??)");
+  }
+
+  #[test]
+  fn long_words_are_broken_to_fit() {
+    let mut pdf = PdfWriter::new(PageSize::A4);
+    let word = "W".repeat(80);
+    pdf.line(&format!("short {word}"));
+    let lines: Vec<&str> = pdf
+      .current
+      .lines()
+      .map(|line| &line[line.find(" Td (").unwrap() + 5..line.find(") Tj").unwrap()])
+      .collect();
+    assert_eq!(lines[0], "short");
+    assert!(lines.len() > 2);
+    assert_eq!(lines[1..].concat(), word);
+    assert!(lines
+      .iter()
+      .all(|line| text_width(line, BODY_SIZE) <= pdf.content_width()));
+  }
+
+  #[test]
+  fn long_sheets_run_over_several_pages() {
+    let snapshot = CharacterSnapshot {
+      notes: (0..40)
+        .map(|index| Note {
+          title: format!("Note {index}"),
+          text: "A paragraph that fills a line.\nAnd another.".into(),
+        })
+        .collect(),
+      ..Default::default()
+    };
+    let pdf = crate::export::to_pdf(&snapshot, None, PageSize::Letter);
+    let pdf = String::from_utf8_lossy(&pdf);
+    let count = &pdf[pdf.find("/Count ").unwrap() + 7..];
+    let count: usize = count[..count.find(' ').unwrap()].parse().unwrap();
+    assert!(count > 1);
+    assert_eq!(pdf.matches("/Type /Page ").count(), count);
+  }
+}
@@ -0,0 +1,325 @@
+use crate::character::{CharacterSnapshot, TabData};
+use crate::imaging::{self, CropRect, MetadataReport, THUMBNAIL_SIZES};
+use crate::library::CharacterRecord;
+use crate::storage::write_atomic;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const PORTRAIT_PROTOCOL: &str = "portrait";
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+
+// Snapshots refer to stored portraits by the SHA-256 of their bytes.
+pub fn is_portrait_hash(value: &str) -> bool {
+  value.len() == 64
+    && value
+      .bytes()
+      .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+// Sniffed from the bytes, so a stored file needs no extension or sidecar.
+pub fn mime_type(bytes: &[u8]) -> Option<&'static str> {
+  if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some("image/png")
+  } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+    Some("image/jpeg")
+  } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+    Some("image/gif")
+  } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+    Some("image/webp")
+  } else {
+    None
+  }
+}
+
+pub fn extension(mime: &str) -> &'static str {
+  match mime {
+    "image/jpeg" => "jpg",
+    "image/gif" => "gif",
+    "image/webp" => "webp",
+    _ => "png",
+  }
+}
+
+pub fn decode_data_url(url: &str) -> Option<Vec<u8>> {
+  let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+  header.strip_suffix(";base64")?.strip_prefix("image/")?;
+  STANDARD.decode(data.trim()).ok()
+}
+
+pub fn encode_data_url(bytes: &[u8]) -> String {
+  let mime = mime_type(bytes).unwrap_or("image/png");
+  format!("data:{mime};base64,{}", STANDARD.encode(bytes))
+}
+
+// Every portrait slot a record carries: its own and the copies cached on relationship entries.
+fn snapshot_slots(snapshot: &mut CharacterSnapshot) -> Vec<&mut Option<String>> {
+  let mut slots = vec![&mut snapshot.portrait];
+  if let Some(relationships) = &mut snapshot.relationships {
+    slots.extend(relationships.entries_mut().map(|entry| &mut entry.portrait));
+  }
+  slots
+}
+
+fn record_slots(record: &mut CharacterRecord) -> Vec<&mut Option<String>> {
+  let mut slots = match &mut record.data {
+    Some(TabData::Sheet(snapshot)) => snapshot_slots(snapshot),
+    _ => Vec::new(),
+  };
+  slots.extend(
+    record
+      .relationships
+      .entries_mut()
+      .map(|entry| &mut entry.portrait),
+  );
+  slots
+}
+
+// Identical images share one file; storing the same bytes twice only refreshes its mtime, which
+// keeps an image that is in use again out of reach of the orphan sweep.
+fn write_once(path: &Path, bytes: &[u8]) -> Result<(), String> {
+  if path.exists() {
+    return OpenOptions::new()
+      .write(true)
+      .open(path)
+      .and_then(|file| file.set_modified(SystemTime::now()))
+      .map_err(|e| e.to_string());
+  }
+  write_atomic(path, bytes)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredPortrait {
+  pub hash: String,
+  pub metadata: MetadataReport,
+}
+
+pub struct PortraitStore {
+  root: PathBuf,
+}
+
+impl PortraitStore {
+  pub fn new(root: PathBuf) -> Self {
+    Self { root }
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  fn path(&self, hash: &str) -> Result<PathBuf, String> {
+    if !is_portrait_hash(hash) {
+      return Err(format!("Invalid portrait hash {hash:?}."));
+    }
+    Ok(self.root.join(hash))
+  }
+
+  fn thumbnail_path(&self, hash: &str, size: u32) -> Result<PathBuf, String> {
+    self.path(hash)?;
+    Ok(
+      self
+        .root
+        .join(THUMBNAILS_DIR)
+        .join(format!("{hash}-{size}")),
+    )
+  }
+
+  // Every portrait goes through the image pipeline on its way in, whatever its source, so none
+  // is stored with its EXIF, XMP or IPTC metadata. Bytes that already are a stored portrait,
+  // say one inlined into an exported file and imported again, keep their hash instead of being
+  // re-encoded into a new one.
+  pub fn put(&self, bytes: &[u8], crop: Option<CropRect>) -> Result<StoredPortrait, String> {
+    if mime_type(bytes).is_none() {
+      return Err("Portraits must be PNG, JPEG, WebP or GIF images.".to_string());
+    }
+    let source_hash = format!("{:x}", Sha256::digest(bytes));
+    let existing = self.path(&source_hash)?;
+    if crop.is_none() && existing.exists() {
+      write_once(&existing, bytes)?;
+      return Ok(StoredPortrait {
+        hash: source_hash,
+        metadata: MetadataReport::default(),
+      });
+    }
+    let processed = imaging::process(bytes, crop)?;
+    let hash = format!("{:x}", Sha256::digest(&processed.main));
+    write_once(&self.path(&hash)?, &processed.main)?;
+    for (size, bytes) in processed.thumbnails {
+      write_once(&self.thumbnail_path(&hash, size)?, &bytes)?;
+    }
+    Ok(StoredPortrait {
+      hash,
+      metadata: processed.metadata,
+    })
+  }
+
+  pub fn get(&self, hash: &str) -> Result<Vec<u8>, String> {
+    fs::read(self.path(hash)?).map_err(|e| e.to_string())
+  }
+
+  // Thumbnails missing from portraits stored before they existed are made on first request.
+  pub fn thumbnail(&self, hash: &str, size: u32) -> Result<Vec<u8>, String> {
+    if !THUMBNAIL_SIZES.contains(&size) {
+      return Err(format!("There is no {size}px portrait thumbnail."));
+    }
+    let path = self.thumbnail_path(hash, size)?;
+    if let Ok(bytes) = fs::read(&path) {
+      return Ok(bytes);
+    }
+    let bytes = imaging::thumbnail(&imaging::decode(&self.get(hash)?)?.0, size)?;
+    write_once(&path, &bytes)?;
+    Ok(bytes)
+  }
+
+  // The image behind a portrait value, whether it is a stored hash or an inline data URL.
+  pub fn bytes(&self, value: &str) -> Option<Vec<u8>> {
+    if is_portrait_hash(value) {
+      self.get(value).ok()
+    } else {
+      decode_data_url(value)
+    }
+  }
+
+  fn intern(&self, slot: &mut Option<String>) -> Result<(), String> {
+    if let Some(bytes) = slot.as_deref().and_then(decode_data_url) {
+      *slot = Some(self.put(&bytes, None)?.hash);
+    }
+    Ok(())
+  }
+
+  fn inline(&self, slot: &mut Option<String>) {
+    if let Some(bytes) = slot
+      .as_deref()
+      .filter(|value| is_portrait_hash(value))
+      .and_then(|hash| self.get(hash).ok())
+    {
+      *slot = Some(encode_data_url(&bytes));
+    }
+  }
+
+  // Moves inline data URLs into the store so the record keeps only hashes.
+  pub fn intern_record(&self, record: &mut CharacterRecord) -> Result<(), String> {
+    record_slots(record)
+      .into_iter()
+      .try_for_each(|slot| self.intern(slot))
+  }
+
+  pub fn intern_snapshot(&self, snapshot: &mut CharacterSnapshot) -> Result<(), String> {
+    snapshot_slots(snapshot)
+      .into_iter()
+      .try_for_each(|slot| self.intern(slot))
+  }
+
+  // Files that leave the app carry their images with them.
+  pub fn inline_record(&self, record: &mut CharacterRecord) {
+    for slot in record_slots(record) {
+      self.inline(slot);
+    }
+  }
+
+  pub fn inline_snapshot(&self, snapshot: &mut CharacterSnapshot) {
+    for slot in snapshot_slots(snapshot) {
+      self.inline(slot);
+    }
+  }
+}
+
+// Serves `portrait://localhost/<hash>` (`https://portrait.localhost/<hash>` on Windows), or one
+// of its thumbnails with `?size=<px>`.
+pub fn respond(
+  store: &PortraitStore,
+  uri: &str,
+) -> Result<tauri::http::Response, Box<dyn std::error::Error>> {
+  let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+  let hash = path
+    .split('#')
+    .next()
+    .unwrap_or_default()
+    .trim_end_matches('/')
+    .rsplit('/')
+    .next()
+    .unwrap_or_default();
+  let size = query
+    .split('&')
+    .find_map(|pair| pair.strip_prefix("size="))
+    .and_then(|size| size.parse::<u32>().ok());
+  let bytes = match size {
+    Some(size) => store.thumbnail(hash, size),
+    None => store.get(hash),
+  };
+  match bytes {
+    Ok(bytes) => tauri::http::ResponseBuilder::new()
+      .mimetype(mime_type(&bytes).unwrap_or("application/octet-stream"))
+      // The hash names the content, so a cached copy can never go stale.
+      .header("Cache-Control", "public, max-age=31536000, immutable")
+      .body(bytes),
+    Err(_) => tauri::http::ResponseBuilder::new()
+      .status(404)
+      .body(Vec::new()),
+  }
+}
+
+// Async so that resizing a large photo does not block the main thread.
+#[tauri::command]
+pub async fn portrait_store(
+  portraits: tauri::State<'_, PortraitStore>,
+  data_url: String,
+  crop: Option<CropRect>,
+) -> Result<StoredPortrait, String> {
+  let bytes =
+    decode_data_url(&data_url).ok_or_else(|| "That portrait is not an image.".to_string())?;
+  portraits.put(&bytes, crop)
+}
+
+#[tauri::command]
+pub fn portrait_data_url(
+  portraits: tauri::State<'_, PortraitStore>,
+  hash: String,
+) -> Result<String, String> {
+  Ok(encode_data_url(&portraits.get(&hash)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{DynamicImage, ImageFormat, RgbImage};
+  use std::io::Cursor;
+
+  fn noisy_png(edge: u32) -> Vec<u8> {
+    let mut seed: u32 = 1;
+    let image = RgbImage::from_fn(edge, edge, |_, _| {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+      let [r, g, b, _] = seed.to_be_bytes();
+      image::Rgb([r, g, b])
+    });
+    let mut out = Vec::new();
+    DynamicImage::ImageRgb8(image)
+      .write_to(&mut Cursor::new(&mut out), ImageFormat::Png)
+      .unwrap();
+    out
+  }
+
+  #[test]
+  fn stored_portraits_keep_their_hash_when_imported_again() {
+    let scratch = tempfile::tempdir().unwrap();
+    let root = scratch.path();
+    let store = PortraitStore::new(root.to_path_buf());
+    let first = store
+      .put(&noisy_png(imaging::MAX_PORTRAIT_EDGE + 100), None)
+      .unwrap();
+    let mut slot = Some(first.hash.clone());
+    store.inline(&mut slot);
+    store.intern(&mut slot).unwrap();
+    assert_eq!(slot.as_deref(), Some(first.hash.as_str()));
+    let stored = fs::read_dir(root)
+      .unwrap()
+      .filter(|entry| entry.as_ref().unwrap().path().is_file())
+      .count();
+    assert_eq!(stored, 1);
+  }
+}
@@ -0,0 +1,484 @@
+use crate::character::{RelationshipEntry, RelationshipKind, TabData};
+use crate::family::{gender_of, FamilyLabel};
+use crate::library::{now_millis, CharacterRecord, Library};
+use crate::links::LinkIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterRef {
+  pub id: String,
+  pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ReciprocityProblem {
+  // `from` lists `to`, but `to` has no entry pointing back.
+  MissingReciprocal {
+    category: RelationshipKind,
+  },
+  #[serde(rename_all = "camelCase")]
+  AsymmetricCategory {
+    from_categories: Vec<RelationshipKind>,
+    to_categories: Vec<RelationshipKind>,
+  },
+  #[serde(rename_all = "camelCase")]
+  FamilyLabelMismatch {
+    from_relation: String,
+    to_relation: String,
+    expected: Vec<String>,
+  },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum ReciprocityFix {
+  #[serde(rename_all = "camelCase")]
+  AddEntry {
+    record_id: String,
+    category: RelationshipKind,
+    entry: RelationshipEntry,
+  },
+  #[serde(rename_all = "camelCase")]
+  SetRelation {
+    record_id: String,
+    entry_id: String,
+    relation: String,
+  },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReciprocityIssue {
+  pub from: CharacterRef,
+  pub to: CharacterRef,
+  #[serde(flatten)]
+  pub problem: ReciprocityProblem,
+  pub fix: Option<ReciprocityFix>,
+}
+
+fn character_ref(record: &CharacterRecord) -> CharacterRef {
+  let name = match &record.data {
+    Some(TabData::Sheet(snapshot)) => snapshot.name().map(str::to_string),
+    _ => None,
+  };
+  CharacterRef {
+    id: record.id.clone(),
+    name: name.unwrap_or_else(|| record.title.clone()),
+  }
+}
+
+// The categories `record` lists `target` under, and the entries doing it.
+fn links_to<'a>(
+  index: &LinkIndex,
+  record: &'a CharacterRecord,
+  target_uuid: &str,
+) -> Vec<(RelationshipKind, &'a RelationshipEntry)> {
+  RelationshipKind::ALL
+    .into_iter()
+    .flat_map(|kind| {
+      record
+        .relationships
+        .get(kind)
+        .iter()
+        .map(move |entry| (kind, entry))
+    })
+    .filter(|(_, entry)| index.resolve(entry).map(|t| t.uuid.as_str()) == Some(target_uuid))
+    .collect()
+}
+
+fn categories(links: &[(RelationshipKind, &RelationshipEntry)]) -> Vec<RelationshipKind> {
+  RelationshipKind::ALL
+    .into_iter()
+    .filter(|kind| links.iter().any(|(linked, _)| linked == kind))
+    .collect()
+}
+
+// Every check runs from both ends of a pair, so the same problem can be found twice. Issues are
+// keyed on the unordered pair, and the report from the lower id wins.
+#[derive(Default)]
+struct Issues(BTreeMap<(String, String, String), ReciprocityIssue>);
+
+impl Issues {
+  fn report(&mut self, detail: String, issue: ReciprocityIssue) {
+    let from_lower = issue.from.id < issue.to.id;
+    let key = if from_lower {
+      (issue.from.id.clone(), issue.to.id.clone(), detail)
+    } else {
+      (issue.to.id.clone(), issue.from.id.clone(), detail)
+    };
+    match self.0.entry(key) {
+      Entry::Vacant(slot) => {
+        slot.insert(issue);
+      }
+      Entry::Occupied(mut slot) if from_lower => {
+        slot.insert(issue);
+      }
+      Entry::Occupied(_) => {}
+    }
+  }
+}
+
+pub fn lint(records: &[CharacterRecord]) -> Vec<ReciprocityIssue> {
+  let index = LinkIndex::from_records(records);
+  let by_uuid = |uuid: &str| records.iter().find(|record| record.uuid() == Some(uuid));
+  let mut issues = Issues::default();
+  for from in records {
+    let Some(from_uuid) = from.uuid() else {
+      continue;
+    };
+    for category in RelationshipKind::ALL {
+      for entry in from.relationships.get(category) {
+        let Some(to) = index
+          .resolve(entry)
+          .and_then(|target| by_uuid(&target.uuid))
+        else {
+          continue;
+        };
+        let Some(to_uuid) = to.uuid().filter(|_| to.id != from.id) else {
+          continue;
+        };
+        let back = links_to(&index, to, from_uuid);
+        let label = entry.relation.as_deref().and_then(FamilyLabel::parse);
+        if back.is_empty() {
+          let relation = match (category, &label) {
+            (RelationshipKind::Family, Some(label)) => {
+              Some(label.inverse().render(gender_of(from)))
+            }
+            (RelationshipKind::Family, None) => Some(String::new()),
+            _ => None,
+          };
+          issues.report(
+            format!("missing {} {}", from.id, category.key()),
+            ReciprocityIssue {
+              from: character_ref(from),
+              to: character_ref(to),
+              problem: ReciprocityProblem::MissingReciprocal { category },
+              fix: Some(ReciprocityFix::AddEntry {
+                record_id: to.id.clone(),
+                category,
+                entry: reciprocal_entry(from, relation),
+              }),
+            },
+          );
+          continue;
+        }
+        let from_categories = categories(&links_to(&index, from, to_uuid));
+        let to_categories = categories(&back);
+        if from_categories != to_categories {
+          let (low, high) = if from.id < to.id {
+            (&from_categories, &to_categories)
+          } else {
+            (&to_categories, &from_categories)
+          };
+          issues.report(
+            format!("categories {low:?} {high:?}"),
+            ReciprocityIssue {
+              from: character_ref(from),
+              to: character_ref(to),
+              problem: ReciprocityProblem::AsymmetricCategory {
+                from_categories,
+                to_categories,
+              },
+              fix: None,
+            },
+          );
+        }
+        let (Some(label), RelationshipKind::Family) = (label, category) else {
+          continue;
+        };
+        let expected = label.inverse();
+        for (_, back_entry) in back.iter().filter(|(kind, _)| *kind == category) {
+          let back_relation = back_entry.relation.as_deref().unwrap_or_default();
+          if FamilyLabel::parse(back_relation).as_ref() == Some(&expected) {
+            continue;
+          }
+          let mut entry_ids = [entry.id.as_str(), back_entry.id.as_str()];
+          entry_ids.sort();
+          issues.report(
+            format!("label {entry_ids:?}"),
+            ReciprocityIssue {
+              from: character_ref(from),
+              to: character_ref(to),
+              problem: ReciprocityProblem::FamilyLabelMismatch {
+                from_relation: entry.relation.clone().unwrap_or_default(),
+                to_relation: back_relation.to_string(),
+                expected: expected.accepted(),
+              },
+              fix: Some(ReciprocityFix::SetRelation {
+                record_id: to.id.clone(),
+                entry_id: back_entry.id.clone(),
+                relation: expected.render(gender_of(from)),
+              }),
+            },
+          );
+        }
+      }
+    }
+  }
+  issues.0.into_values().collect()
+}
+
+fn reciprocal_entry(target: &CharacterRecord, relation: Option<String>) -> RelationshipEntry {
+  let (name, portrait) = match &target.data {
+    Some(TabData::Sheet(snapshot)) => (
+      snapshot
+        .name()
+        .map_or_else(|| target.title.clone(), str::to_string),
+      snapshot.portrait.clone(),
+    ),
+    _ => (target.title.clone(), None),
+  };
+  RelationshipEntry {
+    id: Uuid::new_v4().to_string(),
+    name,
+    portrait,
+    relation,
+    source_file: None,
+    target_uuid: target.uuid().map(str::to_string),
+    added_at: now_millis(),
+  }
+}
+
+pub fn apply_fixes(
+  library: &Library,
+  fixes: &[ReciprocityFix],
+) -> Result<Vec<CharacterRecord>, String> {
+  let mut touched: Vec<CharacterRecord> = Vec::new();
+  for fix in fixes {
+    let record_id = match fix {
+      ReciprocityFix::AddEntry { record_id, .. }
+      | ReciprocityFix::SetRelation { record_id, .. } => record_id,
+    };
+    let position = match touched.iter().position(|record| &record.id == record_id) {
+      Some(position) => position,
+      None => {
+        touched.push(library.open(record_id)?);
+        touched.len() - 1
+      }
+    };
+    let record = &mut touched[position];
+    match fix {
+      ReciprocityFix::AddEntry {
+        category, entry, ..
+      } => {
+        let entries = record.relationships.get_mut(*category);
+        let linked = |existing: &RelationshipEntry| {
+          existing.target_uuid.is_some() && existing.target_uuid == entry.target_uuid
+        };
+        if !entries.iter().any(linked) {
+          entries.push(entry.clone());
+        }
+      }
+      ReciprocityFix::SetRelation {
+        entry_id, relation, ..
+      } => {
+        let mut found = false;
+        for kind in RelationshipKind::ALL {
+          for entry in record.relationships.get_mut(kind) {
+            if &entry.id == entry_id {
+              entry.relation = Some(relation.clone());
+              found = true;
+            }
+          }
+        }
+        if !found {
+          return Err(format!(
+            "{} no longer has that relationship entry.",
+            record.title
+          ));
+        }
+      }
+    }
+  }
+  touched
+    .into_iter()
+    .map(|record| library.save(record))
+    .collect()
+}
+
+#[tauri::command]
+pub fn reciprocity_lint(library: tauri::State<'_, Library>) -> Vec<ReciprocityIssue> {
+  lint(&library.records())
+}
+
+#[tauri::command]
+pub fn reciprocity_fix(
+  library: tauri::State<'_, Library>,
+  fixes: Vec<ReciprocityFix>,
+) -> Result<Vec<CharacterRecord>, String> {
+  apply_fixes(&library, &fixes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::CharacterSnapshot;
+
+  fn uuid(name: &str) -> String {
+    let bits = name.bytes().fold(0u64, |bits, b| bits << 8 | u64::from(b));
+    format!("00000000-0000-4000-8000-{bits:012x}")
+  }
+
+  fn sheet(id: &str, name: &str, gender: &str) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(uuid(name)),
+      ..Default::default()
+    };
+    snapshot.identity.insert("Name".into(), name.into());
+    snapshot.identity.insert("Gender".into(), gender.into());
+    CharacterRecord {
+      id: id.into(),
+      title: name.into(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..Default::default()
+    }
+  }
+
+  fn link(
+    record: &mut CharacterRecord,
+    category: RelationshipKind,
+    target: &CharacterRecord,
+    relation: Option<&str>,
+  ) {
+    let entries = record.relationships.get_mut(category);
+    entries.push(RelationshipEntry {
+      id: format!("{}-{}", record.title, entries.len()),
+      name: target.title.clone(),
+      relation: relation.map(str::to_string),
+      target_uuid: target.uuid().map(str::to_string),
+      ..Default::default()
+    });
+  }
+
+  // Each scenario runs with Ada on either side of the id order.
+  fn pair(ada_first: bool) -> (CharacterRecord, CharacterRecord) {
+    let (ada, bo) = if ada_first { ("a", "b") } else { ("b", "a") };
+    (sheet(ada, "Ada", "female"), sheet(bo, "Bo", "male"))
+  }
+
+  #[test]
+  fn missing_reciprocals_are_reported_from_the_side_that_lists() {
+    for ada_first in [true, false] {
+      let (mut ada, bo) = pair(ada_first);
+      link(&mut ada, RelationshipKind::Family, &bo, Some("son"));
+      let issues = lint(&[ada, bo]);
+      assert_eq!(issues.len(), 1);
+      assert_eq!(issues[0].from.name, "Ada");
+      let Some(ReciprocityFix::AddEntry {
+        category, entry, ..
+      }) = &issues[0].fix
+      else {
+        panic!("expected an entry to add");
+      };
+      assert_eq!(*category, RelationshipKind::Family);
+      assert_eq!(entry.relation.as_deref(), Some("mother"));
+      assert_eq!(entry.target_uuid.as_deref(), Some(uuid("Ada").as_str()));
+    }
+  }
+
+  #[test]
+  fn extra_categories_are_reported_once_from_either_side() {
+    for ada_first in [true, false] {
+      let (mut ada, mut bo) = pair(ada_first);
+      link(&mut ada, RelationshipKind::Love, &bo, None);
+      link(&mut bo, RelationshipKind::Love, &ada, None);
+      link(&mut bo, RelationshipKind::Hate, &ada, None);
+      let issues = lint(&[ada, bo]);
+      assert_eq!(issues.len(), 1, "ada first: {ada_first}");
+      let ReciprocityProblem::AsymmetricCategory {
+        from_categories,
+        to_categories,
+      } = &issues[0].problem
+      else {
+        panic!("expected an asymmetric pair");
+      };
+      let (ada_side, bo_side) = if issues[0].from.name == "Ada" {
+        (from_categories, to_categories)
+      } else {
+        (to_categories, from_categories)
+      };
+      assert_eq!(ada_side, &[RelationshipKind::Love]);
+      assert_eq!(bo_side, &[RelationshipKind::Love, RelationshipKind::Hate]);
+      assert!(issues[0].fix.is_none());
+    }
+  }
+
+  #[test]
+  fn mismatched_labels_are_found_when_only_one_side_parses() {
+    for ada_first in [true, false] {
+      let (mut ada, mut bo) = pair(ada_first);
+      link(&mut ada, RelationshipKind::Family, &bo, Some("son"));
+      link(&mut bo, RelationshipKind::Family, &ada, Some("favourite"));
+      let issues = lint(&[ada, bo.clone()]);
+      assert_eq!(issues.len(), 1, "ada first: {ada_first}");
+      assert_eq!(issues[0].from.name, "Ada");
+      let Some(ReciprocityFix::SetRelation {
+        record_id,
+        relation,
+        ..
+      }) = &issues[0].fix
+      else {
+        panic!("expected a relation to set");
+      };
+      assert_eq!(record_id, &bo.id);
+      assert_eq!(relation, "mother");
+    }
+  }
+
+  #[test]
+  fn labels_both_sides_disagree_on_are_reported_once() {
+    for ada_first in [true, false] {
+      let (mut ada, mut bo) = pair(ada_first);
+      link(&mut ada, RelationshipKind::Family, &bo, Some("father"));
+      link(&mut bo, RelationshipKind::Family, &ada, Some("father"));
+      let issues = lint(&[ada, bo]);
+      assert_eq!(issues.len(), 1);
+      assert_eq!(issues[0].from.id, "a");
+    }
+  }
+
+  #[test]
+  fn fixes_are_saved_and_leave_nothing_to_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let library = Library::new(dir.path().to_path_buf());
+    let (mut ada, mut bo) = pair(true);
+    let cy = sheet("c", "Cy", "");
+    link(&mut ada, RelationshipKind::Family, &bo, Some("son"));
+    link(&mut bo, RelationshipKind::Family, &ada, Some("aunt"));
+    link(&mut ada, RelationshipKind::Friends, &cy, None);
+    for record in [ada, bo, cy] {
+      library.save(record).unwrap();
+    }
+    let issues = lint(&library.records());
+    assert_eq!(issues.len(), 2);
+    let mut fixes: Vec<ReciprocityFix> = issues.into_iter().filter_map(|i| i.fix).collect();
+    // Applying the same addition twice adds one entry.
+    fixes.push(fixes[0].clone());
+    let touched = apply_fixes(&library, &fixes).unwrap();
+    assert_eq!(touched.len(), 2);
+    assert!(lint(&library.records()).is_empty());
+    let bo = library.open("b").unwrap();
+    assert_eq!(
+      bo.relationships.family[0].relation.as_deref(),
+      Some("mother")
+    );
+    let cy = library.open("c").unwrap();
+    assert_eq!(cy.relationships.friends.len(), 1);
+    assert_eq!(
+      cy.relationships.friends[0].target_uuid.as_deref(),
+      Some(uuid("Ada").as_str())
+    );
+
+    let stale = ReciprocityFix::SetRelation {
+      record_id: "b".into(),
+      entry_id: "gone".into(),
+      relation: "mother".into(),
+    };
+    assert!(apply_fixes(&library, &[stale]).is_err());
+  }
+}
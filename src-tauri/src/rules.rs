@@ -0,0 +1,571 @@
+use crate::character::{CharacterSnapshot, Section, SNAPSHOT_VERSION};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use uuid::Uuid;
+
+pub const SLIDER_MAX: u8 = 10;
+
+pub const IDENTITY_FIELDS: [&str; 8] = [
+  "Name",
+  "Nickname",
+  "Race/Species",
+  "Age",
+  "Gender",
+  "Birthday",
+  "Class/Job",
+  "Height",
+];
+
+// Other keys earlier sheets and imports used for the character's name; read wherever "Name" is.
+pub const NAME_ALIASES: [&str; 3] = ["name", "Character Name", "characterName"];
+
+const SNAPSHOT_KEYS: [&str; 12] = [
+  "version",
+  "uuid",
+  "identity",
+  "notes",
+  "body",
+  "skills",
+  "priorities",
+  "mind",
+  "social",
+  "portrait",
+  "relationships",
+  "relationshipSelection",
+];
+
+pub struct SectionRules {
+  pub section: Section,
+  pub cap: u32,
+  pub dots: u8,
+  pub stats: &'static [&'static str],
+  pub sliders: &'static [&'static str],
+  pub traits: &'static [&'static str],
+}
+
+pub const SECTION_RULES: [SectionRules; 5] = [
+  SectionRules {
+    section: Section::Body,
+    cap: 20,
+    dots: 6,
+    stats: &[
+      "Strength",
+      "Dexterity",
+      "Health",
+      "Energy",
+      "Beauty",
+      "Style",
+    ],
+    sliders: &[],
+    traits: &[],
+  },
+  SectionRules {
+    section: Section::Skills,
+    cap: 50,
+    dots: 4,
+    stats: &[
+      "Perception",
+      "Communication",
+      "Persuasion",
+      "Mediation",
+      "Literacy",
+      "Creativity",
+      "Cooking",
+      "Combat",
+      "Gardening",
+      "Dancing",
+      "Storytelling",
+      "Survival",
+      "Stealth",
+      "Tech Savvy",
+      "Street Smarts",
+      "Seduction",
+      "Luck",
+      "Artistry",
+      "Music",
+      "History",
+      "Animal Care",
+      "Child Care",
+    ],
+    sliders: &[],
+    traits: &[],
+  },
+  SectionRules {
+    section: Section::Priorities,
+    cap: 25,
+    dots: 4,
+    stats: &[
+      "Justice", "Truth", "Power", "Fame", "Wealth", "Family", "Friends", "Love", "Home", "Health",
+      "Approval",
+    ],
+    sliders: &[],
+    traits: &[],
+  },
+  SectionRules {
+    section: Section::Mind,
+    cap: 20,
+    dots: 5,
+    stats: &[
+      "Intelligence",
+      "Happiness",
+      "Spirituality",
+      "Confidence",
+      "Humor",
+      "Anxiety",
+      "Patience",
+      "Passion",
+    ],
+    sliders: &[
+      "Nice / Mean",
+      "Brave / Cowardly",
+      "Pacifist / Violent",
+      "Thoughtful / Impulsive",
+      "Agreeable / Contrary",
+      "Idealistic / Pragmatic",
+      "Frugal / Big Spender",
+      "Extrovert / Introvert",
+      "Collected / Wild",
+    ],
+    traits: &[
+      "Ambitious",
+      "Possessive",
+      "Stubborn",
+      "Jealous",
+      "Decisive",
+      "Perfectionist",
+    ],
+  },
+  SectionRules {
+    section: Section::Social,
+    cap: 15,
+    dots: 5,
+    stats: &[
+      "Charisma",
+      "Empathy",
+      "Generosity",
+      "Wealth",
+      "Aggression",
+      "Libido",
+    ],
+    sliders: &[
+      "Honest / Deceptive",
+      "Leader / Follower",
+      "Polite / Rude",
+      "Political / Indifferent",
+    ],
+    traits: &[
+      "Cool",
+      "Flirty",
+      "Cute",
+      "Obedient",
+      "Fun",
+      "Forgiving",
+      "Gullible",
+      "Scary",
+    ],
+  },
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RuleViolation {
+  #[serde(rename_all = "camelCase")]
+  UnsupportedVersion { version: u64, supported: u32 },
+  #[serde(rename_all = "camelCase")]
+  OverCap {
+    section: Section,
+    used: u64,
+    cap: u32,
+  },
+  #[serde(rename_all = "camelCase")]
+  StatOutOfRange {
+    section: Section,
+    stat: String,
+    value: f64,
+    max: u8,
+  },
+  #[serde(rename_all = "camelCase")]
+  SliderOutOfRange {
+    section: Section,
+    slider: String,
+    value: f64,
+    max: u8,
+  },
+  #[serde(rename_all = "camelCase")]
+  UnknownKey { path: String },
+  #[serde(rename_all = "camelCase")]
+  InvalidValue {
+    path: String,
+    expected: &'static str,
+  },
+}
+
+impl fmt::Display for RuleViolation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RuleViolation::UnsupportedVersion { version, supported } => write!(
+        f,
+        "Snapshot version {version} is newer than the supported version {supported}."
+      ),
+      RuleViolation::OverCap { section, used, cap } => write!(
+        f,
+        "{} uses {used} points but the cap is {cap}.",
+        section.key()
+      ),
+      RuleViolation::StatOutOfRange {
+        section,
+        stat,
+        value,
+        max,
+      } => write!(
+        f,
+        "{}.stats.{stat} is {value}, expected 0 to {max}.",
+        section.key()
+      ),
+      RuleViolation::SliderOutOfRange {
+        section,
+        slider,
+        value,
+        max,
+      } => write!(
+        f,
+        "{}.sliders.{slider} is {value}, expected 0 to {max}.",
+        section.key()
+      ),
+      RuleViolation::UnknownKey { path } => write!(f, "Unknown key {path}."),
+      RuleViolation::InvalidValue { path, expected } => {
+        write!(f, "{path} should be {expected}.")
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+  pub valid: bool,
+  pub violations: Vec<RuleViolation>,
+  pub messages: Vec<String>,
+}
+
+impl ValidationReport {
+  fn from_violations(violations: Vec<RuleViolation>) -> Self {
+    Self {
+      valid: violations.is_empty(),
+      messages: violations.iter().map(|v| v.to_string()).collect(),
+      violations,
+    }
+  }
+
+  pub fn into_result(self) -> Result<(), String> {
+    if self.valid {
+      Ok(())
+    } else {
+      Err(self.messages.join("\n"))
+    }
+  }
+}
+
+pub fn validate_value(value: &Value) -> ValidationReport {
+  let mut violations = Vec::new();
+  let Some(root) = value.as_object() else {
+    violations.push(RuleViolation::InvalidValue {
+      path: "snapshot".to_string(),
+      expected: "an object",
+    });
+    return ValidationReport::from_violations(violations);
+  };
+
+  for key in root.keys() {
+    if !SNAPSHOT_KEYS.contains(&key.as_str()) {
+      violations.push(RuleViolation::UnknownKey { path: key.clone() });
+    }
+  }
+
+  if let Some(version) = root.get("version") {
+    match version.as_u64() {
+      Some(version) if version > u64::from(SNAPSHOT_VERSION) => {
+        violations.push(RuleViolation::UnsupportedVersion {
+          version,
+          supported: SNAPSHOT_VERSION,
+        });
+      }
+      Some(_) => {}
+      None => violations.push(RuleViolation::InvalidValue {
+        path: "version".to_string(),
+        expected: "a whole number",
+      }),
+    }
+  }
+
+  if let Some(uuid) = root.get("uuid").filter(|uuid| !uuid.is_null()) {
+    if uuid
+      .as_str()
+      .and_then(|uuid| Uuid::parse_str(uuid).ok())
+      .is_none()
+    {
+      violations.push(RuleViolation::InvalidValue {
+        path: "uuid".to_string(),
+        expected: "a UUID",
+      });
+    }
+  }
+
+  if let Some(identity) = object_at(root, "identity", &mut violations) {
+    for (key, value) in identity {
+      let path = format!("identity.{key}");
+      if !IDENTITY_FIELDS.contains(&key.as_str()) && !NAME_ALIASES.contains(&key.as_str()) {
+        violations.push(RuleViolation::UnknownKey { path });
+      } else if !value.is_string() {
+        violations.push(RuleViolation::InvalidValue {
+          path,
+          expected: "text",
+        });
+      }
+    }
+  }
+
+  for rules in &SECTION_RULES {
+    if let Some(section) = object_at(root, rules.section.key(), &mut violations) {
+      validate_section(rules, section, &mut violations);
+    }
+  }
+
+  ValidationReport::from_violations(violations)
+}
+
+pub fn validate_snapshot(snapshot: &CharacterSnapshot) -> ValidationReport {
+  match serde_json::to_value(snapshot) {
+    Ok(value) => validate_value(&value),
+    Err(err) => ValidationReport::from_violations(vec![RuleViolation::InvalidValue {
+      path: format!("snapshot ({err})"),
+      expected: "serializable",
+    }]),
+  }
+}
+
+fn object_at<'a>(
+  root: &'a Map<String, Value>,
+  key: &str,
+  violations: &mut Vec<RuleViolation>,
+) -> Option<&'a Map<String, Value>> {
+  match root.get(key) {
+    None | Some(Value::Null) => None,
+    Some(Value::Object(map)) => Some(map),
+    Some(_) => {
+      violations.push(RuleViolation::InvalidValue {
+        path: key.to_string(),
+        expected: "an object",
+      });
+      None
+    }
+  }
+}
+
+fn validate_section(
+  rules: &SectionRules,
+  section: &Map<String, Value>,
+  violations: &mut Vec<RuleViolation>,
+) {
+  let name = rules.section.key();
+  let has_persona = !rules.sliders.is_empty() || !rules.traits.is_empty();
+  for key in section.keys() {
+    let known = key == "stats" || (has_persona && (key == "sliders" || key == "traits"));
+    if !known {
+      violations.push(RuleViolation::UnknownKey {
+        path: format!("{name}.{key}"),
+      });
+    }
+  }
+
+  let mut used = 0u64;
+  if let Some(stats) = object_at(section, "stats", violations) {
+    for (stat, value) in stats {
+      let path = format!("{name}.stats.{stat}");
+      if !rules.stats.contains(&stat.as_str()) {
+        violations.push(RuleViolation::UnknownKey { path });
+        continue;
+      }
+      let Some(number) = value.as_f64() else {
+        violations.push(RuleViolation::InvalidValue {
+          path,
+          expected: "a number",
+        });
+        continue;
+      };
+      if number.fract() != 0.0 || number < 0.0 || number > f64::from(rules.dots) {
+        violations.push(RuleViolation::StatOutOfRange {
+          section: rules.section,
+          stat: stat.clone(),
+          value: number,
+          max: rules.dots,
+        });
+        continue;
+      }
+      used += number as u64;
+    }
+  }
+  if used > u64::from(rules.cap) {
+    violations.push(RuleViolation::OverCap {
+      section: rules.section,
+      used,
+      cap: rules.cap,
+    });
+  }
+
+  if !has_persona {
+    return;
+  }
+  if let Some(sliders) = object_at(section, "sliders", violations) {
+    for (slider, value) in sliders {
+      let path = format!("{name}.sliders.{slider}");
+      if !rules.sliders.contains(&slider.as_str()) {
+        violations.push(RuleViolation::UnknownKey { path });
+        continue;
+      }
+      match value.as_f64() {
+        Some(number)
+          if number.fract() == 0.0 && (0.0..=f64::from(SLIDER_MAX)).contains(&number) => {}
+        Some(number) => violations.push(RuleViolation::SliderOutOfRange {
+          section: rules.section,
+          slider: slider.clone(),
+          value: number,
+          max: SLIDER_MAX,
+        }),
+        None => violations.push(RuleViolation::InvalidValue {
+          path,
+          expected: "a number",
+        }),
+      }
+    }
+  }
+  if let Some(traits) = object_at(section, "traits", violations) {
+    for (name_key, value) in traits {
+      let path = format!("{name}.traits.{name_key}");
+      if !rules.traits.contains(&name_key.as_str()) {
+        violations.push(RuleViolation::UnknownKey { path });
+      } else if !value.is_boolean() {
+        violations.push(RuleViolation::InvalidValue {
+          path,
+          expected: "true or false",
+        });
+      }
+    }
+  }
+}
+
+#[tauri::command]
+pub fn validate_character(snapshot: Value) -> ValidationReport {
+  validate_value(&snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn violations(value: Value) -> Vec<RuleViolation> {
+    validate_value(&value).violations
+  }
+
+  #[test]
+  fn accepts_a_sheet_at_the_cap() {
+    let report = validate_value(&json!({
+      "version": 2,
+      "identity": { "Name": "Ada", "Age": "30" },
+      "body": { "stats": { "Strength": 6, "Dexterity": 6, "Health": 6, "Energy": 2 } },
+      "mind": { "stats": {}, "sliders": { "Nice / Mean": 10 }, "traits": { "Stubborn": true } },
+    }));
+    assert!(report.valid, "{:?}", report.messages);
+  }
+
+  #[test]
+  fn rejects_a_section_over_its_cap() {
+    let found = violations(json!({
+      "body": { "stats": { "Strength": 6, "Dexterity": 6, "Health": 6, "Energy": 3 } },
+    }));
+    assert_eq!(
+      found,
+      vec![RuleViolation::OverCap {
+        section: Section::Body,
+        used: 21,
+        cap: 20,
+      }]
+    );
+  }
+
+  #[test]
+  fn rejects_stats_outside_their_dots() {
+    let found = violations(json!({
+      "skills": { "stats": { "Combat": 5, "Cooking": -1, "Luck": 1.5 } },
+    }));
+    assert_eq!(found.len(), 3);
+    assert!(found.iter().all(|violation| matches!(
+      violation,
+      RuleViolation::StatOutOfRange {
+        section: Section::Skills,
+        max: 4,
+        ..
+      }
+    )));
+  }
+
+  #[test]
+  fn out_of_range_stats_do_not_count_towards_the_cap() {
+    let found = violations(json!({ "social": { "stats": { "Charisma": 99 } } }));
+    assert_eq!(found.len(), 1);
+    assert!(matches!(found[0], RuleViolation::StatOutOfRange { .. }));
+  }
+
+  #[test]
+  fn rejects_sliders_outside_their_range() {
+    let found = violations(json!({
+      "social": { "sliders": { "Polite / Rude": 11, "Leader / Follower": 0 } },
+    }));
+    assert_eq!(
+      found,
+      vec![RuleViolation::SliderOutOfRange {
+        section: Section::Social,
+        slider: "Polite / Rude".to_string(),
+        value: 11.0,
+        max: SLIDER_MAX,
+      }]
+    );
+  }
+
+  #[test]
+  fn sections_without_sliders_reject_them() {
+    let found = violations(json!({ "body": { "sliders": {} } }));
+    assert_eq!(
+      found,
+      vec![RuleViolation::UnknownKey {
+        path: "body.sliders".to_string(),
+      }]
+    );
+  }
+
+  #[test]
+  fn accepts_the_name_aliases() {
+    for alias in NAME_ALIASES {
+      assert!(violations(json!({ "identity": { alias: "Ada" } })).is_empty());
+    }
+    assert_eq!(
+      violations(json!({ "identity": { "Surname": "Lovelace" } })),
+      vec![RuleViolation::UnknownKey {
+        path: "identity.Surname".to_string(),
+      }]
+    );
+  }
+
+  #[test]
+  fn rejects_newer_versions() {
+    assert_eq!(
+      violations(json!({ "version": SNAPSHOT_VERSION + 1 })),
+      vec![RuleViolation::UnsupportedVersion {
+        version: u64::from(SNAPSHOT_VERSION + 1),
+        supported: SNAPSHOT_VERSION,
+      }]
+    );
+  }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub fn enderfall_dir(app_data_dir: Option<PathBuf>) -> Result<PathBuf, String> {
+  app_data_dir
+    .map(|dir| dir.join("Enderfall"))
+    .ok_or_else(|| "App data directory is unavailable.".to_string())
+}
+
+// Distinguishes temp files written concurrently by this process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Writes beside `path` under a hidden name derived from the whole file name, so that nothing the
+// user keeps in the same folder (say `family.tmp` next to `family.svg`) is touched.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let name = path
+    .file_name()
+    .ok_or_else(|| format!("{} is not a file path.", path.display()))?;
+  let tmp = path.with_file_name(format!(
+    ".{}.{}-{}.tmp",
+    name.to_string_lossy(),
+    std::process::id(),
+    TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+  ));
+  let written = OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(&tmp)
+    .and_then(|mut file| {
+      file.write_all(bytes)?;
+      file.sync_all()
+    })
+    .and_then(|()| fs::rename(&tmp, path));
+  if let Err(e) = written {
+    let _ = fs::remove_file(&tmp);
+    return Err(e.to_string());
+  }
+  Ok(())
+}
+
+pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+  let raw = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+  write_atomic(path, &raw)
+}
+
+pub fn is_safe_id(id: &str) -> bool {
+  !id.is_empty()
+    && id.len() <= 128
+    && id
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn leaves_files_beside_the_target_alone() {
+    let scratch = tempfile::tempdir().unwrap();
+    let dir = scratch.path();
+    fs::write(dir.join("family.tmp"), "mine").unwrap();
+    write_atomic(&dir.join("family.svg"), b"<svg/>").unwrap();
+    write_atomic(&dir.join("family.svg"), b"<svg></svg>").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("family.tmp")).unwrap(), "mine");
+    assert_eq!(
+      fs::read_to_string(dir.join("family.svg")).unwrap(),
+      "<svg></svg>"
+    );
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 2);
+  }
+}
@@ -0,0 +1,733 @@
+use crate::entitlement::Entitlements;
+use crate::family::{Family, FamilyModel, Gender};
+use crate::graph::xml_escape;
+use crate::library::Library;
+use crate::portraits::{encode_data_url, is_portrait_hash, PortraitStore};
+use crate::storage::write_atomic;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const NODE_WIDTH: f64 = 140.0;
+const NODE_HEIGHT: f64 = 150.0;
+const NODE_GAP: f64 = 30.0;
+const ROW_GAP: f64 = 90.0;
+const MARGIN: f64 = 60.0;
+const PORTRAIT_SIZE: f64 = 88.0;
+const NAME_CHARS: usize = 18;
+// Largest stored thumbnail; the node draws portraits at PORTRAIT_SIZE.
+const EMBEDDED_THUMBNAIL: u32 = 128;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+  pub uuid: String,
+  pub name: String,
+  pub generation: usize,
+  // Top-left corner of the node's box.
+  pub x: f64,
+  pub y: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeLayout {
+  pub width: f64,
+  pub height: f64,
+  pub nodes: Vec<TreeNode>,
+  pub families: Vec<Family>,
+  // Family links the tree leaves out, such as step-relations or a third parent.
+  pub omitted: Vec<String>,
+}
+
+impl TreeLayout {
+  fn node(&self, uuid: &str) -> Option<&TreeNode> {
+    self.nodes.iter().find(|node| node.uuid == uuid)
+  }
+}
+
+// Children sit one row below their parents and partners share a row. Someone who married into
+// the family is pulled down next to their spouse rather than left in the top row.
+fn generations(model: &FamilyModel) -> BTreeMap<String, usize> {
+  let mut generation: BTreeMap<String, usize> = model
+    .people
+    .iter()
+    .map(|person| (person.uuid.clone(), 0))
+    .collect();
+  let has_parents: BTreeSet<&String> = model
+    .families
+    .iter()
+    .filter(|family| !family.partners.is_empty())
+    .flat_map(|family| &family.children)
+    .collect();
+  let get = |generation: &BTreeMap<String, usize>, uuid: &String| {
+    generation.get(uuid).copied().unwrap_or_default()
+  };
+  // Every pass only moves people down, so the loop settles; the bound covers parent cycles.
+  for _ in 0..=model.people.len() {
+    let mut changed = false;
+    for family in &model.families {
+      let mut row = family
+        .partners
+        .iter()
+        .map(|uuid| get(&generation, uuid))
+        .max()
+        .unwrap_or_default();
+      if let Some(first_child) = family
+        .children
+        .iter()
+        .map(|uuid| get(&generation, uuid))
+        .min()
+      {
+        if family
+          .partners
+          .iter()
+          .all(|uuid| !has_parents.contains(uuid))
+          && first_child > 0
+        {
+          row = row.max(first_child - 1);
+        }
+      }
+      for partner in &family.partners {
+        if get(&generation, partner) < row {
+          generation.insert(partner.clone(), row);
+          changed = true;
+        }
+      }
+      let child_row = if family.partners.is_empty() {
+        family
+          .children
+          .iter()
+          .map(|uuid| get(&generation, uuid))
+          .max()
+          .unwrap_or_default()
+      } else {
+        row + 1
+      };
+      for child in &family.children {
+        if get(&generation, child) < child_row {
+          generation.insert(child.clone(), child_row);
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      break;
+    }
+  }
+  generation
+}
+
+// Depth-first order keeps each couple together with their descendants beneath them. With
+// several marriages the first spouse goes to the left and the rest to the right.
+fn row_order(model: &FamilyModel, generation: &BTreeMap<String, usize>) -> Vec<Vec<String>> {
+  let rows = generation.values().max().map_or(0, |max| max + 1);
+  let mut order: Vec<Vec<String>> = vec![Vec::new(); rows];
+  let mut placed: BTreeSet<String> = BTreeSet::new();
+  let mut roots: Vec<&String> = model.people.iter().map(|person| &person.uuid).collect();
+  roots.sort_by_key(|uuid| generation[*uuid]);
+  for root in roots {
+    place(model, generation, root, &mut order, &mut placed);
+  }
+  order
+}
+
+fn place(
+  model: &FamilyModel,
+  generation: &BTreeMap<String, usize>,
+  uuid: &String,
+  order: &mut [Vec<String>],
+  placed: &mut BTreeSet<String>,
+) {
+  if placed.contains(uuid) {
+    return;
+  }
+  let unions: Vec<&Family> = model
+    .families
+    .iter()
+    .filter(|family| family.partners.contains(uuid))
+    .collect();
+  let spouses: Vec<&String> = unions
+    .iter()
+    .flat_map(|family| family.partners.iter().filter(|partner| *partner != uuid))
+    .filter(|spouse| !placed.contains(*spouse))
+    .collect();
+  let mut unit: Vec<&String> = Vec::new();
+  if spouses.len() > 1 {
+    unit.push(spouses[0]);
+    unit.push(uuid);
+    unit.extend(&spouses[1..]);
+  } else {
+    unit.push(uuid);
+    unit.extend(spouses);
+  }
+  for member in &unit {
+    if placed.insert((*member).clone()) {
+      order[generation[*member]].push((*member).clone());
+    }
+  }
+  for family in unions {
+    for child in &family.children {
+      place(model, generation, child, order, placed);
+    }
+  }
+  // Siblings without known parents stay next to each other.
+  for family in model
+    .families
+    .iter()
+    .filter(|family| family.partners.is_empty() && family.children.contains(uuid))
+  {
+    for sibling in &family.children {
+      place(model, generation, sibling, order, placed);
+    }
+  }
+}
+
+// Moves each node toward its target while keeping row order and spacing.
+fn settle(row: &[String], targets: &BTreeMap<&str, f64>, x: &mut BTreeMap<String, f64>) {
+  let spacing = NODE_WIDTH + NODE_GAP;
+  let mut previous: Option<f64> = None;
+  for uuid in row {
+    let mut next = targets.get(uuid.as_str()).copied().unwrap_or(x[uuid]);
+    if let Some(previous) = previous {
+      next = next.max(previous + spacing);
+    }
+    x.insert(uuid.clone(), next);
+    previous = Some(next);
+  }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+  let (sum, count) = values.fold((0.0, 0usize), |(sum, count), value| {
+    (sum + value, count + 1)
+  });
+  (count > 0).then(|| sum / count as f64)
+}
+
+pub fn layout(model: &FamilyModel) -> TreeLayout {
+  let generation = generations(model);
+  let order = row_order(model, &generation);
+  let spacing = NODE_WIDTH + NODE_GAP;
+  // Centre x of every node.
+  let mut x: BTreeMap<String, f64> = BTreeMap::new();
+  for row in &order {
+    for (index, uuid) in row.iter().enumerate() {
+      x.insert(uuid.clone(), index as f64 * spacing);
+    }
+  }
+
+  let center_parents = |x: &mut BTreeMap<String, f64>| {
+    for row in order.iter().rev() {
+      let mut targets: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+      for family in &model.families {
+        let Some(center) = mean(family.children.iter().map(|child| x[child])) else {
+          continue;
+        };
+        let mut partners: Vec<&String> = family
+          .partners
+          .iter()
+          .filter(|partner| row.contains(partner))
+          .collect();
+        partners.sort_by(|a, b| x[*a].total_cmp(&x[*b]));
+        let offset = (partners.len() as f64 - 1.0) / 2.0;
+        for (index, partner) in partners.into_iter().enumerate() {
+          let target = center + (index as f64 - offset) * spacing;
+          targets.entry(partner.as_str()).or_default().push(target);
+        }
+      }
+      let targets = targets
+        .into_iter()
+        .filter_map(|(uuid, values)| Some((uuid, mean(values.into_iter())?)))
+        .collect();
+      settle(row, &targets, x);
+    }
+  };
+  let center_children = |x: &mut BTreeMap<String, f64>| {
+    for row in order.iter().skip(1) {
+      let mut targets: BTreeMap<&str, f64> = BTreeMap::new();
+      for family in &model.families {
+        let Some(center) = mean(family.partners.iter().map(|partner| x[partner])) else {
+          continue;
+        };
+        let children: Vec<&String> = family
+          .children
+          .iter()
+          .filter(|child| row.contains(child))
+          .collect();
+        let offset = (children.len() as f64 - 1.0) / 2.0;
+        for (index, child) in children.into_iter().enumerate() {
+          targets.insert(child, center + (index as f64 - offset) * spacing);
+        }
+      }
+      settle(row, &targets, x);
+    }
+  };
+  for _ in 0..3 {
+    center_parents(&mut x);
+    center_children(&mut x);
+  }
+  center_parents(&mut x);
+
+  let left = x.values().copied().fold(f64::INFINITY, f64::min);
+  let right = x.values().copied().fold(f64::NEG_INFINITY, f64::max);
+  let shift = MARGIN + NODE_WIDTH / 2.0 - if left.is_finite() { left } else { 0.0 };
+  let mut nodes: Vec<TreeNode> = order
+    .iter()
+    .flatten()
+    .filter_map(|uuid| model.person(uuid))
+    .map(|person| {
+      let row = generation[&person.uuid];
+      TreeNode {
+        uuid: person.uuid.clone(),
+        name: person.name.clone(),
+        generation: row,
+        x: x[&person.uuid] + shift - NODE_WIDTH / 2.0,
+        y: MARGIN + row as f64 * (NODE_HEIGHT + ROW_GAP),
+      }
+    })
+    .collect();
+  nodes.sort_by_key(|node| node.generation);
+  let width = if right.is_finite() {
+    right - left + NODE_WIDTH + MARGIN * 2.0
+  } else {
+    MARGIN * 2.0
+  };
+  let height = order.len() as f64 * (NODE_HEIGHT + ROW_GAP) - ROW_GAP + MARGIN * 2.0;
+  TreeLayout {
+    width,
+    height: height.max(MARGIN * 2.0),
+    nodes,
+    families: model.families.clone(),
+    omitted: model
+      .omitted
+      .iter()
+      .map(|link| link.reason.clone())
+      .collect(),
+  }
+}
+
+fn gender_color(gender: Gender) -> &'static str {
+  match gender {
+    Gender::Male => "#4c78a8",
+    Gender::Female => "#e15759",
+    Gender::Unknown => "#8a8a8a",
+  }
+}
+
+fn short_name(name: &str) -> String {
+  if name.chars().count() <= NAME_CHARS {
+    return name.to_string();
+  }
+  let mut short: String = name.chars().take(NAME_CHARS - 1).collect();
+  short.push('…');
+  short
+}
+
+fn initials(name: &str) -> String {
+  name
+    .split_whitespace()
+    .filter_map(|word| word.chars().next())
+    .take(2)
+    .collect::<String>()
+    .to_uppercase()
+}
+
+pub fn render_svg(model: &FamilyModel, layout: &TreeLayout) -> String {
+  let mut out = String::new();
+  let _ = writeln!(
+    out,
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+    w = layout.width,
+    h = layout.height
+  );
+  out.push_str("  <style>text{font-family:'Segoe UI',Helvetica,Arial,sans-serif;fill:#222}.name{font-size:14px}.initials{font-size:28px;fill:#fff}.link{stroke:#777;stroke-width:2;fill:none}</style>\n");
+  let _ = writeln!(
+    out,
+    "  <rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>",
+    layout.width, layout.height
+  );
+
+  let mid = |node: &TreeNode| node.x + NODE_WIDTH / 2.0;
+  for family in &layout.families {
+    let partners: Vec<&TreeNode> = family
+      .partners
+      .iter()
+      .filter_map(|uuid| layout.node(uuid))
+      .collect();
+    let children: Vec<&TreeNode> = family
+      .children
+      .iter()
+      .filter_map(|uuid| layout.node(uuid))
+      .collect();
+    if let [a, b] = partners.as_slice() {
+      let (a, b) = if a.x <= b.x { (a, b) } else { (b, a) };
+      let y = a.y + PORTRAIT_SIZE / 2.0 + 10.0;
+      let _ = writeln!(
+        out,
+        "  <path class=\"link\" d=\"M{} {y} H{}\"/>",
+        a.x + NODE_WIDTH,
+        b.x
+      );
+    }
+    let Some(top) = children.iter().map(|child| child.y).reduce(f64::min) else {
+      continue;
+    };
+    let bus = top - ROW_GAP / 2.0;
+    let mut xs: Vec<f64> = children.iter().map(|child| mid(child)).collect();
+    let mut d = String::new();
+    match partners.as_slice() {
+      [] => {}
+      [only] => {
+        let _ = write!(d, "M{} {} V{bus} ", mid(only), only.y + NODE_HEIGHT);
+        xs.push(mid(only));
+      }
+      _ => {
+        let stem = mean(partners.iter().map(|partner| mid(partner))).unwrap_or_default();
+        let start = partners[0].y + PORTRAIT_SIZE / 2.0 + 10.0;
+        let _ = write!(d, "M{stem} {start} V{bus} ");
+        xs.push(stem);
+      }
+    }
+    let left = xs.iter().copied().fold(f64::INFINITY, f64::min);
+    let right = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let _ = write!(d, "M{left} {bus} H{right}");
+    for child in &children {
+      let _ = write!(d, " M{} {bus} V{}", mid(child), child.y);
+    }
+    let _ = writeln!(out, "  <path class=\"link\" d=\"{d}\"/>");
+  }
+
+  for (index, node) in layout.nodes.iter().enumerate() {
+    let Some(person) = model.person(&node.uuid) else {
+      continue;
+    };
+    let color = gender_color(person.gender);
+    let cx = NODE_WIDTH / 2.0;
+    let cy = 10.0 + PORTRAIT_SIZE / 2.0;
+    let radius = PORTRAIT_SIZE / 2.0;
+    let _ = writeln!(out, "  <g transform=\"translate({} {})\">", node.x, node.y);
+    let _ = writeln!(
+      out,
+      "    <rect width=\"{NODE_WIDTH}\" height=\"{NODE_HEIGHT}\" rx=\"10\" fill=\"#fafafa\" stroke=\"{color}\" stroke-width=\"2\"/>"
+    );
+    match person
+      .portrait
+      .as_deref()
+      .filter(|portrait| portrait.starts_with("data:image/"))
+    {
+      Some(portrait) => {
+        let _ = writeln!(
+          out,
+          "    <clipPath id=\"portrait-{index}\"><circle cx=\"{cx}\" cy=\"{cy}\" r=\"{radius}\"/></clipPath>"
+        );
+        let _ = writeln!(
+          out,
+          "    <image href=\"{}\" x=\"{}\" y=\"10\" width=\"{PORTRAIT_SIZE}\" height=\"{PORTRAIT_SIZE}\" preserveAspectRatio=\"xMidYMid slice\" clip-path=\"url(#portrait-{index})\"/>",
+          xml_escape(portrait),
+          cx - radius
+        );
+      }
+      None => {
+        let _ = writeln!(
+          out,
+          "    <circle cx=\"{cx}\" cy=\"{cy}\" r=\"{radius}\" fill=\"{color}\"/>"
+        );
+        let _ = writeln!(
+          out,
+          "    <text class=\"initials\" x=\"{cx}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+          cy + 10.0,
+          xml_escape(&initials(&person.name))
+        );
+      }
+    }
+    let _ = writeln!(
+      out,
+      "    <text class=\"name\" x=\"{cx}\" y=\"{}\" text-anchor=\"middle\"><title>{}</title>{}</text>",
+      NODE_HEIGHT - 22.0,
+      xml_escape(&person.name),
+      xml_escape(&short_name(&person.name))
+    );
+    out.push_str("  </g>\n");
+  }
+  out.push_str("</svg>\n");
+  out
+}
+
+// The whole library, or only the family connected to `root` when one is given.
+pub fn family_model(library: &Library, root: Option<&str>) -> Result<FamilyModel, String> {
+  let model = FamilyModel::build(&library.records());
+  match root {
+    Some(root) if model.person(root).is_none() => {
+      Err("That character is not in the library.".to_string())
+    }
+    Some(root) => Ok(model.component(root)),
+    None => Ok(model),
+  }
+}
+
+pub fn export_svg(
+  library: &Library,
+  portraits: &PortraitStore,
+  root: Option<&str>,
+  path: &Path,
+) -> Result<TreeLayout, String> {
+  write_svg(family_model(library, root)?, portraits, path)
+}
+
+fn write_svg(
+  mut model: FamilyModel,
+  portraits: &PortraitStore,
+  path: &Path,
+) -> Result<TreeLayout, String> {
+  // The SVG has to stand alone, so portraits are embedded, as thumbnails where one is stored.
+  for person in &mut model.people {
+    person.portrait = person
+      .portrait
+      .as_deref()
+      .and_then(|portrait| {
+        if is_portrait_hash(portrait) {
+          portraits.thumbnail(portrait, EMBEDDED_THUMBNAIL).ok()
+        } else {
+          portraits.bytes(portrait)
+        }
+      })
+      .map(|bytes| encode_data_url(&bytes));
+  }
+  let layout = layout(&model);
+  write_atomic(path, render_svg(&model, &layout).as_bytes())?;
+  Ok(layout)
+}
+
+// One SVG per separate family in the library, named after its eldest member, written into `dir`.
+pub fn export_all_svgs(
+  library: &Library,
+  portraits: &PortraitStore,
+  dir: &Path,
+) -> Result<Vec<(PathBuf, TreeLayout)>, String> {
+  let model = FamilyModel::build(&library.records());
+  let mut seen = BTreeSet::new();
+  let mut names = BTreeSet::new();
+  let mut written = Vec::new();
+  for person in &model.people {
+    if seen.contains(&person.uuid) {
+      continue;
+    }
+    let family = model.component(&person.uuid);
+    seen.extend(family.people.iter().map(|member| member.uuid.clone()));
+    // Characters with no family links are not a house of their own.
+    if family.families.is_empty() {
+      continue;
+    }
+    let generation = generations(&family);
+    let eldest = family
+      .people
+      .iter()
+      .min_by_key(|member| generation.get(&member.uuid))
+      .map_or("family", |member| member.name.as_str());
+    let stem = file_stem(eldest);
+    let mut name = format!("{stem} family.svg");
+    let mut copy = 1;
+    while !names.insert(name.clone()) {
+      copy += 1;
+      name = format!("{stem} family {copy}.svg");
+    }
+    let path = dir.join(name);
+    let layout = write_svg(family, portraits, &path)?;
+    written.push((path, layout));
+  }
+  Ok(written)
+}
+
+fn file_stem(name: &str) -> String {
+  let stem: String = name
+    .chars()
+    .map(|c| {
+      if c.is_alphanumeric() || c == ' ' || c == '-' {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect();
+  match stem.trim() {
+    "" => "Unnamed".to_string(),
+    stem => stem.to_string(),
+  }
+}
+
+#[tauri::command]
+pub fn tree_layout(
+  library: tauri::State<'_, Library>,
+  root: Option<String>,
+) -> Result<TreeLayout, String> {
+  Ok(layout(&family_model(&library, root.as_deref())?))
+}
+
+#[tauri::command]
+pub fn tree_export(
+  library: tauri::State<'_, Library>,
+  portraits: tauri::State<'_, PortraitStore>,
+  entitlements: tauri::State<'_, Entitlements>,
+  root: Option<String>,
+  path: String,
+) -> Result<TreeLayout, String> {
+  entitlements.require_premium()?;
+  export_svg(&library, &portraits, root.as_deref(), Path::new(&path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::character::{CharacterSnapshot, RelationshipEntry, TabData};
+  use crate::family::Person;
+  use crate::library::CharacterRecord;
+
+  fn person(uuid: &str) -> Person {
+    Person {
+      uuid: uuid.into(),
+      name: uuid.into(),
+      gender: Gender::Unknown,
+      birthday: None,
+      portrait: None,
+    }
+  }
+
+  fn family(partners: &[&str], children: &[&str]) -> Family {
+    Family {
+      partners: partners.iter().map(|uuid| uuid.to_string()).collect(),
+      children: children.iter().map(|uuid| uuid.to_string()).collect(),
+    }
+  }
+
+  fn model(people: &[&str], families: Vec<Family>) -> FamilyModel {
+    FamilyModel {
+      people: people.iter().map(|uuid| person(uuid)).collect(),
+      families,
+      omitted: Vec::new(),
+    }
+  }
+
+  fn rows(model: &FamilyModel) -> (BTreeMap<String, usize>, Vec<Vec<String>>) {
+    let generation = generations(model);
+    let order = row_order(model, &generation);
+    (generation, order)
+  }
+
+  #[test]
+  fn second_marriages_sit_on_the_other_side() {
+    let model = model(
+      &["al", "bea", "di", "cy", "ed"],
+      vec![
+        family(&["al", "bea"], &["cy"]),
+        family(&["al", "di"], &["ed"]),
+      ],
+    );
+    let (generation, order) = rows(&model);
+    assert_eq!(generation["al"], 0);
+    assert_eq!(generation["ed"], 1);
+    assert_eq!(order, [vec!["bea", "al", "di"], vec!["cy", "ed"]]);
+  }
+
+  #[test]
+  fn spouses_who_married_in_share_their_partners_row() {
+    let model = model(
+      &["gus", "hil", "al", "bea", "cy"],
+      vec![
+        family(&["gus", "hil"], &["al"]),
+        family(&["al", "bea"], &["cy"]),
+      ],
+    );
+    let (generation, order) = rows(&model);
+    assert_eq!(generation["bea"], 1);
+    assert_eq!(generation["cy"], 2);
+    assert_eq!(order, [vec!["gus", "hil"], vec!["al", "bea"], vec!["cy"]]);
+  }
+
+  #[test]
+  fn siblings_without_parents_stay_together() {
+    let model = model(
+      &["jo", "zed", "kim"],
+      vec![family(&[], &["jo", "kim"]), family(&["zed"], &[])],
+    );
+    let (generation, order) = rows(&model);
+    assert_eq!(generation["jo"], generation["kim"]);
+    assert_eq!(order, [vec!["jo", "kim", "zed"]]);
+  }
+
+  #[test]
+  fn parent_cycles_still_lay_out() {
+    let model = model(
+      &["a", "b"],
+      vec![family(&["a"], &["b"]), family(&["b"], &["a"])],
+    );
+    let (generation, order) = rows(&model);
+    assert_eq!(generation.len(), 2);
+    let placed: usize = order.iter().map(Vec::len).sum();
+    assert_eq!(placed, 2);
+    assert_eq!(layout(&model).nodes.len(), 2);
+  }
+
+  #[test]
+  fn names_are_escaped_in_the_svg() {
+    let mut model = model(&["a"], Vec::new());
+    model.people[0].name = r#"<Bo & "Cy">"#.into();
+    let svg = render_svg(&model, &layout(&model));
+    assert!(svg.contains("&lt;Bo &amp; &quot;Cy&quot;&gt;"));
+    assert!(!svg.contains("<Bo"));
+  }
+
+  fn uuid(index: u64) -> String {
+    format!("00000000-0000-4000-8000-{index:012x}")
+  }
+
+  fn sheet(index: u64, name: &str) -> CharacterRecord {
+    let mut snapshot = CharacterSnapshot {
+      uuid: Some(uuid(index)),
+      ..Default::default()
+    };
+    snapshot.identity.insert("Name".into(), name.into());
+    CharacterRecord {
+      id: format!("c{index}"),
+      title: name.into(),
+      data: Some(TabData::Sheet(Box::new(snapshot))),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn each_family_gets_its_own_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let library = Library::new(dir.path().join("library"));
+    let portraits = PortraitStore::new(dir.path().join("portraits"));
+    let parents = [(1, "Ada"), (3, "Ada"), (5, "Cy/Di")];
+    for (index, name) in parents {
+      library.save(sheet(index, name)).unwrap();
+      let mut child = sheet(index + 1, "Child");
+      child.relationships.family.push(RelationshipEntry {
+        id: format!("e{index}"),
+        name: name.into(),
+        relation: Some("mother".into()),
+        target_uuid: Some(uuid(index)),
+        ..Default::default()
+      });
+      library.save(child).unwrap();
+    }
+    library.save(sheet(9, "Loner")).unwrap();
+
+    let out = dir.path().join("out");
+    let written = export_all_svgs(&library, &portraits, &out).unwrap();
+    let mut names: Vec<String> = written
+      .iter()
+      .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+      .collect();
+    names.sort();
+    assert_eq!(
+      names,
+      ["Ada family 2.svg", "Ada family.svg", "Cy_Di family.svg"]
+    );
+    assert!(written
+      .iter()
+      .all(|(path, layout)| { path.exists() && layout.nodes.len() == 2 }));
+  }
+}
@@ -1,5 +1,6 @@
 import { useEffect, useMemo, useRef, useState, type ChangeEvent } from "react";
 import {
+  adoptCharacterUuid,
   applySheetSnapshot,
   getDefaultSnapshot,
   getSheetSnapshot,
@@ -7,16 +8,18 @@ import {
   initSheet,
   resetSectionPoints,
 } from "./sheet";
-import { open as openExternal } from "@tauri-apps/api/shell";
-import { convertFileSrc } from "@tauri-apps/api/tauri";
 import {
-  isEntitledForApp,
-  openAppBrowser,
-  readSharedPreferences,
-  refreshLaunchToken,
-  writeSharedPreferences,
-  type LaunchToken,
-} from "@enderfall/runtime";
+  PORTRAIT_THUMB_LARGE,
+  PORTRAIT_THUMB_SMALL,
+  inlinePortrait,
+  portraitSrc,
+  storePortrait,
+} from "./portraits";
+import { open as openDialog, save as saveDialog } from "@tauri-apps/api/dialog";
+import { open as openExternal } from "@tauri-apps/api/shell";
+import { listen } from "@tauri-apps/api/event";
+import { convertFileSrc, invoke } from "@tauri-apps/api/tauri";
+import { openAppBrowser, readSharedPreferences, writeSharedPreferences } from "@enderfall/runtime";
 import { AccessGate, Button, Dropdown, Input, MainHeader, Panel, PreferencesModal, Select, SideMenu, SideMenuSubmenu, Slider, StatDots, Textarea, Toggle, applyTheme, getStoredTheme } from "@enderfall/ui";
 
 type ThemeMode = "galaxy" | "atelier" | "system" | "light" | "plain-light" | "plain-dark";
@@ -89,6 +92,7 @@ type RelationshipEntry = {
   portrait: string | null;
   relation?: string;
   sourceFile?: string;
+  targetUuid?: string;
   addedAt: number;
 };
 
@@ -146,7 +150,7 @@ const createUntitledTitle = (index?: number) => {
 
 const createInitialTabs = () => {
   const fallbackId = createTabId();
-  if (typeof window !== "undefined") {
+  if (typeof window !== "undefined" && !isTauri) {
     try {
       const stored = window.localStorage.getItem(tabsStorageKey);
       if (stored) {
@@ -188,6 +192,177 @@ const createInitialTabs = () => {
   };
 };
 
+type ValidationReport = {
+  valid: boolean;
+  violations: { kind: string }[];
+  messages: string[];
+};
+
+type MigrationReport = {
+  snapshot: any;
+  blank: boolean;
+  fromVersion: number;
+  toVersion: number;
+  applied: { from: number; to: number; description: string }[];
+};
+
+const migrateSnapshot = async (data: any) => {
+  if (!isTauri) return data;
+  const report = await invoke<MigrationReport>("migrate_character", { snapshot: data });
+  if (report.applied.length) {
+    console.info(
+      `[Character Creation] migrated sheet v${report.fromVersion} -> v${report.toVersion}`,
+      report.applied
+    );
+  }
+  return report.snapshot;
+};
+
+const validateSnapshot = async (data: unknown) => {
+  if (!isTauri) return true;
+  const report = await invoke<ValidationReport>("validate_character", { snapshot: data });
+  if (!report.valid) {
+    window.alert(`This sheet breaks the character rules:\n\n${report.messages.join("\n")}`);
+  }
+  return report.valid;
+};
+
+const openFilesEvent = "open-files";
+const launchExitEvent = "launch-exit";
+
+type LaunchExit = { id: number; code: number | null; success: boolean };
+type HubOpened = { status: "focused" } | { status: "launched"; handle: { id: number; pid: number } };
+
+type Entitlement = {
+  allowed: boolean;
+  reason: "noToken" | "badSignature" | "wrongApp" | "expired" | null;
+  offline: boolean;
+  expiresAt: number | null;
+  displayName: string | null;
+  avatarUrl: string | null;
+  avatarPath: string | null;
+};
+
+const lockReasons: Record<NonNullable<Entitlement["reason"]>, string> = {
+  noToken: "no token found",
+  badSignature: "token signature is invalid",
+  wrongApp: "token is for another app",
+  expired: "token expired",
+};
+
+const describeEntitlement = (entitlement: Entitlement) => {
+  if (!entitlement.allowed) return lockReasons[entitlement.reason ?? "noToken"];
+  const expires = entitlement.expiresAt ? new Date(entitlement.expiresAt).toLocaleString() : "never";
+  return `${entitlement.offline ? "offline, " : ""}expires ${expires}`;
+};
+
+const launchErrorMessage = (err: unknown) =>
+  typeof err === "object" && err !== null && "message" in err ? String(err.message) : String(err);
+
+type LibraryRecord = SheetTab & { updatedAt: number };
+
+type RestoredSession = {
+  activeId: string | null;
+  tabs: LibraryRecord[];
+  skipped: string[];
+};
+
+const tabToRecord = (tab: SheetTab) => ({
+  id: tab.id,
+  title: tab.title,
+  data: tab.data,
+  relationships: tab.relationships,
+  relationshipSelection: tab.relationshipSelection,
+});
+
+const recordToTab = (record: LibraryRecord): SheetTab => ({
+  id: record.id,
+  title: record.title,
+  data: record.data ?? null,
+  relationships: normalizeRelationshipMap(record.relationships),
+  relationshipSelection: normalizeRelationshipSelection(record.relationshipSelection),
+});
+
+type ReciprocityIssue = {
+  from: { id: string; name: string };
+  to: { id: string; name: string };
+  fix: unknown | null;
+} & (
+  | { kind: "missingReciprocal"; category: RelationshipTab }
+  | { kind: "asymmetricCategory"; fromCategories: RelationshipTab[]; toCategories: RelationshipTab[] }
+  | { kind: "familyLabelMismatch"; fromRelation: string; toRelation: string; expected: string[] }
+);
+
+type RecoveredTab = {
+  tabId: string;
+  title: string | null;
+  journaledAt: number;
+  state: any;
+};
+
+const isPlainObject = (value: unknown): value is Record<string, any> =>
+  typeof value === "object" && value !== null && !Array.isArray(value);
+
+const createMergePatch = (previous: any, next: any): any => {
+  if (!isPlainObject(previous) || !isPlainObject(next)) {
+    return JSON.stringify(previous) === JSON.stringify(next) ? undefined : next;
+  }
+  const patch: Record<string, unknown> = {};
+  Object.keys(previous).forEach((key) => {
+    if (!(key in next)) patch[key] = null;
+  });
+  Object.keys(next).forEach((key) => {
+    const child = createMergePatch(previous[key], next[key]);
+    if (child !== undefined) patch[key] = child;
+  });
+  return Object.keys(patch).length ? patch : undefined;
+};
+
+const recoverJournaledTabs = async (tabs: SheetTab[]) => {
+  const pending = await invoke<RecoveredTab[]>("journal_recovery");
+  if (!pending.length) return tabs;
+  const restore = pending
+    .filter((entry) =>
+      window.confirm(
+        `Character Creation closed unexpectedly. Restore unsaved changes to "${
+          entry.title || "Untitled"
+        }" from ${new Date(entry.journaledAt).toLocaleString()}?`
+      )
+    )
+    .map((entry) => entry.tabId);
+  const recovered = await invoke<RecoveredTab[]>("journal_resolve_recovery", { restore });
+  const nextTabs = [...tabs];
+  recovered.forEach((entry) => {
+    const state = entry.state ?? {};
+    const index = nextTabs.findIndex((tab) => tab.id === entry.tabId);
+    const base: SheetTab =
+      index >= 0
+        ? nextTabs[index]
+        : {
+            id: entry.tabId,
+            title: entry.title || createUntitledTitle(nextTabs.length + 1),
+            data: null,
+            relationships: createRelationshipDefaults(),
+            relationshipSelection: createRelationshipSelectionDefaults(),
+          };
+    const restored: SheetTab = {
+      ...base,
+      title: typeof state.title === "string" && state.title ? state.title : base.title,
+      data: state.data ?? base.data,
+      relationships: normalizeRelationshipMap(state.relationships ?? base.relationships),
+      relationshipSelection: normalizeRelationshipSelection(
+        state.relationshipSelection ?? base.relationshipSelection
+      ),
+    };
+    if (index >= 0) {
+      nextTabs[index] = restored;
+    } else {
+      nextTabs.push(restored);
+    }
+  });
+  return nextTabs;
+};
+
 const getCharacterNameFromSnapshot = (data: any) => {
   if (data?.__blank) {
     return "";
@@ -203,8 +378,14 @@ export default function App() {
   const [tabs, setTabs] = useState<SheetTab[]>(initialTabs.tabs);
   const [activeTabId, setActiveTabId] = useState(initialTabs.activeId);
   const tabsRef = useRef<SheetTab[]>(initialTabs.tabs);
+  const linkRefreshTabRef = useRef<string | null>(null);
   const [sheetReady, setSheetReady] = useState(false);
+  const [libraryReady, setLibraryReady] = useState(!isTauri);
   const hasAppliedStoredTab = useRef(false);
+  const savedTabsRef = useRef(new Map<string, SheetTab>());
+  const journaledTabsRef = useRef(new Map<string, unknown>());
+  // Bundles handed over at launch while premium was unverified; opened once it is.
+  const heldBundlesRef = useRef<string[]>([]);
   const [menuOpen, setMenuOpen] = useState<"file" | "edit" | "view" | "help" | null>(null);
   const menuCloseRef = useRef<number | null>(null);
   const [preferencesOpen, setPreferencesOpen] = useState(false);
@@ -242,7 +423,8 @@ export default function App() {
   const [requestedBrowser, setRequestedBrowser] = useState(false);
   const [isPremium, setIsPremium] = useState(!isTauri);
   const [entitlementDebug, setEntitlementDebug] = useState("");
-  const [launchToken, setLaunchToken] = useState<LaunchToken | null>(null);
+  const [entitlement, setEntitlement] = useState<Entitlement | null>(null);
+  const hubLaunchRef = useRef<number | null>(null);
   const portraitInputRef = useRef<HTMLInputElement | null>(null);
 
   const cloneRelationshipMap = (map: Record<RelationshipTab, RelationshipEntry[]>) => ({
@@ -268,7 +450,39 @@ export default function App() {
   }, [tabs]);
 
   useEffect(() => {
-    if (!sheetReady || hasAppliedStoredTab.current) return;
+    if (!isTauri) return;
+    let active = true;
+    const legacyTabs = window.localStorage.getItem(tabsStorageKey);
+    invoke<RestoredSession>("library_restore_session", { legacyTabs })
+      .then(async (session) => {
+        if (!active) return;
+        window.localStorage.removeItem(tabsStorageKey);
+        if (session.skipped.length) {
+          window.alert(
+            `Some tabs from an earlier version could not be opened and were set aside:\n${session.skipped.join("\n")}`
+          );
+        }
+        const stored = session.tabs.map(recordToTab);
+        stored.forEach((tab) => savedTabsRef.current.set(tab.id, tab));
+        const restored = await recoverJournaledTabs(stored);
+        if (!active) return;
+        if (restored.length) {
+          updateTabsState(restored);
+          setActiveTabId(session.activeId ?? restored[0].id);
+        }
+        setLibraryReady(true);
+      })
+      .catch((err) => {
+        console.error("Failed to restore character library", err);
+        if (active) setLibraryReady(true);
+      });
+    return () => {
+      active = false;
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!sheetReady || !libraryReady || hasAppliedStoredTab.current) return;
     const activeTab = tabsRef.current.find((tab) => tab.id === activeTabId);
     applySheetSnapshot(activeTab?.data ?? null);
     setRelationshipMap(normalizeRelationshipMap(activeTab?.relationships));
@@ -276,10 +490,30 @@ export default function App() {
       normalizeRelationshipSelection(activeTab?.relationshipSelection)
     );
     hasAppliedStoredTab.current = true;
-  }, [activeTabId, sheetReady]);
+  }, [activeTabId, sheetReady, libraryReady]);
 
   useEffect(() => {
     if (typeof window === "undefined") return;
+    if (isTauri) {
+      if (!libraryReady) return;
+      const timer = window.setTimeout(() => {
+        tabs.forEach((tab) => {
+          if (savedTabsRef.current.get(tab.id) === tab) return;
+          savedTabsRef.current.set(tab.id, tab);
+          invoke<LibraryRecord>("library_save", { record: tabToRecord(tab) })
+            .then((saved) => {
+              const uuid = saved.data && "uuid" in saved.data ? saved.data.uuid : undefined;
+              if (tab.id === activeTabId) adoptCharacterUuid(uuid);
+            })
+            .catch((err) => console.error("Failed to save character", err));
+        });
+        invoke("library_save_session", {
+          activeId: activeTabId,
+          tabIds: tabs.map((tab) => tab.id),
+        }).catch(() => undefined);
+      }, 400);
+      return () => window.clearTimeout(timer);
+    }
     try {
       window.localStorage.setItem(
         tabsStorageKey,
@@ -291,7 +525,46 @@ export default function App() {
     } catch {
       // ignore storage failures
     }
-  }, [activeTabId, tabs]);
+  }, [activeTabId, tabs, libraryReady]);
+
+  useEffect(() => {
+    if (!isTauri || !sheetReady || !libraryReady) return;
+    let timer: number | null = null;
+    const journalActiveTab = () => {
+      const tab = tabsRef.current.find((item) => item.id === activeTabId);
+      const next = JSON.parse(
+        JSON.stringify({
+          title: tab?.title ?? "",
+          data: getSheetSnapshot(),
+          relationships: relationshipMap,
+          relationshipSelection,
+        })
+      );
+      const previous = journaledTabsRef.current.get(activeTabId);
+      const patch = previous === undefined ? next : createMergePatch(previous, next);
+      if (patch === undefined) return;
+      journaledTabsRef.current.set(activeTabId, next);
+      invoke("journal_append", {
+        tabId: activeTabId,
+        patch,
+        replace: previous === undefined,
+      }).catch((err) => console.error("Failed to journal changes", err));
+    };
+    const schedule = () => {
+      if (timer !== null) window.clearTimeout(timer);
+      timer = window.setTimeout(journalActiveTab, 300);
+    };
+    schedule();
+    document.addEventListener("input", schedule, true);
+    document.addEventListener("change", schedule, true);
+    document.addEventListener("click", schedule, true);
+    return () => {
+      if (timer !== null) window.clearTimeout(timer);
+      document.removeEventListener("input", schedule, true);
+      document.removeEventListener("change", schedule, true);
+      document.removeEventListener("click", schedule, true);
+    };
+  }, [activeTabId, sheetReady, libraryReady, relationshipMap, relationshipSelection]);
 
   useEffect(() => {
     if (!sheetReady) return;
@@ -444,24 +717,37 @@ export default function App() {
     setIsPremium(true);
   }, []);
 
+  useEffect(() => {
+    if (!isTauri || !sheetReady || !libraryReady || entitlementStatus === "checking") return;
+    let active = true;
+    let stopListening: (() => void) | null = null;
+    openLaunchFiles().catch(() => undefined);
+    listen(openFilesEvent, () => {
+      openLaunchFiles().catch(() => undefined);
+    }).then((stop) => {
+      if (active) {
+        stopListening = stop;
+      } else {
+        stop();
+      }
+    });
+    return () => {
+      active = false;
+      stopListening?.();
+    };
+  }, [sheetReady, libraryReady, entitlementStatus, isPremium]);
+
   const refreshEntitlement = async () => {
     if (!supportsHubAuth) {
       setEntitlementStatus("allowed");
       setIsPremium(true);
       return;
     }
-    const token = await refreshLaunchToken(appId);
-    console.log("[Character Creation] launch token", token);
-    setLaunchToken(token);
-    const allowed = isEntitledForApp(token, appId);
-    setEntitlementStatus(allowed ? "allowed" : "locked");
-    setIsPremium(allowed);
-    const now = Date.now();
-    const expires = token?.expiresAt ?? 0;
-    const debug = token
-      ? `token ${token.appId} exp ${new Date(expires).toLocaleString()} (${expires - now}ms)`
-      : "no token found";
-    setEntitlementDebug(debug);
+    const result = await invoke<Entitlement>("entitlement_refresh");
+    setEntitlement(result);
+    setEntitlementStatus(result.allowed ? "allowed" : "locked");
+    setIsPremium(result.allowed);
+    setEntitlementDebug(describeEntitlement(result));
   };
 
   useEffect(() => {
@@ -476,6 +762,44 @@ export default function App() {
     return () => window.clearInterval(interval);
   }, [supportsHubAuth]);
 
+  const openHub = async () => {
+    if (!isTauri) {
+      openAppBrowser(appId);
+      return;
+    }
+    try {
+      const opened = await invoke<HubOpened>("hub_open", { appId });
+      hubLaunchRef.current = opened.status === "launched" ? opened.handle.id : null;
+    } catch (err) {
+      window.alert(`Unable to open Enderfall Hub: ${launchErrorMessage(err)}`);
+    }
+  };
+
+  useEffect(() => {
+    if (!isTauri) return;
+    let active = true;
+    let stopListening: (() => void) | null = null;
+    listen<LaunchExit>(launchExitEvent, (event) => {
+      if (event.payload.id !== hubLaunchRef.current) return;
+      hubLaunchRef.current = null;
+      if (!event.payload.success) {
+        window.alert(
+          `Enderfall Hub exited unexpectedly (code ${event.payload.code ?? "unknown"}).`
+        );
+      }
+    }).then((stop) => {
+      if (active) {
+        stopListening = stop;
+      } else {
+        stop();
+      }
+    });
+    return () => {
+      active = false;
+      stopListening?.();
+    };
+  }, []);
+
   useEffect(() => {
     if (!supportsHubAuth) return;
     if (entitlementStatus !== "locked" || requestedBrowser) return;
@@ -513,13 +837,48 @@ export default function App() {
     setMenuOpen(null);
   };
 
-  const triggerExport = () => {
+  const inlineRelationships = async (map: Record<RelationshipTab, RelationshipEntry[]>) => {
+    const inlineList = (list: RelationshipEntry[]) =>
+      Promise.all(
+        list.map(async (entry) => ({ ...entry, portrait: await inlinePortrait(entry.portrait) }))
+      );
+    return {
+      family: await inlineList(map.family),
+      friends: await inlineList(map.friends),
+      love: await inlineList(map.love),
+      hate: await inlineList(map.hate),
+    };
+  };
+
+  const triggerExport = async () => {
     if (!isPremium) return;
     if (!sheetReady) return;
     const snapshot = getSheetSnapshot();
+    if (isTauri) {
+      setMenuOpen(null);
+      const path = await saveDialog({
+        defaultPath: "character-sheet.json",
+        filters: [{ name: "Character sheet", extensions: ["json"] }],
+      });
+      if (!path) return;
+      try {
+        await invoke("save_character", {
+          path,
+          snapshot: {
+            ...(snapshot ?? { version: 2 }),
+            relationships: relationshipMap,
+            relationshipSelection,
+          },
+        });
+      } catch (err) {
+        window.alert(`Unable to export character: ${String(err)}`);
+      }
+      return;
+    }
     const payload = {
       ...(snapshot ?? { version: 2 }),
-      relationships: relationshipMap,
+      portrait: await inlinePortrait(snapshot?.portrait),
+      relationships: await inlineRelationships(relationshipMap),
       relationshipSelection,
     };
     const blob = new Blob([JSON.stringify(payload, null, 2)], {
@@ -534,10 +893,11 @@ export default function App() {
     setMenuOpen(null);
   };
 
-  const triggerSave = () => {
+  const triggerSave = async () => {
     if (!isPremium) return;
     if (!sheetReady) return;
     const snapshot = getSheetSnapshot();
+    if (!(await validateSnapshot(snapshot))) return;
     const nextTabs = tabsRef.current.map((tab) =>
       tab.id === activeTabId
         ? {
@@ -552,6 +912,244 @@ export default function App() {
     setMenuOpen(null);
   };
 
+  const triggerCheckpoint = async () => {
+    if (!isTauri || !sheetReady) return;
+    const activeTab = tabsRef.current.find((tab) => tab.id === activeTabId);
+    if (!activeTab) return;
+    const label = window.prompt("Checkpoint name", activeTab.title);
+    if (label === null) return;
+    const snapshot = getSheetSnapshot();
+    if (!(await validateSnapshot(snapshot))) return;
+    const record = tabToRecord({
+      ...activeTab,
+      data: snapshot,
+      relationships: cloneRelationshipMap(relationshipMap),
+      relationshipSelection: { ...relationshipSelection },
+    });
+    try {
+      await invoke("history_checkpoint", { record, label });
+    } catch (err) {
+      window.alert(`Unable to save checkpoint: ${String(err)}`);
+    }
+    setMenuOpen(null);
+  };
+
+  const triggerBundleExport = async () => {
+    if (!isPremium || !isTauri || !sheetReady) return;
+    setMenuOpen(null);
+    const activeTab = tabsRef.current.find((tab) => tab.id === activeTabId);
+    if (!activeTab) return;
+    const path = await saveDialog({
+      defaultPath: `${activeTab.title || "character"}.ecc`,
+      filters: [{ name: "Character bundle", extensions: ["ecc"] }],
+    });
+    if (!path) return;
+    const record = tabToRecord({
+      ...activeTab,
+      data: getSheetSnapshot(),
+      relationships: cloneRelationshipMap(relationshipMap),
+      relationshipSelection: { ...relationshipSelection },
+    });
+    try {
+      const report = await invoke<{ unresolved: string[] }>("bundle_export", { record, path });
+      if (report.unresolved.length) {
+        window.alert(
+          `Bundle saved. These relationships have no sheet in the library and were left out:\n${report.unresolved.join("\n")}`
+        );
+      }
+    } catch (err) {
+      window.alert(`Unable to export bundle: ${String(err)}`);
+    }
+  };
+
+  const triggerPdfExport = async () => {
+    if (!isPremium || !isTauri || !sheetReady) return;
+    setMenuOpen(null);
+    const activeTab = tabsRef.current.find((tab) => tab.id === activeTabId);
+    const path = await saveDialog({
+      defaultPath: `${activeTab?.title || "character"}.pdf`,
+      filters: [{ name: "PDF", extensions: ["pdf"] }],
+    });
+    if (!path) return;
+    // Letter where it is the paper people have to hand, A4 everywhere else.
+    const page = /^(en-US|en-CA|es-MX|fr-CA)/.test(navigator.language) ? "letter" : "a4";
+    const snapshot = { ...(getSheetSnapshot() ?? { version: 2 }), relationships: relationshipMap };
+    try {
+      await invoke("export_pdf", { snapshot, path, page });
+    } catch (err) {
+      window.alert(`Unable to export PDF: ${String(err)}`);
+    }
+  };
+
+  const triggerBundleImport = async () => {
+    if (!isPremium || !isTauri) return;
+    setMenuOpen(null);
+    const path = await openDialog({
+      multiple: false,
+      filters: [{ name: "Character bundle", extensions: ["ecc"] }],
+    });
+    if (typeof path !== "string") return;
+    try {
+      const imported = await invoke<{ record: LibraryRecord }>("bundle_import", { path });
+      const nextTab = recordToTab(imported.record);
+      commitActiveTab();
+      updateTabsState([...tabsRef.current, nextTab]);
+      setActiveTabId(nextTab.id);
+      applySheetSnapshot(nextTab.data);
+      setRelationshipMap(nextTab.relationships);
+      setRelationshipSelection(nextTab.relationshipSelection);
+    } catch (err) {
+      window.alert(`Unable to open bundle: ${String(err)}`);
+    }
+  };
+
+  const triggerGraphExport = async () => {
+    if (!isPremium || !isTauri) return;
+    setMenuOpen(null);
+    const path = await saveDialog({
+      defaultPath: "relationships.gexf",
+      filters: [
+        { name: "Gephi graph", extensions: ["gexf"] },
+        { name: "Graphviz DOT", extensions: ["dot", "gv"] },
+      ],
+    });
+    if (!path) return;
+    const format = /\.(dot|gv)$/i.test(path) ? "dot" : "gexf";
+    try {
+      await invoke("graph_export", { format, path });
+    } catch (err) {
+      window.alert(`Unable to export relationship graph: ${String(err)}`);
+    }
+  };
+
+  const triggerTreeExport = async () => {
+    if (!isPremium || !isTauri || !sheetReady) return;
+    setMenuOpen(null);
+    const activeTab = tabsRef.current.find((tab) => tab.id === activeTabId);
+    const snapshot = getSheetSnapshot();
+    const root = typeof snapshot?.uuid === "string" ? snapshot.uuid : null;
+    const path = await saveDialog({
+      defaultPath: `${activeTab?.title || "family"} tree.svg`,
+      filters: [{ name: "SVG image", extensions: ["svg"] }],
+    });
+    if (!path) return;
+    try {
+      const exported = await invoke<{ omitted: string[] }>("tree_export", { root, path });
+      if (exported.omitted.length) {
+        window.alert(`The family tree leaves out:\n\n${exported.omitted.join("\n")}`);
+      }
+    } catch (err) {
+      window.alert(`Unable to export family tree: ${String(err)}`);
+    }
+  };
+
+  const triggerGedcomExport = async () => {
+    if (!isPremium || !isTauri) return;
+    setMenuOpen(null);
+    const path = await saveDialog({
+      defaultPath: "family.ged",
+      filters: [{ name: "GEDCOM", extensions: ["ged"] }],
+    });
+    if (!path) return;
+    try {
+      const exported = await invoke<{ omitted: string[] }>("gedcom_export", { path });
+      if (exported.omitted.length) {
+        window.alert(`The GEDCOM file leaves out:\n\n${exported.omitted.join("\n")}`);
+      }
+    } catch (err) {
+      window.alert(`Unable to export GEDCOM: ${String(err)}`);
+    }
+  };
+
+  const triggerGedcomImport = async () => {
+    if (!isPremium || !isTauri) return;
+    setMenuOpen(null);
+    const path = await openDialog({
+      multiple: false,
+      filters: [{ name: "GEDCOM", extensions: ["ged"] }],
+    });
+    if (typeof path !== "string") return;
+    try {
+      const imported = await invoke<{ records: LibraryRecord[] }>("gedcom_import", { path });
+      const nextTabs = imported.records.map(recordToTab);
+      if (!nextTabs.length) return;
+      commitActiveTab();
+      updateTabsState([...tabsRef.current, ...nextTabs]);
+      setActiveTabId(nextTabs[0].id);
+      applySheetSnapshot(nextTabs[0].data);
+      setRelationshipMap(nextTabs[0].relationships);
+      setRelationshipSelection(nextTabs[0].relationshipSelection);
+    } catch (err) {
+      window.alert(`Unable to import GEDCOM: ${String(err)}`);
+    }
+  };
+
+  const describeReciprocityIssue = (issue: ReciprocityIssue) => {
+    const from = issue.from.name || "Untitled";
+    const to = issue.to.name || "Untitled";
+    if (issue.kind === "missingReciprocal") {
+      return `${from} lists ${to} under ${issue.category}, but ${to} does not list ${from}.`;
+    }
+    if (issue.kind === "asymmetricCategory") {
+      return `${from} lists ${to} under ${issue.fromCategories.join(", ")}, but ${to} lists ${from} under ${issue.toCategories.join(", ")}.`;
+    }
+    return `${from} calls ${to} "${issue.fromRelation}", but ${to} calls ${from} "${issue.toRelation}" (expected ${issue.expected.join(" or ")}).`;
+  };
+
+  const triggerReciprocityCheck = async () => {
+    if (!isTauri || !sheetReady) return;
+    setMenuOpen(null);
+    commitActiveTab();
+    try {
+      await Promise.all(
+        tabsRef.current
+          .filter((tab) => savedTabsRef.current.get(tab.id) !== tab)
+          .map((tab) => {
+            savedTabsRef.current.set(tab.id, tab);
+            return invoke("library_save", { record: tabToRecord(tab) });
+          })
+      );
+      const issues = await invoke<ReciprocityIssue[]>("reciprocity_lint");
+      if (!issues.length) {
+        window.alert("Every relationship is reciprocated.");
+        return;
+      }
+      const fixes = issues
+        .filter(
+          (issue) =>
+            issue.fix &&
+            window.confirm(`${describeReciprocityIssue(issue)}\n\nFix this now?`)
+        )
+        .map((issue) => issue.fix);
+      const unfixable = issues.filter((issue) => !issue.fix);
+      if (unfixable.length) {
+        window.alert(
+          `These need fixing by hand:\n\n${unfixable.map(describeReciprocityIssue).join("\n")}`
+        );
+      }
+      if (!fixes.length) return;
+      const updated = await invoke<LibraryRecord[]>("reciprocity_fix", { fixes });
+      const reloaded = new Map(updated.map((record) => [record.id, recordToTab(record)]));
+      updateTabsState(
+        tabsRef.current.map((tab) => {
+          const next = reloaded.get(tab.id);
+          if (!next) return tab;
+          savedTabsRef.current.set(tab.id, next);
+          journaledTabsRef.current.delete(tab.id);
+          return next;
+        })
+      );
+      const active = reloaded.get(activeTabId);
+      if (active) {
+        applySheetSnapshot(active.data);
+        setRelationshipMap(active.relationships);
+        setRelationshipSelection(active.relationshipSelection);
+      }
+    } catch (err) {
+      window.alert(`Unable to check relationships: ${String(err)}`);
+    }
+  };
+
   const startNewSheet = () => {
     createNewTab();
     setMenuOpen(null);
@@ -562,11 +1160,10 @@ export default function App() {
     setMenuOpen(null);
   };
 
-  const displayName =
-    launchToken?.displayName || launchToken?.email?.split("@")[0] || "Account";
-  const rawAvatarUrl = launchToken?.avatarUrl ?? null;
-  const normalizedAvatarPath = launchToken?.avatarPath
-    ? launchToken.avatarPath.replace(/\\/g, "/")
+  const displayName = entitlement?.displayName || "Account";
+  const rawAvatarUrl = entitlement?.avatarUrl ?? null;
+  const normalizedAvatarPath = entitlement?.avatarPath
+    ? entitlement.avatarPath.replace(/\\/g, "/")
     : null;
   const canUseLocalAvatar =
     supportsHubAuth &&
@@ -616,6 +1213,18 @@ export default function App() {
     }
     setRelationshipMap(normalizeRelationshipMap(target?.relationships));
     setRelationshipSelection(normalizeRelationshipSelection(target?.relationshipSelection));
+    linkRefreshTabRef.current = tabId;
+    if (isTauri && target) {
+      invoke<{ relationships: Record<RelationshipTab, RelationshipEntry[]>; changed: boolean }>(
+        "links_refresh",
+        { relationships: target.relationships }
+      )
+        .then((result) => {
+          if (!result.changed || linkRefreshTabRef.current !== tabId) return;
+          setRelationshipMap(normalizeRelationshipMap(result.relationships));
+        })
+        .catch((err) => console.error("Failed to refresh relationship links", err));
+    }
     const nextTitle = getCharacterNameFromSnapshot(target?.data);
     if (nextTitle) {
       setTabs((prev) =>
@@ -674,6 +1283,10 @@ export default function App() {
     if (currentTabs.length <= 1) return;
     const nextTabs = currentTabs.filter((tab) => tab.id !== tabId);
     const wasActive = tabId === activeTabId;
+    if (isTauri) {
+      journaledTabsRef.current.delete(tabId);
+      invoke("journal_append", { tabId, patch: null, replace: true }).catch(() => undefined);
+    }
     setTabs(nextTabs);
     if (wasActive) {
       const removedIndex = currentTabs.findIndex((tab) => tab.id === tabId);
@@ -686,6 +1299,64 @@ export default function App() {
     }
   };
 
+  const openSnapshotTab = (data: any, fileName: string) => {
+    const nextId = createTabId();
+    const nameFromData = getCharacterNameFromSnapshot(data);
+    const nextTitle =
+      nameFromData ||
+      fileName.replace(/\.[^/.]+$/, "") ||
+      `Sheet ${tabsRef.current.length + 1}`;
+    commitActiveTab();
+    const nextTabs = [
+      ...tabsRef.current,
+      {
+        id: nextId,
+        title: nextTitle,
+        data,
+        relationships: normalizeRelationshipMap(data?.relationships),
+        relationshipSelection: normalizeRelationshipSelection(data?.relationshipSelection),
+      },
+    ];
+    updateTabsState(nextTabs);
+    setActiveTabId(nextId);
+    applySheetSnapshot(data);
+    setRelationshipMap(normalizeRelationshipMap(data?.relationships));
+    setRelationshipSelection(normalizeRelationshipSelection(data?.relationshipSelection));
+  };
+
+  const openLaunchFiles = async () => {
+    const taken = await invoke<string[]>("cli_take_pending");
+    const paths = [...heldBundlesRef.current, ...taken];
+    heldBundlesRef.current = [];
+    for (const path of paths) {
+      const fileName = path.split(/[\\/]/).pop() ?? path;
+      try {
+        if (/\.ecc$/i.test(path)) {
+          if (!isPremium) {
+            heldBundlesRef.current.push(path);
+            if (taken.includes(path)) {
+              window.alert(`Opening bundles requires premium. ${fileName} will open once it is verified.`);
+            }
+            continue;
+          }
+          const imported = await invoke<{ record: LibraryRecord }>("bundle_import", { path });
+          const nextTab = recordToTab(imported.record);
+          commitActiveTab();
+          updateTabsState([...tabsRef.current, nextTab]);
+          setActiveTabId(nextTab.id);
+          applySheetSnapshot(nextTab.data);
+          setRelationshipMap(nextTab.relationships);
+          setRelationshipSelection(nextTab.relationshipSelection);
+        } else {
+          const loaded = await invoke<{ snapshot: any }>("load_character", { path });
+          openSnapshotTab(loaded.snapshot, fileName);
+        }
+      } catch (err) {
+        window.alert(`Unable to open ${fileName}: ${String(err)}`);
+      }
+    }
+  };
+
   const handleImportTab = async (event: ChangeEvent<HTMLInputElement>) => {
     const files = event.target.files;
     if (!files || files.length === 0) {
@@ -694,29 +1365,9 @@ export default function App() {
     const file = files[0];
     try {
       const text = await file.text();
-      const data = JSON.parse(text);
-      const nextId = createTabId();
-      const nameFromData = getCharacterNameFromSnapshot(data);
-      const nextTitle =
-        nameFromData ||
-        file.name.replace(/\.[^/.]+$/, "") ||
-        `Sheet ${tabsRef.current.length + 1}`;
-      commitActiveTab();
-      const nextTabs = [
-        ...tabsRef.current,
-        {
-          id: nextId,
-          title: nextTitle,
-          data,
-          relationships: normalizeRelationshipMap(data?.relationships),
-          relationshipSelection: normalizeRelationshipSelection(data?.relationshipSelection),
-        },
-      ];
-      updateTabsState(nextTabs);
-      setActiveTabId(nextId);
-      applySheetSnapshot(data);
-      setRelationshipMap(normalizeRelationshipMap(data?.relationships));
-      setRelationshipSelection(normalizeRelationshipSelection(data?.relationshipSelection));
+      const data = await migrateSnapshot(JSON.parse(text));
+      if (!(await validateSnapshot(data))) return;
+      openSnapshotTab(data, file.name);
     } catch (err) {
       console.error("Invalid import file", err);
     } finally {
@@ -753,13 +1404,14 @@ export default function App() {
       const raw = await file.text();
       const data = JSON.parse(raw);
       const name = getRelationshipName(data, file.name);
-      const portrait = typeof data?.portrait === "string" ? data.portrait : null;
+      const portrait = await storePortrait(typeof data?.portrait === "string" ? data.portrait : null);
       return {
         id: makeRelationshipId(),
         name,
         portrait,
         relation: target === "family" ? "" : undefined,
         sourceFile: file.name,
+        targetUuid: typeof data?.uuid === "string" ? data.uuid : undefined,
         addedAt: Date.now(),
       } as RelationshipEntry;
     } catch {
@@ -832,7 +1484,7 @@ export default function App() {
     id: entry.id,
     label: entry.name,
     subtitle: entry.relation ?? undefined,
-    avatarUrl: entry.portrait ?? null,
+    avatarUrl: portraitSrc(entry.portrait, PORTRAIT_THUMB_SMALL),
     avatarFallback: entry.name.slice(0, 1).toUpperCase(),
     onClick: () => {
       setRelationshipTab("family");
@@ -847,7 +1499,7 @@ export default function App() {
   const friendsDropdownItems = relationshipMap.friends.map((entry) => ({
     id: entry.id,
     label: entry.name,
-    avatarUrl: entry.portrait ?? null,
+    avatarUrl: portraitSrc(entry.portrait, PORTRAIT_THUMB_SMALL),
     avatarFallback: entry.name.slice(0, 1).toUpperCase(),
     onClick: () => {
       setRelationshipTab("friends");
@@ -862,7 +1514,7 @@ export default function App() {
   const loveDropdownItems = relationshipMap.love.map((entry) => ({
     id: entry.id,
     label: entry.name,
-    avatarUrl: entry.portrait ?? null,
+    avatarUrl: portraitSrc(entry.portrait, PORTRAIT_THUMB_SMALL),
     avatarFallback: entry.name.slice(0, 1).toUpperCase(),
     onClick: () => {
       setRelationshipTab("love");
@@ -877,7 +1529,7 @@ export default function App() {
   const hateDropdownItems = relationshipMap.hate.map((entry) => ({
     id: entry.id,
     label: entry.name,
-    avatarUrl: entry.portrait ?? null,
+    avatarUrl: portraitSrc(entry.portrait, PORTRAIT_THUMB_SMALL),
     avatarFallback: entry.name.slice(0, 1).toUpperCase(),
     onClick: () => {
       setRelationshipTab("hate");
@@ -889,10 +1541,10 @@ export default function App() {
     },
     onDelete: () => removeRelationship("hate", entry.id),
   }));
-  const familyAvatar = relationshipMap.family[0]?.portrait ?? null;
-  const friendsAvatar = relationshipMap.friends[0]?.portrait ?? null;
-  const loveAvatar = relationshipMap.love[0]?.portrait ?? null;
-  const hateAvatar = relationshipMap.hate[0]?.portrait ?? null;
+  const familyAvatar = portraitSrc(relationshipMap.family[0]?.portrait, PORTRAIT_THUMB_SMALL);
+  const friendsAvatar = portraitSrc(relationshipMap.friends[0]?.portrait, PORTRAIT_THUMB_SMALL);
+  const loveAvatar = portraitSrc(relationshipMap.love[0]?.portrait, PORTRAIT_THUMB_SMALL);
+  const hateAvatar = portraitSrc(relationshipMap.hate[0]?.portrait, PORTRAIT_THUMB_SMALL);
 
   return (
     <div className="page">
@@ -900,7 +1552,7 @@ export default function App() {
         status={entitlementStatus}
         primaryLabel="Open Enderfall Hub"
         secondaryLabel="Retry"
-        onPrimary={() => openAppBrowser(appId)}
+        onPrimary={openHub}
         onSecondary={refreshEntitlement}
         primaryClassName="action-btn"
         secondaryClassName="action-btn"
@@ -941,6 +1593,79 @@ export default function App() {
                 >
                   Export...
                 </button>
+                {isTauri ? (
+                  <>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerBundleImport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Open bundle...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerBundleExport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Export bundle...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerPdfExport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Export PDF...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerGraphExport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Export relationship graph...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerTreeExport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Export family tree...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerGedcomImport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Import GEDCOM...
+                    </button>
+                    <button
+                      className="ef-menu-item"
+                      type="button"
+                      onClick={triggerGedcomExport}
+                      disabled={!isPremium}
+                      title={!isPremium ? "Premium required" : undefined}
+                    >
+                      Export GEDCOM...
+                    </button>
+                    <button className="ef-menu-item" type="button" onClick={triggerCheckpoint}>
+                      Save checkpoint...
+                    </button>
+                    <button className="ef-menu-item" type="button" onClick={triggerReciprocityCheck}>
+                      Check relationships...
+                    </button>
+                  </>
+                ) : null}
                 <div className="ef-menu-divider" />
                 <button className="ef-menu-item" type="button" onClick={startNewSheet}>
                   New sheet
@@ -1033,7 +1758,7 @@ export default function App() {
                 items={[
                   {
                     label: "Open Enderfall Hub",
-                    onClick: () => openAppBrowser(appId),
+                    onClick: openHub,
                     title: "Focuses Enderfall Hub if it's already open.",
                   },
                   {
@@ -1085,6 +1810,7 @@ export default function App() {
               <Panel variant="card" borderWidth={2} className="portrait-card">
                 <img className="portrait" id="portraitPreview" alt="portrait preview" />
                 <div className="portrait-placeholder" id="portraitPlaceholder">Upload Portrait</div>
+                <div className="portrait-note" id="portraitNote" />
                 <Button
                   type="button"
                   className="upload-chip"
@@ -1298,7 +2024,7 @@ export default function App() {
                       <div className="relationship-avatar">
                         {activeRelationship.portrait ? (
                           <img
-                            src={activeRelationship.portrait}
+                            src={portraitSrc(activeRelationship.portrait, PORTRAIT_THUMB_LARGE) ?? undefined}
                             alt={`${activeRelationship.name} portrait`}
                           />
                         ) : (
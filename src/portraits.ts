@@ -0,0 +1,61 @@
+import { convertFileSrc, invoke } from "@tauri-apps/api/tauri";
+
+const isTauri = typeof window !== "undefined" && "__TAURI_IPC__" in window;
+
+// Stored portraits are referenced by the SHA-256 of their bytes; older sheets still carry data URLs.
+export const isPortraitHash = (value: string | null | undefined): value is string =>
+  typeof value === "string" && /^[0-9a-f]{64}$/.test(value);
+
+// Thumbnail sizes the backend generates for every stored portrait.
+export const PORTRAIT_THUMB_SMALL = 64;
+export const PORTRAIT_THUMB_LARGE = 128;
+
+export type PortraitCrop = { x: number; y: number; width: number; height: number };
+
+export const portraitSrc = (value: string | null | undefined, size?: number) => {
+  if (!value) return null;
+  if (!isPortraitHash(value)) return value;
+  const src = convertFileSrc(value, "portrait");
+  return size ? `${src}?size=${size}` : src;
+};
+
+export type PortraitMetadata = "exif" | "location" | "camera" | "xmp" | "iptc";
+
+export type StoredPortrait = {
+  hash: string;
+  metadata: { removed: PortraitMetadata[]; rotated: boolean };
+};
+
+// The backend rotates, crops, resizes and re-encodes the image, dropping its metadata.
+export const importPortrait = async (dataUrl: string, crop?: PortraitCrop) => {
+  if (!isTauri || !dataUrl.startsWith("data:image/")) return null;
+  return invoke<StoredPortrait>("portrait_store", { dataUrl, crop: crop ?? null });
+};
+
+export const storePortrait = async (value: string | null) => {
+  if (!value) return value;
+  return (await importPortrait(value))?.hash ?? value;
+};
+
+const sensitiveMetadata: Partial<Record<PortraitMetadata, string>> = {
+  location: "location",
+  camera: "camera details",
+};
+
+export const describeRemovedMetadata = (stored: StoredPortrait | null) => {
+  const removed = stored?.metadata.removed ?? [];
+  if (!removed.length) return "";
+  const sensitive = removed.flatMap((kind) => sensitiveMetadata[kind] ?? []);
+  const blocks = removed.filter((kind) => !sensitiveMetadata[kind]).map((kind) => kind.toUpperCase());
+  return `Removed ${[...sensitive, `${blocks.join("/")} metadata`].join(", ")}.`;
+};
+
+// Files written outside the app must carry the image itself.
+export const inlinePortrait = async (value: string | null | undefined) => {
+  if (!isTauri || !isPortraitHash(value)) return value ?? null;
+  try {
+    return await invoke<string>("portrait_data_url", { hash: value });
+  } catch {
+    return null;
+  }
+};
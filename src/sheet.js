@@ -1,4 +1,7 @@
+import { describeRemovedMetadata, importPortrait, portraitSrc } from "./portraits";
+
 let portraitData = "";
+let characterUuid = "";
 let defaultsSnapshot = null;
 const pointsCapBySection = {
   body: 20,
@@ -483,10 +486,18 @@ const buildDefaultSnapshot = () => {
   };
 };
 
+const setPortraitNote = (text) => {
+  const note = document.getElementById("portraitNote");
+  if (note) {
+    note.textContent = text;
+  }
+};
+
 const resetToDefaults = () => {
   if (!defaultsSnapshot) return;
   applySnapshot(defaultsSnapshot);
   portraitData = "";
+  setPortraitNote("");
   const preview = document.getElementById("portraitPreview");
   const placeholder = document.getElementById("portraitPlaceholder");
   if (preview) {
@@ -529,6 +540,7 @@ const hardResetSheet = () => {
     });
   }
   portraitData = "";
+  setPortraitNote("");
   const preview = document.getElementById("portraitPreview");
   const placeholder = document.getElementById("portraitPlaceholder");
   if (preview) {
@@ -637,12 +649,21 @@ export const getSheetSnapshot = () => {
   const state = getState();
   return {
     version: 2,
+    ...(characterUuid ? { uuid: characterUuid } : {}),
     ...state,
     portrait: portraitData || null
   };
 };
 
+// The library hands out identities when a sheet is first saved; until then the sheet has none.
+export const adoptCharacterUuid = (uuid) => {
+  if (!characterUuid && typeof uuid === "string") {
+    characterUuid = uuid;
+  }
+};
+
 export const applySheetSnapshot = (data) => {
+  characterUuid = typeof data?.uuid === "string" ? data.uuid : "";
   if (!data) {
     hardResetSheet();
     return;
@@ -666,12 +687,13 @@ export const applySheetSnapshot = (data) => {
     hardResetSheet();
     applySnapshot(data);
   }
+  setPortraitNote("");
   if (data.portrait) {
     portraitData = data.portrait;
     const preview = document.getElementById("portraitPreview");
     const placeholder = document.getElementById("portraitPlaceholder");
     if (preview) {
-      preview.src = portraitData;
+      preview.src = portraitSrc(portraitData);
     }
     if (placeholder) {
       placeholder.style.display = "none";
@@ -709,9 +731,17 @@ export function initSheet() {
         return;
       }
       const reader = new FileReader();
-      reader.onload = () => {
-        portraitData = reader.result;
-        portraitPreview.src = portraitData;
+      reader.onload = async () => {
+        let stored;
+        try {
+          stored = await importPortrait(reader.result);
+        } catch (error) {
+          window.alert(`Could not store portrait: ${error}`);
+          return;
+        }
+        portraitData = stored?.hash ?? reader.result;
+        portraitPreview.src = portraitSrc(portraitData);
+        setPortraitNote(describeRemovedMetadata(stored));
         portraitPlaceholder.style.display = "none";
       };
       reader.readAsDataURL(file);